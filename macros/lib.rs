@@ -15,6 +15,7 @@ extern crate rustfmt;
 mod parse;
 mod table;
 mod output;
+mod query;
 
 define_proc_macros! {
     #[allow(non_snake_case)]
@@ -86,6 +87,12 @@ define_proc_macros! {
     }
 }
 
+/// Loops over joined tables. See `v11::query` for details.
+#[proc_macro]
+pub fn query(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    ::query::query(input)
+}
+
 
 
 use std::fmt::Display;
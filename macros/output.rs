@@ -3,7 +3,7 @@ use std::io::Write;
 use quote::{Ident, Tokens};
 use syntex_syntax::print::pprust as pp;
 
-use super::table::{Table, TableKind};
+use super::table::{Table, TableKind, IndexKind};
 
 /// Convert a string into a quote `Ident`.
 fn i<S: AsRef<str>>(s: S) -> Ident {
@@ -80,9 +80,18 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
     let COL_TYPE_STR: &Vec<_> = &table.cols.iter()
         .map(|x| {
             let ct = pp::ty_to_string(&*x.colty);
+            // `#[journal]` finds out about edits the same way `#[track_edits]` does.
+            let ct = if x.track_edits || table.journal {
+                format!("EditTracked<{}>", ct)
+            } else {
+                ct
+            };
             if x.indexed {
-                // FIXME: More index types
-                format!("Col<BTreeIndex<{}, Row>, Row>", ct)
+                let index = match x.index_kind {
+                    IndexKind::BTree => "BTreeIndex",
+                    IndexKind::Hash => "HashIndex",
+                };
+                format!("Col<{}<{}, Row>, Row>", index, ct)
             } else {
                 format!("Col<{}, Row>", ct)
             }
@@ -122,6 +131,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         }
     };
     //: bool = table.sort_key.is_some();
+    let GET_REMAPPING = quote_if(table.kind == Some(::table::TableKind::Bag), quote! {
+        fn get_remapping() -> bool { true }
+    });
     out! { ["Imports"] {
         // These imports are messy.
         // This is difficult to cleanup because sections can't import their own requirements,
@@ -137,7 +149,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         use self::v11::index::{Checkable, CheckedIter};
         use self::v11::intern::{self, BiRef, GenerativeIter, MaybeBorrow};
         use self::v11::joincore::*;
-        use self::v11::map_index::BTreeIndex;
+        use self::v11::par::{ParRows, ParRowsMut};
+        use self::v11::map_index::{BTreeIndex, HashIndex, UniqueViolation};
+        use self::v11::edits::{EditTracked, TrackEdits};
         use self::v11::tables::*;
         use self::v11::tracking::{Flush, GetParam, GuardedFlush, Select, SelectAny, SelectOwned, SelectRows, Tracker};
 
@@ -209,6 +223,12 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         /// Creates an index into the `i`th row.
         pub fn at(i: #ROW_ID_TYPE) -> RowId { RowId::new(i) }
     }}
+    out! { table.kind == Some(TableKind::Indirect) => ["Handles"] {
+        /// A generational handle to a row. Unlike a `RowId`, it stays attached to its row when
+        /// other rows are deleted, and stops working when its own row is deleted.
+        pub type Handle = v11::handle::GenericHandle<Row>;
+        impl CheckId for Handle {}
+    };}
 
     let ROW_DERIVES: &Vec<_> = &table.row_derive.iter()
         .map(pp::meta_list_item_to_string)
@@ -235,6 +255,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             fn get_domain() -> DomainName { TABLE_DOMAIN }
             fn get_name() -> TableName { TABLE_NAME }
             fn get_guarantee() -> Guarantee { GUARANTEES }
+            #GET_REMAPPING
             fn get_generic_table(universe: &Universe) -> &RwLock<GenericTable> {
                 RowId::get_generic_table(universe)
             }
@@ -283,6 +304,154 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         }
     };
+    // Loading a snapshot isn't a change, so it isn't journaled.
+    let JOURNAL_PAUSE = quote_if(table.journal, quote! {
+        let mut table = table;
+        table.journal_edits();
+        let journal = ::std::mem::replace(&mut table._table.journal, Default::default());
+    });
+    let JOURNAL_RESUME = quote_if(table.journal, quote! {
+        {
+            let mut table = write(universe);
+            table.journal_edits();
+            table._table.journal = journal;
+        }
+    });
+    let RESTORER = if table.save {
+        quote! {
+            fn get_restorer(&self) -> Option<Restorer> {
+                fn restore(
+                    universe: &Universe,
+                    deserializer: &mut self::v11::erased_serde::Deserializer,
+                    event: Event,
+                ) -> Result<(), String> {
+                    let extraction: Extraction = self::v11::erased_serde::deserialize(deserializer)
+                        .map_err(|e| e.to_string())?;
+                    let table = write(universe);
+                    #JOURNAL_PAUSE
+                    let ret = table
+                        .restore_extract_ids(universe, extraction, event, true)
+                        .map_err(|e| e.to_string());
+                    #JOURNAL_RESUME
+                    ret
+                }
+                Some(restore)
+            }
+        }
+    } else {
+        quote! {
+            fn get_restorer(&self) -> Option<Restorer> { None }
+        }
+    };
+    let DELTA_FUNCTIONS = if table.journal {
+        quote! {
+            fn get_delta_functions(&self) -> Option<(DeltaSaver, Restorer)> {
+                fn save(universe: &Universe) -> Box<PendingDelta> {
+                    Box::new(write(universe).extract_delta())
+                }
+                fn apply(
+                    universe: &Universe,
+                    deserializer: &mut self::v11::erased_serde::Deserializer,
+                    _event: Event,
+                ) -> Result<(), String> {
+                    let delta: Delta = self::v11::erased_serde::deserialize(deserializer)
+                        .map_err(|e| e.to_string())?;
+                    write(universe)
+                        .apply_delta(delta)
+                        .map_err(|e| e.to_string())
+                }
+                Some((save, apply))
+            }
+        }
+    } else {
+        quote! {
+            fn get_delta_functions(&self) -> Option<(DeltaSaver, Restorer)> { None }
+        }
+    };
+    let per_col = |t: Tokens| -> Vec<Tokens> { table.cols.iter().map(|_| t.clone()).collect() };
+    let REFLECT_DEBUG: &Vec<Tokens> = &per_col(if table.derive.debug {
+        quote! { Some({
+            fn get<'a>(col: &'a AnyCol, i: usize) -> &'a ::std::fmt::Debug {
+                let col: &CT = col.downcast_ref().unwrap();
+                col.inner().checked_index(i)
+            }
+            get
+        }) }
+    } else {
+        quote! { None }
+    });
+    let REFLECT_SERIALIZE: &Vec<Tokens> = &per_col(if table.save {
+        quote! { Some({
+            fn get<'a>(col: &'a AnyCol, i: usize) -> &'a self::v11::erased_serde::Serialize {
+                let col: &CT = col.downcast_ref().unwrap();
+                col.inner().checked_index(i)
+            }
+            get
+        }) }
+    } else {
+        quote! { None }
+    });
+    // Sorted tables and composite indexes would fall out of order. Unique columns would go unchecked.
+    let REFLECT_SET: &Vec<Tokens> = &table.cols.iter().map(|col| {
+        let in_ci = table.composite_indexes.iter().any(|ci| ci.cols.iter().any(|c| *c == col.name.to_string()));
+        if !table.save || table.sorted || in_ci || col.unique {
+            return quote! { None };
+        }
+        let element = i(pp::ty_to_string(&*col.element));
+        quote! { Some({
+            fn set(
+                col: &mut AnyCol,
+                i: usize,
+                deserializer: &mut self::v11::erased_serde::Deserializer,
+            ) -> Result<(), self::v11::erased_serde::Error> {
+                let col: &mut CT = col.downcast_mut().unwrap();
+                let mut element: #element = self::v11::erased_serde::deserialize(deserializer)?;
+                unsafe { col.inner_mut().unchecked_swap_out(i, &mut element); }
+                Ok(())
+            }
+            set
+        }) }
+    }).collect();
+    let REPAIRER = if table.consistent {
+        quote! {
+            fn get_repairer(&self) -> Option<fn(&Universe)> {
+                fn repair(universe: &Universe) {
+                    let flush = write(universe)._table.flush.clone();
+                    let interrupted = flush.read().unwrap().take_interrupted();
+                    if let Some(interrupted) = interrupted {
+                        let event = interrupted.event;
+                        let changes = flush.read().unwrap().resume(universe, interrupted);
+                        if event.is_removal {
+                            let mut table = write(universe);
+                            match changes {
+                                Select::All => table.clear_raw(),
+                                Select::These(rows) => for row in rows {
+                                    unsafe { table.delete_raw(row.to_usize()); }
+                                },
+                            }
+                        }
+                    }
+                }
+                Some(repair)
+            }
+        }
+    } else {
+        quote! {
+            fn get_repairer(&self) -> Option<fn(&Universe)> { None }
+        }
+    };
+    // Only tables with `#[unique]` columns can fail to restore for any reason but a mismatch.
+    let RESTORE_ERR = if table.has_unique() {
+        quote! { RestoreError }
+    } else {
+        quote! { &'static str }
+    };
+    let RESTORE_MISMATCH = quote_if(table.has_unique(), quote! {
+        .map_err(|e| match e {
+            RestoreError::Mismatch(msg) => msg,
+            RestoreError::Unique(_) => "a restored row duplicates a #[unique] column",
+        })
+    });
     let SERIAL_EXTRACT_IMPL = quote_if(table.save, quote! {
         impl SerialExtraction for Row {
             type Extraction = self::Extraction;
@@ -293,16 +462,61 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
 
             fn restore(universe: &Universe, extraction: Self::Extraction, event: Event) -> Result<(), &'static str> {
-                write(universe).restore_extract(universe, extraction, event)
+                write(universe).restore_extract(universe, extraction, event)#RESTORE_MISMATCH
             }
         }
     });
 
+    let IS_INDIRECT = table.kind == Some(TableKind::Indirect);
+    let TABLE_INDIRECT_FIELDS = quote_if(IS_INDIRECT, quote! {
+        handles: v11::handle::Handles<Row>,
+    });
+
+    // #[index(name = "...", cols = "...")]
+    let HAS_CI = !table.composite_indexes.is_empty();
+    let CI_NAME: &Vec<_> = &table.composite_indexes.iter()
+        .map(|x| i(x.name.clone()))
+        .collect();
+    let CI_NAME2 = CI_NAME;
+    let CI_NAME3 = CI_NAME;
+    let CI_KEY: &Vec<_> = &table.composite_indexes.iter()
+        .map(|x| {
+            let elements: Vec<_> = x.cols.iter()
+                .map(|c| table.cols.iter().find(|col| col.name.to_string() == *c).unwrap())
+                .map(|col| i(pp::ty_to_string(&*col.element)))
+                .collect();
+            quote! { (#(#elements,)*) }
+        })
+        .collect();
+    let CI_KEY_OF_ROW: &Vec<_> = &table.composite_indexes.iter()
+        .map(|x| {
+            let cols: Vec<_> = x.cols.iter().map(|c| i(c.clone())).collect();
+            quote! { (#(*self.#cols.deref().inner().checked_index(i),)*) }
+        })
+        .collect();
+    let CI_KEY_OF_ROW2 = CI_KEY_OF_ROW;
+    let ci_hook = |method: &str, arg: Tokens| quote_if(HAS_CI, {
+        let method = i(method);
+        quote! { self.#method(#arg); }
+    });
+    let UNINDEX_I = ci_hook("unindex_row", quote! { i });
+    let INDEX_I = ci_hook("index_row", quote! { i });
+    let UNINDEX_LEN1 = ci_hook("unindex_row", quote! { len1 });
+    let REINDEX = quote_if(HAS_CI, quote! { self.reindex(); });
+    let TABLE_CI_FIELDS = quote! {
+        #(#CI_NAME: v11::map_index::CompositeIndex<#CI_KEY, Row>,)*
+    };
+    let TABLE_JOURNAL_FIELDS = quote_if(table.journal, quote! {
+        journal: v11::journal::Journal<Row>,
+    });
     out! { ["The `Table` struct"] {
         #[derive(Default)]
         pub struct Table {
             flush: GuardedFlush<Row>,
             free: FreeList<Row>,
+            #TABLE_INDIRECT_FIELDS
+            #TABLE_JOURNAL_FIELDS
+            #TABLE_CI_FIELDS
         }
         impl TTable for Table {
             fn new() -> Self where Self: Sized { Default::default() }
@@ -323,6 +537,13 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
 
             #SAVE_EXTRACTION
+            #RESTORER
+            #DELTA_FUNCTIONS
+            #REPAIRER
+
+            fn is_free(&self, i: usize) -> bool {
+                self.free.contains_key(&RowId::from_usize(i))
+            }
 
             fn generic_select(
                 &self,
@@ -437,6 +658,37 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                         }
                         col.truncate(bad);
                     })*
+                    #REINDEX
+                }
+            }
+        };
+        table.kind == Some(TableKind::Bag) || table.kind == Some(TableKind::List) || table.kind == Some(TableKind::Indirect) => ["`Table` bag, list, or indirect"] {
+            impl Table {
+                pub fn remove_rows(universe: &Universe, event: Event, rows: SelectAny) {
+                    let mut table = write(universe);
+                    match rows {
+                        Select::These(rows) => {
+                            if let Some(rows) = rows.downcast::<RowId>() {
+                                table.remove_rows(rows);
+                            } else {
+                                panic!("wrong rows type");
+                            }
+                        },
+                        Select::All => table.clear(),
+                    }
+                    table.flush(universe, event);
+                }
+            }
+            impl<'u> Write<'u> {
+                pub fn remove_rows(&mut self, to_remove: &[RowId]) {
+                    // Going from the back to the front means the rows that get moved into the
+                    // holes are never rows that are yet to be removed.
+                    let mut to_remove = to_remove.to_vec();
+                    to_remove.sort();
+                    to_remove.dedup();
+                    for row in to_remove.into_iter().rev() {
+                        self.delete(row);
+                    }
                 }
             }
         };
@@ -461,7 +713,14 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
     };};
 
     let COL_MUT: &Vec<_> = &table.cols.iter()
-        .map(|x| if x.indexed { "EditA" } else { "MutA" })
+        .map(|x| if x.unique || table.in_composite_index(x) || (x.indexed && Some(x.name) == table.sort_key) {
+            // Changing these would break uniqueness, or the order of the table.
+            "KeyA"
+        } else if x.indexed {
+            "EditA"
+        } else {
+            "MutA"
+        })
         .map(i)
         .collect();
 
@@ -470,9 +729,34 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             self._table.free.get(&idx).is_some()
         }
     });
+    // Rows removed from a list are still there until the next `compact`.
+    let LOCKED_TABLE_REMOVED_ROW = quote_if(table.kind == Some(TableKind::List), quote! {
+        fn is_deleted(&self, idx: GenericRowId<Row>) -> bool {
+            self._removed.contains(&idx)
+        }
+    });
+    let LOCKED_TABLE_RESOLVE_HANDLE = quote_if(IS_INDIRECT, quote! {
+        fn resolve_handle(&self, handle: Handle) -> Option<RowId> {
+            self._table.handles.resolve(handle)
+        }
+    });
     let DERIVE_SERDE = quote_if(table.save, quote! {
         #[derive(Serialize, Deserialize)]
     });
+    let IS_BAG = table.kind == Some(TableKind::Bag);
+    let WRITE_BAG_FIELDS = quote_if(IS_BAG, quote! {
+        /// Maps the current position of each moved row to its position when the table was
+        /// last flushed. This is `None` if nobody is tracking the table.
+        _moved: Option<::std::collections::HashMap<RowId, RowId>>,
+    });
+    let IS_LIST = table.kind == Some(TableKind::List);
+    if IS_LIST && !table.derive.clone { panic!("#[kind = \"list\"] requires #[row_derive(Clone)]"); }
+    let WRITE_LIST_FIELDS = quote_if(IS_LIST, quote! {
+        /// Rows to be removed by the next `compact`.
+        _removed: ::std::collections::BTreeSet<RowId>,
+        /// Rows to be inserted by the next `compact`, before the keyed row.
+        _inserts: ::std::collections::BTreeMap<RowId, Vec<Row>>,
+    });
     out! { ["Table locks"] {
         /**
          * The table, locked for reading.
@@ -491,9 +775,13 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             _lock: ::std::sync::RwLockWriteGuard<'u, GenericTable>,
             _table: &'u mut Table,
             _changes: SelectOwned<Row>,
+            /// The parts of `_changes` that were pushed, so that a panic can undo them.
+            _push_runs: Vec<::std::ops::Range<usize>>,
             _pushed: bool,
             _delete: bool,
-            // '#COL_MUT' is either MutA or EditA
+            #WRITE_BAG_FIELDS
+            #WRITE_LIST_FIELDS
+            // '#COL_MUT' is MutA, EditA, or KeyA
             #(pub #COL_NAME: #COL_MUT<'u, #COL_TYPE>,)*
         }
 
@@ -527,6 +815,19 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                     }, EditIter::new(me3.row_range(), me3._table.free.keys()))
                 }
             }
+
+            /// Like `editing`, but the rows can be visited in parallel, zipped with the `Edit`'s
+            /// columns. Requires v11's `rayon` feature; see `v11::par`.
+            pub fn par_iter_mut<'w>(&'w mut self) -> (Edit<'u, 'w>, ParRowsMut<'w, Row>)
+            where 'u: 'w
+            {
+                let len = self.len();
+                // As with `editing`, the `Edit` can't make structural changes, so the free list
+                // stays put.
+                let free: &'w FreeList<Row> = unsafe { &*(&self._table.free as *const FreeList<Row>) };
+                let (edit, _) = self.editing();
+                (edit, ParRowsMut::new(len, free))
+            }
         }
         // By good fortune this is safe. Implementing DerefMut would *not* be safe.
         // Suppose we use Deref to call `Write::get_row_ref(edit)`. Can we mutably alias
@@ -544,11 +845,14 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             type Row = Row;
             fn len(&self) -> usize { self.len() }
             #LOCKED_TABLE_DELETED_ROW
+            #LOCKED_TABLE_RESOLVE_HANDLE
         }
         impl<'u> LockedTable for Write<'u> {
             type Row = Row;
             fn len(&self) -> usize { self.len() }
             #LOCKED_TABLE_DELETED_ROW
+            #LOCKED_TABLE_REMOVED_ROW
+            #LOCKED_TABLE_RESOLVE_HANDLE
             fn delete_row(&mut self, i: RowId) { self.delete(i) }
         }
 
@@ -573,6 +877,19 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             #(pub #COL_NAME: #COL_TYPE2,)*
         }
         impl Extraction {
+            /// The `ColumnMeta`s of the table as it is now.
+            pub fn current_columns() -> Vec<ColumnMeta> {
+                vec![
+                    #(
+                        ColumnMeta {
+                            name: Cow::Borrowed(#COL_NAME_STR),
+                            stored_type_name: Cow::Borrowed(column_format::#COL_NAME),
+                            version: #COL_VERSION,
+                        },
+                    )*
+                ]
+            }
+
             fn validate(&self) -> Result<(), &'static str> {
                 // Validate
                 if self.extraction_fmt != EXTRACTION_FMT {
@@ -664,7 +981,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         }
     });
     out! {
-        table.kind == Some(TableKind::Bag) => ["iter bag"] {
+        table.kind == Some(TableKind::Bag) || table.kind == Some(TableKind::Indirect) => ["iter bag"] {
             impl<'u> Read<'u> {
                 /// Returns a pre-checking iterator over each row in the table.
                 pub fn iter(&self) -> CheckedIter<Self> {
@@ -798,9 +1115,27 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         }
 
+        /// Returns a parallel iterator over each row in the table, skipping deleted rows.
+        /// Requires v11's `rayon` feature; see `v11::par`. (R/W)
+        pub fn par_iter(&self) -> ParRows<Self> {
+            ParRows::new(self)
+        }
+
         /// Explicitly drop the lock. (R/W)
         pub fn close(self) { /* You are not expected to understand this. */ }
     };
+    let RW_FUNCTIONS_INDIRECT = quote_if(IS_INDIRECT, quote! {
+        /// Returns the row a handle refers to, or `None` if the row has been deleted. (R/W)
+        pub fn resolve(&self, handle: Handle) -> Option<RowId> {
+            self._table.handles.resolve(handle)
+        }
+
+        /// Returns the handle of a row. (R/W)
+        pub fn handle<R: Checkable<Row=Row>>(&self, index: R) -> Handle {
+            let index = index.check(self);
+            self._table.handles.handle(index.to_usize())
+        }
+    });
     let RW_FUNCTIONS = if table.consistent { RW_FUNCTIONS_CONSISTENT } else { RW_FUNCTIONS_INCONSISTENT };
     out! { ["methods common to both Read and Write"] {
         // We're only repeating ourselves twice here.
@@ -808,15 +1143,18 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         impl<'u> Read<'u> {
             #RW_FUNCTIONS
             #RW_FUNCTIONS_BOTH
+            #RW_FUNCTIONS_INDIRECT
         }
         impl<'u> Write<'u> {
             #RW_FUNCTIONS
             #RW_FUNCTIONS_BOTH
+            #RW_FUNCTIONS_INDIRECT
 
             // FIXME: add `fn iter()` that returns an iterator yielding MaybeDeleted things.
         }
     }};
 
+    let JOURNAL_DELETED = quote_if(table.journal, quote! { self._table.journal.deleted(RowId::from_usize(i)); });
     out! {
         table.consistent => ["Change tracking"] {
             impl<'a> Write<'a> {
@@ -827,10 +1165,12 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
 
                 unsafe fn delete_raw(&mut self, i: usize) {
+                    #UNINDEX_I
                     #(
                         self.#COL_NAME.deref_mut().inner_mut().deleted(i);
                     )*
                     self._table.free.insert(RowId::from_usize(i), ());
+                    #JOURNAL_DELETED
                 }
 
                 /*
@@ -841,10 +1181,46 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 */
             }
 
+            impl<'a> Write<'a> {
+                /// Undoes the pushes that haven't been flushed. The deletions haven't happened yet,
+                /// so they're simply forgotten.
+                fn rollback_unflushed(&mut self) {
+                    use std::mem;
+                    let changes = mem::replace(&mut self._changes, Select::These(vec![]));
+                    let runs = mem::replace(&mut self._push_runs, Vec::new());
+                    let changes = match changes {
+                        Select::These(rows) => rows,
+                        Select::All => return,
+                    };
+                    let mut rows: Vec<RowId> = runs
+                        .into_iter()
+                        .flat_map(|run| changes[run].to_vec())
+                        .collect();
+                    rows.sort();
+                    for row in rows.into_iter().rev() {
+                        let i = row.to_usize();
+                        if i + 1 == self.len() {
+                            self.truncate(i);
+                        } else {
+                            unsafe { self.delete_raw(i); }
+                        }
+                    }
+                }
+            }
+
             /// Makes sure the flush requirement has been acknowledged
             impl<'a> Drop for Write<'a> {
                 fn drop(&mut self) {
+                    if ::std::thread::panicking() {
+                        // This poisons the lock, whether or not an earlier poisoning was repaired.
+                        self._lock.set_repaired(false);
+                    }
                     if !self._changes.as_slice().is_empty() {
+                        if ::std::thread::panicking() {
+                            // Panicking again would abort.
+                            self.rollback_unflushed();
+                            return;
+                        }
                         panic!("Changes to {} were not flushed", TABLE_NAME);
                     }
                 }
@@ -852,20 +1228,80 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         };
         table.kind == Some(TableKind::Bag) => ["bag delete"] {
             impl<'a> Write<'a> {
+                /// Removes a row immediately, by moving the last row into its place.
                 pub fn delete<I: CheckId>(&mut self, row: I) {
-                    // We don't actually delete the row here! The tracker'll manage.
                     let i = row.check(self).uncheck();
+                    let last = self.last().unwrap();
                     unsafe { self.delete_raw(i.to_usize()) }
+                    let original = match self._moved {
+                        // Nobody is watching.
+                        None => return,
+                        Some(ref mut moved) => {
+                            // Trackers only know rows by where they were at the last flush.
+                            let original = moved.remove(&i).unwrap_or(i);
+                            if i != last {
+                                let last_original = moved.remove(&last).unwrap_or(last);
+                                moved.insert(i, last_original);
+                            }
+                            original
+                        },
+                    };
+                    if original != INVALID {
+                        self.event_del(original);
+                    }
+                }
+                unsafe fn delete_raw(&mut self, i: usize) {
+                    let len = self.len();
+                    let len1 = len - 1;
+                    #UNINDEX_I
+                    if i < len1 {
+                        #UNINDEX_LEN1
+                        #(self.#COL_NAME.deref_mut().inner_mut().unchecked_swap(i, len1);)*
+                        #INDEX_I
+                    }
+                    #(
+                        self.#COL_NAME.deref_mut().inner_mut().truncate(len1);
+                    )*
+                }
+            }
+        };
+        table.kind == Some(TableKind::Indirect) => ["indirect delete"] {
+            impl<'a> Write<'a> {
+                /// Removes a row immediately, by moving the last row into its place.
+                /// Handles to the moved row remain valid; handles to the removed row become stale.
+                pub fn delete<I: CheckId>(&mut self, row: I) {
+                    let i = row.check(self).to_usize();
+                    unsafe { self.delete_raw(i) }
+                }
+                /// Like `delete`, but finds the row by its handle. Panics if the handle is stale.
+                pub fn delete_handle(&mut self, handle: Handle) {
+                    let i = handle.check(self).to_usize();
+                    unsafe { self.delete_raw(i) }
                 }
                 unsafe fn delete_raw(&mut self, i: usize) {
                     let len = self.len();
                     let len1 = len - 1;
+                    #UNINDEX_I
                     if i < len1 {
+                        #UNINDEX_LEN1
                         #(self.#COL_NAME.deref_mut().inner_mut().unchecked_swap(i, len1);)*
+                        #INDEX_I
                     }
                     #(
                         self.#COL_NAME.deref_mut().inner_mut().truncate(len1);
                     )*
+                    self._table.handles.swap_remove(i);
+                }
+            }
+        };
+        table.kind == Some(TableKind::List) => ["list delete"] {
+            impl<'a> Write<'a> {
+                /// Same as `remove`.
+                pub fn delete<I: CheckId>(&mut self, row: I) {
+                    self.remove(row)
+                }
+                unsafe fn delete_raw(&mut self, _i: usize) {
+                    panic!("Unexpected call to delete_raw");
                 }
             }
         };
@@ -882,6 +1318,231 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         };
     }
+    let EDIT_COL: &Vec<_> = &table.cols.iter()
+        .filter(|x| x.track_edits)
+        .map(|x| i(pp::ident_to_string(x.name)))
+        .collect();
+    let EDIT_COL2 = EDIT_COL;
+    let HAS_EDITS = !EDIT_COL.is_empty();
+    let JOURNAL_TAKEN = quote_if(table.journal, quote! {
+        for &i in &rows {
+            self._table.journal.changed(i);
+        }
+    });
+    let FLUSH_EDITS = quote_if(HAS_EDITS, quote! {
+        if !event.is_creation && !event.is_removal {
+            // Trackers hear about the rows with `#[track_edits]` columns that were changed.
+            for i in table.take_edits() {
+                table._changes.push(i);
+            }
+        }
+    });
+    let EDITED_ROWS = quote! {
+        /// Returns the rows that had a `#[track_edits]` column changed since the edits were last taken. (R/W)
+        pub fn edited_rows(&self) -> Vec<RowId> {
+            let mut rows: Vec<usize> = Vec::new();
+            #(rows.extend(self.#EDIT_COL.deref().inner().edited());)*
+            rows.sort();
+            rows.dedup();
+            rows.into_iter().map(RowId::from_usize).collect()
+        }
+    };
+    out! { HAS_EDITS => ["edit tracking"] {
+        impl<'u> Read<'u> {
+            #EDITED_ROWS
+        }
+        impl<'u> Write<'u> {
+            #EDITED_ROWS
+
+            /// Returns the edited rows, and forgets them.
+            pub fn take_edits(&mut self) -> Vec<RowId> {
+                let rows = self.edited_rows();
+                #(self.#EDIT_COL2.deref_mut().inner_mut().clear_edits();)*
+                #JOURNAL_TAKEN
+                rows
+            }
+        }
+    };};
+    let WRITE_FLUSH = if IS_LIST {
+        quote! {
+            /// Applies the pending `insert_at`s and `remove`s. Lists have no trackers, so
+            /// nobody else needs to hear about it.
+            pub fn flush(mut self, _universe: &Universe, _event: Event) {
+                self.compact();
+            }
+
+            /// Same as `flush`, but keeps the lock.
+            pub fn live_flush(&mut self, _universe: &Universe, _event: Event) {
+                self.compact();
+            }
+        }
+    } else if IS_BAG {
+        quote! {
+            /// Propagate all changes. Trackers are told about deleted rows, and then about the rows
+            /// that were moved to fill the holes, through `Tracker::moved`.
+            pub fn flush(mut self, universe: &Universe, event: Event) {
+                if !self.is_dirty() { return; }
+                let (changes, remap) = self.take_changes();
+                let flush = self._table.flush.clone();
+                {self};
+                flush_bag(universe, flush, event, changes, remap);
+            }
+
+            /// Flush table without releasing the lock. This will of course cause a deadlock if
+            /// the table has trackers that need to look at values.
+            pub fn live_flush(&mut self, universe: &Universe, event: Event) {
+                if !self.is_dirty() { return; }
+                let (changes, remap) = self.take_changes();
+                let flush = self._table.flush.clone();
+                flush_bag(universe, flush, event, changes, remap);
+            }
+
+            fn is_dirty(&self) -> bool {
+                if !self._changes.as_slice().is_empty() { return true; }
+                match self._moved {
+                    Some(ref moved) => moved.values().any(|&old| old != INVALID),
+                    None => false,
+                }
+            }
+
+            fn take_changes(&mut self) -> (SelectOwned<Row>, Vec<(RowId, RowId)>) {
+                use std::mem;
+                self._delete = false;
+                let changes = mem::replace(&mut self._changes, Select::These(vec![]));
+                let remap = match self._moved {
+                    Some(ref mut moved) => moved
+                        .drain()
+                        .filter(|&(_, old)| old != INVALID)
+                        .map(|(new, old)| (old, new))
+                        .collect(),
+                    None => vec![],
+                };
+                (changes, remap)
+            }
+        }
+    } else {
+        quote! {
+            /// Propagate all changes
+            pub fn flush(self, universe: &'a Universe, event: Event) {
+                // FIXME: Ditching MaybeBorrow should be *easy*. But it isn't. Deadlocks
+                // happen. This is stupid.
+                let mut table = MaybeBorrow::Owned(self);
+                #FLUSH_EDITS
+                if table._changes.as_slice().is_empty() { return; }
+                use std::mem;
+                let pushed = table._pushed;
+                let delete = table._delete;
+                let changes = mem::replace(&mut table._changes, Select::These(vec![]));
+                table._push_runs.clear();
+                let flush_lock = table._table.flush.clone();
+                if table.is_owned() {
+                    table = MaybeBorrow::Nothing;
+                }
+                let changes = flush_lock.read().unwrap().do_flush(
+                    universe,
+                    event,
+                    pushed,
+                    delete,
+                    changes,
+                    false, // include self
+                );
+                if event.is_removal && !changes.as_slice().is_empty() {
+                    if table.is_missing() {
+                        table = MaybeBorrow::Owned(write(universe));
+                    }
+                    match changes {
+                        Select::All => table.clear_raw(),
+                        Select::These(rows) => {
+                            let len = table.len();
+                            for row in rows {
+                                unsafe {
+                                    let row = row.to_usize();
+                                    assert!(row < len);
+                                    table.delete_raw(row);
+                                }
+                            }
+                        },
+                    }
+                }
+                if !table.is_missing() {
+                    if !table._changes.as_slice().is_empty() {
+                        panic!("more changes added during flush");
+                    }
+                }
+            }
+
+            /// Flush table without releasing the lock. This will of course cause a deadlock if
+            /// the table has trackers that need to look at values.
+            pub fn live_flush<'b>(&mut self, universe: &'b Universe, event: Event) {
+                let table = self;
+                #FLUSH_EDITS
+                if table._changes.as_slice().is_empty() { return; }
+                use std::mem;
+                let pushed = table._pushed;
+                let delete = table._delete;
+                let changes = mem::replace(&mut table._changes, Select::These(vec![]));
+                table._push_runs.clear();
+                let flush_lock = table._table.flush.clone();
+                let flush = flush_lock.read().unwrap();
+                let changes = flush.do_flush(
+                    universe,
+                    event,
+                    pushed,
+                    delete,
+                    changes,
+                    false,
+                );
+                if event.is_removal && !changes.as_slice().is_empty() {
+                    match changes {
+                        Select::All => table.clear_raw(),
+                        Select::These(rows) => {
+                            let len = table.len();
+                            for row in rows {
+                                unsafe {
+                                    let row = row.to_usize();
+                                    assert!(row < len);
+                                    table.delete_raw(row);
+                                }
+                            }
+                        },
+                    }
+                }
+                if !table._changes.as_slice().is_empty() {
+                    panic!("more changes added during flush");
+                }
+                if flush.has_remapping() {
+                    mem::forget(flush);
+                    if let Ok(mut flush) = flush_lock.try_write() {
+                        flush.set_remapping(&[]);
+                    }
+                }
+                table._pushed = false;
+                table._delete = false;
+            }
+        }
+    };
+    out! {
+        table.kind == Some(TableKind::Bag) => ["bag flushing"] {
+            fn flush_bag(
+                universe: &Universe,
+                flush: GuardedFlush<Row>,
+                event: Event,
+                changes: SelectOwned<Row>,
+                remap: Vec<(RowId, RowId)>,
+            ) {
+                flush.write().unwrap().set_remapping(&remap);
+                flush.read().unwrap().do_flush(
+                    universe,
+                    event,
+                    false, // pushed
+                    true, // delete
+                    changes,
+                    false,
+                );
+                flush.write().unwrap().set_remapping(&[]);
+            }
+        };
+    }
     out! {
         ["selecting"] {
             fn select(
@@ -928,99 +1589,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
             }
             impl<'a> Write<'a> {
-                /// Propagate all changes
-                pub fn flush(self, universe: &'a Universe, event: Event) {
-                    // FIXME: Ditching MaybeBorrow should be *easy*. But it isn't. Deadlocks
-                    // happen. This is stupid.
-                    let mut table = MaybeBorrow::Owned(self);
-                    if table._changes.as_slice().is_empty() { return; }
-                    use std::mem;
-                    let pushed = table._pushed;
-                    let delete = table._delete;
-                    let changes = mem::replace(&mut table._changes, Select::These(vec![]));
-                    let flush_lock = table._table.flush.clone();
-                    if table.is_owned() {
-                        table = MaybeBorrow::Nothing;
-                    }
-                    let changes = flush_lock.read().unwrap().do_flush(
-                        universe,
-                        event,
-                        pushed,
-                        delete,
-                        changes,
-                        false, // include self
-                    );
-                    if event.is_removal && !changes.as_slice().is_empty() {
-                        if table.is_missing() {
-                            table = MaybeBorrow::Owned(write(universe));
-                        }
-                        match changes {
-                            Select::All => table.clear_raw(),
-                            Select::These(rows) => {
-                                let len = table.len();
-                                for row in rows {
-                                    unsafe {
-                                        let row = row.to_usize();
-                                        assert!(row < len);
-                                        table.delete_raw(row);
-                                    }
-                                }
-                            },
-                        }
-                    }
-                    if !table.is_missing() {
-                        if !table._changes.as_slice().is_empty() {
-                            panic!("more changes added during flush");
-                        }
-                    }
-                }
-
-                /// Flush table without releasing the lock. This will of course cause a deadlock if
-                /// the table has trackers that need to look at values.
-                pub fn live_flush<'b>(&mut self, universe: &'b Universe, event: Event) {
-                    let table = self;
-                    if table._changes.as_slice().is_empty() { return; }
-                    use std::mem;
-                    let pushed = table._pushed;
-                    let delete = table._delete;
-                    let changes = mem::replace(&mut table._changes, Select::These(vec![]));
-                    let flush_lock = table._table.flush.clone();
-                    let flush = flush_lock.read().unwrap();
-                    let changes = flush.do_flush(
-                        universe,
-                        event,
-                        pushed,
-                        delete,
-                        changes,
-                        false,
-                    );
-                    if event.is_removal && !changes.as_slice().is_empty() {
-                        match changes {
-                            Select::All => table.clear_raw(),
-                            Select::These(rows) => {
-                                let len = table.len();
-                                for row in rows {
-                                    unsafe {
-                                        let row = row.to_usize();
-                                        assert!(row < len);
-                                        table.delete_raw(row);
-                                    }
-                                }
-                            },
-                        }
-                    }
-                    if !table._changes.as_slice().is_empty() {
-                        panic!("more changes added during flush");
-                    }
-                    if flush.has_remapping() {
-                        mem::forget(flush);
-                        if let Ok(mut flush) = flush_lock.try_write() {
-                            flush.set_remapping(&[]);
-                        }
-                    }
-                    table._pushed = false;
-                    table._delete = false;
-                }
+                #WRITE_FLUSH
 
                 /// This method is here as a convenience for macros.
                 pub fn flush_or_close(self, universe: &Universe, event: Event) { self.flush(universe, event) }
@@ -1040,15 +1609,17 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
 
     // 'ifc' = "indexed foreign column"
     let ifcs = || table.cols.iter().filter(|x| x.indexed && x.foreign);
-    let IFC: Vec<_> = ifcs()
+    let IFC: &Vec<_> = &ifcs()
         .map(|x| i(pp::ident_to_string(x.name)))
         .collect();
+    let IFC2 = IFC;
     let SELECT_IFC: Vec<_> = ifcs()
         .map(|x| i(format!("select_{}", x.name)))
         .collect();
-    let IFC_ELEMENT: Vec<_> = ifcs()
+    let IFC_ELEMENT: &Vec<_> = &ifcs()
         .map(|x| i(pp::ty_to_string(&*x.element)))
         .collect();
+    let IFC_ELEMENT2 = IFC_ELEMENT;
 
     let sorted_foreign = || table.cols.iter().filter(|x| Some(x.name) == table.sort_key && x.foreign);
     let TRACKED_SORTED_COL: &Vec<_> = &sorted_foreign()
@@ -1060,6 +1631,12 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
     let TRACK_SORTED_COL_ELEMENT: &Vec<_> = &sorted_foreign()
         .map(|x| i(pp::ty_to_string(&*x.element)))
         .collect();
+    let REMAP_IFC: Vec<_> = ifcs()
+        .map(|x| i(format!("remap_{}", x.name)))
+        .collect();
+    // (Repeated so they can be used inside `#(...)*`.)
+    let REMAP_UNINDEX: Vec<_> = ifcs().map(|_| UNINDEX_I.clone()).collect();
+    let REMAP_INDEX: Vec<_> = ifcs().map(|_| INDEX_I.clone()).collect();
     out! { ["foreign selection"] {
         impl<'u> Read<'u> {
             #(
@@ -1103,9 +1680,30 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
             )*
         }
-    }};
-
-    let mut FOREIGN_NAME_NONCE = Vec::new();
+        impl<'u> Write<'u> {
+            #(
+                /// Moves references from old foreign rows to new ones. The foreign rows must be
+                /// given as `(old, new)` pairs, which may overlap.
+                #[doc(hidden)]
+                pub fn #REMAP_IFC(&mut self, remap: &[(#IFC_ELEMENT, #IFC_ELEMENT2)]) {
+                    let mut moves = vec![];
+                    for &(old, new) in remap {
+                        moves.extend(self.#IFC.deref().inner().find(old).map(|row| (row, new)));
+                    }
+                    for (row, mut new) in moves {
+                        let i = row.to_usize();
+                        #REMAP_UNINDEX
+                        unsafe {
+                            self.#IFC2.deref_mut().inner_mut().unchecked_swap_out(i, &mut new);
+                        }
+                        #REMAP_INDEX
+                    }
+                }
+            )*
+        }
+    }};
+
+    let mut FOREIGN_NAME_NONCE = Vec::new();
     let mut FOREIGN_LOCAL_COL = Vec::new();
     let mut FOREIGN_ELEMENTS = Vec::new();
     for col in &table.cols {
@@ -1119,6 +1717,14 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             panic!("`#[foreign_auto]` can only be used on columns with `#[index]` or `#[sort_key]`.");
         });
         let FOREIGN_ELEMENT = i(pp::ty_to_string(&*col.element));
+        // Rows of a bag can move around. `#[sort_key]` columns can't follow them, because
+        // changing the key would unsort the table.
+        let REMAP_IFC = i(format!("remap_{}", col.name));
+        let FOLLOW_MOVES = quote_if(col.indexed && Some(col.name) != table.sort_key, quote! {
+            fn moved(&self, universe: &Universe, moves: &[(#FOREIGN_ELEMENT, #FOREIGN_ELEMENT)]) {
+                write(universe).#REMAP_IFC(moves);
+            }
+        });
         out! { ["foreign_auto"] {
             impl Tracker for #TRACK_EVENTS {
                 // #FOREIGN_ELEMENT is a GenericRowId<TableRow>.
@@ -1138,6 +1744,8 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                     let rows = read(universe).#SELECT(rows);
                     handler.run(universe, event, rows);
                 }
+
+                #FOLLOW_MOVES
             }
         }};
         FOREIGN_NAME_NONCE.push(i(format!("_foreign_{}", col.name)));
@@ -1145,10 +1753,8 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         FOREIGN_ELEMENTS.push(FOREIGN_ELEMENT);
     }
     let FOREIGN_NAME_NONCE = &FOREIGN_NAME_NONCE;
-    let FOREIGN_NAME_NONCE2 = FOREIGN_NAME_NONCE;
     let FOREIGN_LOCAL_COL = &FOREIGN_LOCAL_COL;
     let FOREIGN_LOCAL_COL2 = FOREIGN_LOCAL_COL;
-    let FOREIGN_LOCAL_COL3 = FOREIGN_LOCAL_COL;
     let FOREIGN_ELEMENTS = &FOREIGN_ELEMENTS;
     let FOREIGN_ELEMENTS2 = FOREIGN_ELEMENTS;
 
@@ -1159,6 +1765,48 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 fn drop(&mut self) {}
             }
         };
+        table.kind == Some(TableKind::List) => ["Extra drops"] {
+            /// Prevent moving out columns to improve `RefA` safety.
+            impl<'u> Drop for Read<'u> {
+                fn drop(&mut self) {}
+            }
+
+            /// Makes sure that pending edits aren't lost.
+            impl<'u> Drop for Write<'u> {
+                fn drop(&mut self) {
+                    if !self._removed.is_empty() || !self._inserts.is_empty() {
+                        if ::std::thread::panicking() {
+                            // Panicking again would abort. The edits were never applied,
+                            // so forgetting them leaves the table as it was.
+                            self._removed.clear();
+                            self._inserts.clear();
+                            return;
+                        }
+                        panic!("Changes to {} were not flushed", TABLE_NAME);
+                    }
+                }
+            }
+        };
+        table.kind == Some(TableKind::Bag) => ["Extra drops"] {
+            /// Prevent moving out columns to improve `RefA` safety.
+            impl<'u> Drop for Read<'u> {
+                fn drop(&mut self) {}
+            }
+
+            /// Makes sure that trackers have heard about any moved rows.
+            impl<'u> Drop for Write<'u> {
+                fn drop(&mut self) {
+                    if self.is_dirty() {
+                        if ::std::thread::panicking() {
+                            // Panicking again would abort. The moves are already done,
+                            // so the trackers will just never hear about them.
+                            return;
+                        }
+                        panic!("Changes to {} were not flushed", TABLE_NAME);
+                    }
+                }
+            }
+        };
         ["Extra drops"] {
             /// Prevent moving out columns to improve `RefA` safety.
             impl<'u> Drop for Read<'u> {
@@ -1172,6 +1820,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         };
     }
 
+    let JOURNAL_CHANGED = quote_if(table.journal, quote! { self._table.journal.changed(i); });
     out! {
         table.consistent => ["event logging for consistent tables"] {
             impl<'u> Write<'u> {
@@ -1183,7 +1832,16 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 #[inline]
                 fn event_add(&mut self, i: RowId) {
                     self._pushed = true;
+                    let at = match self._changes {
+                        Select::These(ref rows) => rows.len(),
+                        Select::All => 0,
+                    };
                     self._changes.push(i);
+                    match self._push_runs.last_mut() {
+                        Some(ref mut run) if run.end == at => run.end += 1,
+                        _ => self._push_runs.push(at..at + 1),
+                    }
+                    #JOURNAL_CHANGED
                 }
                 #[inline]
                 fn event_del(&mut self, i: RowId) {
@@ -1194,7 +1852,56 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 #[inline] fn event_del_reserve(&mut self, n: usize) { self._changes.reserve(n) }
             }
         };
-        table.kind == Some(TableKind::Append) || table.kind == Some(TableKind::Bag) => ["event logging for basic tables"] {
+        table.kind == Some(TableKind::Bag) => ["event logging for bags"] {
+            impl<'u> Write<'u> {
+                #[inline]
+                fn event_cleared(&mut self) {
+                    self.clear_raw();
+                    if let Some(moved) = self._moved.as_mut() {
+                        moved.clear();
+                    } else {
+                        return;
+                    }
+                    self._delete = true;
+                    self._changes = Select::All;
+                }
+                #[inline] fn event_add(&mut self, _: RowId) {}
+                #[inline]
+                fn event_del(&mut self, i: RowId) {
+                    self._delete = true;
+                    self._changes.push(i);
+                }
+                #[inline] fn event_add_reserve(&mut self, _: usize) {}
+                #[inline] fn event_del_reserve(&mut self, n: usize) { self._changes.reserve(n) }
+            }
+        };
+        table.kind == Some(TableKind::Indirect) => ["event logging for indirect tables"] {
+            impl<'u> Write<'u> {
+                #[inline]
+                fn event_cleared(&mut self) {
+                    self.clear_raw();
+                }
+                #[inline] fn event_add(&mut self, _: RowId) {}
+                #[inline] fn event_del(&mut self, _: RowId) {}
+                #[inline] fn event_add_reserve(&mut self, _: usize) {}
+                #[inline] fn event_del_reserve(&mut self, _: usize) {}
+            }
+        };
+        table.kind == Some(TableKind::List) => ["event logging for lists"] {
+            impl<'u> Write<'u> {
+                #[inline]
+                fn event_cleared(&mut self) {
+                    self.clear_raw();
+                    self._removed.clear();
+                    self._inserts.clear();
+                }
+                #[inline] fn event_add(&mut self, _: RowId) {}
+                #[inline] fn event_del(&mut self, _: RowId) {}
+                #[inline] fn event_add_reserve(&mut self, _: usize) {}
+                #[inline] fn event_del_reserve(&mut self, _: usize) {}
+            }
+        };
+        table.kind == Some(TableKind::Append) => ["event logging for basic tables"] {
             impl<'u> Write<'u> {
                 #[inline]
                 fn event_cleared(&mut self) {
@@ -1218,6 +1925,11 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         // FIXME: event_del_reserve is inacessible!
     }
 
+    let CLEAR_CI = quote! { #(self._table.#CI_NAME.clear();)* };
+    let JOURNAL_CLEARED = quote_if(table.journal, quote! { self._table.journal.cleared(); });
+    // The slots are released, rather than forgotten, so that old handles stay stale.
+    let CLEAR_HANDLES = quote_if(IS_INDIRECT, quote! { self._table.handles.clear(); });
+    let INDEX_PUSHED = ci_hook("index_row", quote! { rowid.to_usize() });
     out! { ["mut methods safe for all guarantees"] {
         impl<'u> Write<'u> {
             /** Prepare the table for insertion of a specific amount of data. `self.len()` is
@@ -1230,6 +1942,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             /// Removes every row from the table.
             pub fn clear_raw(&mut self) {
                 #(self.#COL_NAME.deref_mut().inner_mut().clear();)*
+                #CLEAR_CI
+                #JOURNAL_CLEARED
+                #CLEAR_HANDLES
             }
 
             pub fn clear(&mut self) {
@@ -1248,7 +1963,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             #[inline]
             fn push_only_unchecked(&mut self, row: Row) -> RowId {
                 #(self.#COL_NAME.deref_mut().inner_mut().push(row.#COL_NAME2);)*
-                self.last().unwrap()
+                let rowid = self.last().unwrap();
+                #INDEX_PUSHED
+                rowid
             }
 
         }
@@ -1300,8 +2017,78 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
             }
         }};
+        let SORT_KEY_ELEMENT = {
+            let col = table.cols.iter().find(|c| c.name == sort_key).unwrap();
+            i(pp::ty_to_string(&*col.element))
+        };
+        let RW_FUNCTIONS_SORT_KEY = quote! {
+            /// Returns the first row whose `#[sort_key]` is not less than `key`,
+            /// or the end of the table. (R/W)
+            pub fn lower_bound(&self, key: &#SORT_KEY_ELEMENT) -> RowId {
+                self.partition_sort_key(|e| e < key)
+            }
+
+            /// Returns the first row whose `#[sort_key]` is greater than `key`,
+            /// or the end of the table. (R/W)
+            pub fn upper_bound(&self, key: &#SORT_KEY_ELEMENT) -> RowId {
+                self.partition_sort_key(|e| e <= key)
+            }
+
+            /// Returns the rows whose `#[sort_key]` is `key`. (R/W)
+            pub fn find_sorted(&self, key: &#SORT_KEY_ELEMENT) -> RowRange<RowId> {
+                RowRange {
+                    start: self.lower_bound(key),
+                    end: self.upper_bound(key),
+                }
+            }
+
+            /// Returns the rows whose `#[sort_key]` is in `range.start .. range.end`. (R/W)
+            pub fn range_sorted(&self, range: ::std::ops::Range<#SORT_KEY_ELEMENT>) -> RowRange<RowId> {
+                let start = self.lower_bound(&range.start);
+                let end = self.lower_bound(&range.end);
+                RowRange {
+                    start,
+                    end: if end < start { start } else { end },
+                }
+            }
+
+            /// Binary searches for the first row whose `#[sort_key]` doesn't satisfy `before`.
+            fn partition_sort_key<F>(&self, before: F) -> RowId
+            where F: Fn(&#SORT_KEY_ELEMENT) -> bool
+            {
+                let col = self.#SORT_KEY.deref().inner();
+                let mut lo = 0;
+                let mut hi = self.len();
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if before(col.checked_index(mid)) {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                RowId::from_usize(lo)
+            }
+        };
+        out! { ["searching the sort key"] {
+            impl<'u> Read<'u> {
+                #RW_FUNCTIONS_SORT_KEY
+            }
+            impl<'u> Write<'u> {
+                #RW_FUNCTIONS_SORT_KEY
+            }
+        }};
     }
 
+    let UNINDEX_A = ci_hook("unindex_row", quote! { a });
+    let UNINDEX_B = ci_hook("unindex_row", quote! { b });
+    let INDEX_A = ci_hook("index_row", quote! { a });
+    let INDEX_B = ci_hook("index_row", quote! { b });
+    let UNINDEX_TAIL = quote_if(HAS_CI, quote! {
+        for i in new_len..self.len() {
+            self.unindex_row(i);
+        }
+    });
     out! {
         table.sorted || !table.immutable => ["swapping"] {
             // Making this public would break many guarantees!
@@ -1310,7 +2097,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 fn swap_out_row(&mut self, i: RowId, row: &mut Row) {
                     unsafe {
                         let i = i.check(self).to_usize();
+                        #UNINDEX_I
                         #(self.#COL_NAME.deref_mut().inner_mut().unchecked_swap_out(i, &mut row.#COL_NAME2);)*
+                        #INDEX_I
                     }
                 }
 
@@ -1319,19 +2108,71 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                     unsafe {
                         let a = a.check(self).to_usize();
                         let b = b.check(self).to_usize();
+                        if a == b { return; }
+                        #UNINDEX_A
+                        #UNINDEX_B
                         #(self.#COL_NAME.deref_mut().inner_mut().unchecked_swap(a, b);)*
+                        #INDEX_A
+                        #INDEX_B
                     }
                 }
 
                 #[inline]
                 fn truncate(&mut self, new_len: usize) {
+                    #UNINDEX_TAIL
                     #(self.#COL_NAME.deref_mut().inner_mut().truncate(new_len);)*
                 }
             }
         };
     };
 
-    out! { !table.immutable && table.derive.clone && !table.consistent => ["merge functions"] {
+    out! { HAS_CI => ["composite indexes"] {
+        impl<'u> Read<'u> {
+            #(
+                /// Returns a multi-column index.
+                pub fn #CI_NAME(&self) -> &v11::map_index::CompositeIndex<#CI_KEY, Row> {
+                    &self._table.#CI_NAME2
+                }
+            )*
+        }
+        impl<'u> Write<'u> {
+            #(
+                /// Returns a multi-column index.
+                pub fn #CI_NAME(&self) -> &v11::map_index::CompositeIndex<#CI_KEY, Row> {
+                    &self._table.#CI_NAME2
+                }
+            )*
+
+            /// Adds a row to the multi-column indexes.
+            fn index_row(&mut self, i: usize) {
+                #(
+                    let key = #CI_KEY_OF_ROW;
+                    self._table.#CI_NAME.insert(key, i);
+                )*
+            }
+
+            /// Removes a row from the multi-column indexes.
+            fn unindex_row(&mut self, i: usize) {
+                #(
+                    let key = #CI_KEY_OF_ROW2;
+                    self._table.#CI_NAME3.remove(key, i);
+                )*
+            }
+
+            /// Rebuilds the multi-column indexes, after the columns were changed directly.
+            fn reindex(&mut self) {
+                #CLEAR_CI
+                for i in 0..self.len() {
+                    if !self._table.free.contains_key(&RowId::from_usize(i)) {
+                        self.index_row(i);
+                    }
+                }
+            }
+        }
+    };};
+
+    // Indirect tables can't have their rows shuffled around behind the handles' back.
+    out! { !table.immutable && table.derive.clone && !table.consistent && !IS_INDIRECT => ["merge functions"] {
         impl<'u> Write<'u> {
             /// Remove all rows for which the predicate returns `false`.
             pub fn retain<F: FnMut(&Self, RowId) -> bool>(&mut self, mut f: F) {
@@ -1415,7 +2256,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         }
     };}
-    out! { !table.immutable && table.derive.clone && !table.sorted && !table.consistent => ["visit"] {
+    // `visit` could add rows that duplicate a `#[unique]` column without them being checked.
+    let VISIT = !table.immutable && table.derive.clone && !table.sorted && !table.consistent && !IS_INDIRECT;
+    out! { VISIT && !table.has_unique() => ["visit"] {
         impl<'u> Write<'u> {
             pub fn visit<IT, F>(&mut self, f: F)
             where
@@ -1426,6 +2269,236 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         }
     };}
+    // #[unique] columns
+    let UNIQUE = table.has_unique();
+    let uniques = || table.cols.iter().filter(|x| x.unique);
+    let UNIQUE_COL: &Vec<_> = &uniques()
+        .map(|x| i(pp::ident_to_string(x.name)))
+        .collect();
+    let UNIQUE_COL2 = UNIQUE_COL;
+    let UNIQUE_COL3 = UNIQUE_COL;
+    let UNIQUE_COL_STR: &Vec<_> = &uniques()
+        .map(|x| pp::ident_to_string(x.name))
+        .collect();
+    let UNIQUE_COL_STR2 = UNIQUE_COL_STR;
+    let UNIQUE_ELEMENT: &Vec<_> = &uniques()
+        .map(|x| i(pp::ty_to_string(&*x.element)))
+        .collect();
+    let GET_BY_UNIQUE: &Vec<_> = &uniques()
+        .map(|x| i(format!("get_by_{}", x.name)))
+        .collect();
+    let GET_BY_UNIQUE2 = GET_BY_UNIQUE;
+    let UNIQUE_SEEN: &Vec<_> = &uniques()
+        .map(|x| i(format!("seen_{}", x.name)))
+        .collect();
+    let UNIQUE_SEEN2 = UNIQUE_SEEN;
+    let UNIQUE_SET: &Vec<_> = &uniques()
+        .map(|x| i(match x.index_kind {
+            IndexKind::BTree => "::std::collections::BTreeSet",
+            IndexKind::Hash => "::std::collections::HashSet",
+        }))
+        .collect();
+    // Rows deleted from a consistent table stay in the indexes until the flush.
+    let LIVE_ROWS: &Vec<_> = &uniques()
+        .map(|_| quote_if(table.consistent, quote! {
+            .filter(|&i| !self.is_pending_delete(i))
+        }))
+        .collect();
+    let PENDING_DELETE = quote_if(table.consistent, quote! {
+        /// Whether the row has been deleted, and will be removed by the next flush.
+        fn is_pending_delete(&self, row: RowId) -> bool {
+            if !self._delete { return false; }
+            match self._changes {
+                // Everything goes, even the rows pushed after the table was cleared.
+                Select::All => true,
+                Select::These(ref rows) => rows
+                    .iter()
+                    .enumerate()
+                    .filter(|&(at, _)| !self._push_runs.iter().any(|run| run.start <= at && at < run.end))
+                    .any(|(_, &r)| r == row),
+            }
+        }
+    });
+    out! { UNIQUE => ["unique columns"] {
+        impl<'u> Read<'u> {
+            #(
+                /// Returns the row with the given value in this `#[unique]` column.
+                pub fn #GET_BY_UNIQUE(&self, key: #UNIQUE_ELEMENT) -> Option<RowId> {
+                    self.#UNIQUE_COL.deref().inner().find(key).next()
+                }
+            )*
+        }
+        impl<'u> Write<'u> {
+            #(
+                /// Returns the row with the given value in this `#[unique]` column. Rows that are
+                /// waiting to be deleted by a flush aren't found.
+                pub fn #GET_BY_UNIQUE(&self, key: #UNIQUE_ELEMENT) -> Option<RowId> {
+                    self.#UNIQUE_COL.deref().inner().find(key)#LIVE_ROWS.next()
+                }
+            )*
+
+            #PENDING_DELETE
+
+            /// Checks that `row` doesn't have the same value as an existing row in any
+            /// `#[unique]` column.
+            pub fn check_unique(&self, row: &Row) -> Result<(), UniqueViolation> {
+                self.check_unique_except(row, None)
+            }
+
+            /// Like `check_unique`, but ignores the row `except`, which is about to be replaced.
+            fn check_unique_except(&self, row: &Row, except: Option<RowId>) -> Result<(), UniqueViolation> {
+                #(
+                    let existing = self.#GET_BY_UNIQUE2(row.#UNIQUE_COL2).filter(|&i| Some(i) != except);
+                    if let Some(existing) = existing {
+                        return Err(UniqueViolation {
+                            table: TABLE_NAME,
+                            column: #UNIQUE_COL_STR,
+                            existing: Some(existing.to_usize()),
+                        });
+                    }
+                )*
+                Ok(())
+            }
+
+            /// Like `check_unique`, but also checks that the rows don't duplicate each other.
+            pub fn check_unique_all(&self, rows: &[Row]) -> Result<(), UniqueViolation> {
+                #(let mut #UNIQUE_SEEN = #UNIQUE_SET::new();)*
+                for row in rows {
+                    self.check_unique(row)?;
+                    #(
+                        if !#UNIQUE_SEEN2.insert(row.#UNIQUE_COL3) {
+                            return Err(UniqueViolation {
+                                table: TABLE_NAME,
+                                column: #UNIQUE_COL_STR2,
+                                existing: None,
+                            });
+                        }
+                    )*
+                }
+                Ok(())
+            }
+        }
+    };};
+    out! {
+        UNIQUE && !table.immutable && table.sorted && !table.consistent => ["unique merging"] {
+            impl<'u> Write<'u> {
+                /// Merge in a sorted iterator of `Row`s.
+                /// `log` will be called with the new RowId of each row.
+                ///
+                /// Panics, before merging anything, if a row would duplicate a `#[unique]` column.
+                pub fn merge_logged<IT, I, L>(&mut self, rows: I, log: L)
+                where
+                    IT: Iterator<Item=Row>,
+                    I: Into<AssertSorted<IT>>,
+                    L: FnMut(&Self, RowId),
+                {
+                    self.try_merge_logged(rows, log).unwrap_or_else(|e| panic!("{}", e))
+                }
+
+                /// Like `merge_logged`, but returns the violation, and merges nothing, instead of
+                /// panicking.
+                pub fn try_merge_logged<IT, I, L>(&mut self, rows: I, log: L) -> Result<(), UniqueViolation>
+                where
+                    IT: Iterator<Item=Row>,
+                    I: Into<AssertSorted<IT>>,
+                    L: FnMut(&Self, RowId),
+                {
+                    let rows: Vec<Row> = rows.into().into_iter().collect();
+                    self.check_unique_all(&rows)?;
+                    self.merge_logged_unchecked(AssertSorted(rows.into_iter()), log);
+                    Ok(())
+                }
+
+                /// Like `merge`, but returns the violation, and merges nothing, instead of
+                /// panicking.
+                pub fn try_merge<IT, I>(&mut self, rows: I) -> Result<(), UniqueViolation>
+                where
+                    IT: Iterator<Item=Row>,
+                    I: Into<AssertSorted<IT>>,
+                {
+                    self.try_merge_logged(rows, |_, _| ())
+                }
+            }
+        };
+        UNIQUE => ["unique pushing"] {
+            impl<'u> Write<'u> {
+                /// Appends a single Row to the table, and returns its RowId.
+                /// Panics if it has the same value as another row in a `#[unique]` column.
+                pub fn push(&mut self, row: Row) -> RowId {
+                    self.try_push(row).unwrap_or_else(|e| panic!("{}", e))
+                }
+
+                /// Like `push`, but returns the violation instead of panicking.
+                pub fn try_push(&mut self, row: Row) -> Result<RowId, UniqueViolation> {
+                    self.check_unique(&row)?;
+                    Ok(self.push_unchecked_unique(row))
+                }
+
+                /// Populate the table with data from the provided iterator.
+                /// Panics, before pushing anything, if a row would duplicate a `#[unique]` column.
+                pub fn push_all<I: ::std::iter::Iterator<Item=Row>>(&mut self, data: I) {
+                    self.try_push_all(data).unwrap_or_else(|e| panic!("{}", e))
+                }
+
+                /// Like `push_all`, but returns the violation, and pushes nothing, instead of
+                /// panicking.
+                pub fn try_push_all<I>(&mut self, data: I) -> Result<(), UniqueViolation>
+                where I: ::std::iter::Iterator<Item=Row>
+                {
+                    let rows: Vec<Row> = data.collect();
+                    self.check_unique_all(&rows)?;
+                    self.reserve(rows.len());
+                    for row in rows {
+                        self.push_unchecked_unique(row);
+                    }
+                    Ok(())
+                }
+
+                /// Push an 'array' of values. The return value is a contiguous range.
+                /// Panics, before pushing anything, if a row would duplicate a `#[unique]` column.
+                pub fn push_array<I>(&mut self, i: I) -> RowRange<RowId>
+                where I: ExactSizeIterator<Item=Row>
+                {
+                    self.try_push_array(i).unwrap_or_else(|e| panic!("{}", e))
+                }
+
+                /// Like `push_array`, but returns the violation, and pushes nothing, instead of
+                /// panicking.
+                pub fn try_push_array<I>(&mut self, i: I) -> Result<RowRange<RowId>, UniqueViolation>
+                where I: ExactSizeIterator<Item=Row>
+                {
+                    let rows: Vec<Row> = i.collect();
+                    self.check_unique_all(&rows)?;
+                    Ok(self.push_array_unchecked_unique(rows.into_iter()))
+                }
+            }
+        };
+    }
+    // The checked versions of these are generated above.
+    let MERGE_LOGGED_DECL = if UNIQUE {
+        quote! { fn merge_logged_unchecked }
+    } else {
+        quote! { pub fn merge_logged }
+    };
+    let PUSH_DECL = if UNIQUE {
+        quote! { fn push_unchecked_unique }
+    } else {
+        quote! { pub fn push }
+    };
+    let PUSH_ARRAY_DECL = if UNIQUE {
+        quote! { fn push_array_unchecked_unique }
+    } else {
+        quote! { pub fn push_array }
+    };
+    let PUSH_ALL = quote_if(!UNIQUE, quote! {
+        /// Populate the table with data from the provided iterator.
+        pub fn push_all<I: ::std::iter::Iterator<Item=Row>>(&mut self, data: I) {
+            self.reserve(data.size_hint().0);
+            for row in data {
+                self.push(row);
+            }
+        }
+    });
     out! {
         !table.immutable && table.sorted && !table.consistent => ["row pushing for sorted tables"] {
             impl<'u> Write<'u> {
@@ -1459,7 +2532,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 /// # Caveats
                 /// The table will not be flushed before calls to `log`; if this is a problem you
                 /// should collect the rows, call flush, and then act.
-                pub fn merge_logged<IT, I, L>(&mut self, rows: I, mut log: L)
+                #MERGE_LOGGED_DECL<IT, I, L>(&mut self, rows: I, mut log: L)
                 where
                     IT: Iterator<Item=Row>,
                     I: Into<AssertSorted<IT>>,
@@ -1536,7 +2609,137 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
             }
         };
-        !table.sorted => ["row pushing for unsorted tables"] {
+        table.kind == Some(TableKind::Indirect) => ["row pushing for indirect tables"] {
+            impl<'u> Write<'u> {
+                /// Populate the table with data from the provided iterator.
+                pub fn push_all<I: ::std::iter::Iterator<Item=Row>>(&mut self, data: I) {
+                    self.reserve(data.size_hint().0);
+                    for row in data {
+                        self.push(row);
+                    }
+                }
+
+                /// Appends a single Row to the end of the table, and returns a handle to it.
+                #[inline]
+                pub fn push(&mut self, row: Row) -> Handle {
+                    let i = self.push_end_unchecked(row);
+                    self._table.handles.insert(i.to_usize())
+                }
+            }
+        };
+        table.kind == Some(TableKind::List) => ["row pushing for lists"] {
+            impl<'u> Write<'u> {
+                /// Populate the table with data from the provided iterator.
+                pub fn push_all<I: ::std::iter::Iterator<Item=Row>>(&mut self, data: I) {
+                    self.reserve(data.size_hint().0);
+                    for row in data {
+                        self.push(row);
+                    }
+                }
+
+                /// Appends a single Row to the end of the table, and returns its RowId.
+                #[inline]
+                pub fn push(&mut self, row: Row) -> RowId {
+                    self.push_end_unchecked(row)
+                }
+
+                /// Returns the RowId of the next row that would be inserted.
+                pub fn next_pushed(&self) -> RowId {
+                    RowId::from_usize(self.len())
+                }
+
+                /// Push an 'array' of values. The return value is a contiguous range.
+                pub fn push_array<I>(&mut self, i: I) -> RowRange<RowId>
+                where I: ExactSizeIterator<Item=Row>
+                {
+                    let start = self.next_pushed();
+                    for row in i {
+                        self.push(row);
+                    }
+                    RowRange {
+                        start,
+                        end: self.next_pushed(),
+                    }
+                }
+
+                /// Inserts `row` before the row at `at`. The row shows up after the next `compact`.
+                /// Rows inserted at the same place keep the order they were inserted in.
+                ///
+                /// If `at` is `self.len()`, this is the same as `push`, so that the row stays
+                /// ahead of anything pushed later.
+                pub fn insert_at(&mut self, at: RowId, row: Row) {
+                    if at.to_usize() > self.len() {
+                        panic!("insert_at {:?}, but the length is {}", at, self.len());
+                    }
+                    if at.to_usize() == self.len() {
+                        self.push(row);
+                        return;
+                    }
+                    self._inserts
+                        .entry(at)
+                        .or_insert_with(Vec::new)
+                        .push(row);
+                }
+
+                /// Marks a row for removal by the next `compact`.
+                pub fn remove<I: CheckId>(&mut self, row: I) {
+                    let i = row.check(self).uncheck();
+                    self._removed.insert(i);
+                }
+
+                /// Marks a range of rows for removal by the next `compact`, and returns copies of
+                /// them.
+                pub fn drain(&mut self, range: RowRange<RowId>) -> Vec<Row> {
+                    let rows: Vec<RowId> = self.range(range).map(|i| i.uncheck()).collect();
+                    let mut ret = Vec::with_capacity(rows.len());
+                    for i in rows {
+                        ret.push(self.get_row(i));
+                        self._removed.insert(i);
+                    }
+                    ret
+                }
+
+                /// Returns `true` if the row will be removed by the next `compact`.
+                pub fn is_removed(&self, row: RowId) -> bool {
+                    self._removed.contains(&row)
+                }
+
+                /// Applies pending `insert_at`s, `remove`s, and `drain`s, shifting the remaining
+                /// rows to close gaps. RowIds given to those functions refer to the positions rows
+                /// had before any of them were applied.
+                pub fn compact(&mut self) {
+                    use std::mem;
+                    use std::collections::{BTreeMap, BTreeSet};
+                    if self._removed.is_empty() && self._inserts.is_empty() { return; }
+                    let mut removed = mem::replace(&mut self._removed, BTreeSet::new());
+                    let mut inserts = mem::replace(&mut self._inserts, BTreeMap::new());
+                    self.merge0(|me, rowid| {
+                        if removed.is_empty() && inserts.is_empty() {
+                            return Action::Break;
+                        }
+                        let remove = removed.remove(&rowid);
+                        match inserts.remove(&rowid) {
+                            None => Action::Continue { remove, add: vec![] },
+                            Some(mut add) => {
+                                // The rug puts additions after the row, so we put the row after
+                                // the additions.
+                                if !remove {
+                                    add.push(me.get_row(rowid));
+                                }
+                                Action::Continue { remove: true, add }
+                            },
+                        }
+                    });
+                    // Anything left goes on the end.
+                    for (_, rows) in inserts {
+                        for row in rows {
+                            self.push_end_unchecked(row);
+                        }
+                    }
+                }
+            }
+        };
+        table.kind == Some(TableKind::Bag) => ["row pushing for bags"] {
             impl<'u> Write<'u> {
                 /// Populate the table with data from the provided iterator.
                 pub fn push_all<I: ::std::iter::Iterator<Item=Row>>(&mut self, data: I) {
@@ -1546,11 +2749,47 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                     }
                 }
 
+                /// Appends a single Row to the end of the table, and returns its RowId.
+                #[inline]
+                pub fn push(&mut self, row: Row) -> RowId {
+                    let i = self.push_end_unchecked(row);
+                    if let Some(moved) = self._moved.as_mut() {
+                        // Trackers haven't heard of this row, so it must not be confused with
+                        // whatever used to be here.
+                        moved.insert(i, INVALID);
+                    }
+                    i
+                }
+
+                /// Returns the RowId of the next row that would be inserted.
+                pub fn next_pushed(&self) -> RowId {
+                    RowId::from_usize(self.len())
+                }
+
+                /// Push an 'array' of values. The return value is a contiguous range.
+                pub fn push_array<I>(&mut self, i: I) -> RowRange<RowId>
+                where I: ExactSizeIterator<Item=Row>
+                {
+                    let start = self.next_pushed();
+                    for row in i {
+                        self.push(row);
+                    }
+                    RowRange {
+                        start,
+                        end: self.next_pushed(),
+                    }
+                }
+            }
+        };
+        !table.sorted => ["row pushing for unsorted tables"] {
+            impl<'u> Write<'u> {
+                #PUSH_ALL
+
                 /// Appends a single Row to the table.
                 /// Returns its RowId. This is not necessarily at the end of the table!
                 // In retrospect 'push' might have been a poor name.
                 #[inline]
-                pub fn push(&mut self, mut row: Row) -> RowId {
+                #PUSH_DECL(&mut self, mut row: Row) -> RowId {
                     let expect = if cfg!(test) {
                         Some(self.next_pushed())
                     } else {
@@ -1584,7 +2823,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 }
 
                 /// Push an 'array' of values. The return value is a contiguous range.
-                pub fn push_array<I>(&mut self, mut i: I) -> RowRange<RowId>
+                #PUSH_ARRAY_DECL<I>(&mut self, mut i: I) -> RowRange<RowId>
                 where I: ExactSizeIterator<Item=Row>
                 {
                     // This implementation doesn't need ExactSizeIterator, but future configurations
@@ -1607,6 +2846,22 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         };
     }
 
+    let WRITE_BAG_INIT = quote_if(IS_BAG, quote! {
+        let _moved = {
+            let _table: &Table = _lock.table.downcast_ref::<Table>().expect("Table downcast failed");
+            if _table.flush.read().unwrap().trackers_is_empty() {
+                // Nobody cares where the rows go.
+                None
+            } else {
+                Some(::std::collections::HashMap::new())
+            }
+        };
+    });
+    let WRITE_BAG_FIELDS_INIT = quote_if(IS_BAG, quote! { _moved, });
+    let WRITE_LIST_FIELDS_INIT = quote_if(IS_LIST, quote! {
+        _removed: Default::default(),
+        _inserts: Default::default(),
+    });
     out! { ["Lock & Load"] {
 
         use std::mem::transmute;
@@ -1672,6 +2927,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                     // FIXME: Actually the comment should go on this one, since mut is harder.
                 }
             };)*
+            #WRITE_BAG_INIT
             let _table = {
                 let _table: &mut Table = _lock.table.downcast_mut::<Table>().expect("Table downcast failed");
                 // See comment about transmute in `convert_read_guard()`.
@@ -1684,6 +2940,9 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 _pushed: false,
                 _delete: false,
                 _changes: Select::These(vec![]),
+                _push_runs: Vec::new(),
+                #WRITE_BAG_FIELDS_INIT
+                #WRITE_LIST_FIELDS_INIT
                 #( #COL_NAME3: #COL_NAME4, )*
             }
         }
@@ -1707,6 +2966,7 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
         fn new_generic_table() -> GenericTable {
             let table = GenericTable::new(Table::new());
             let mut table = table #(.add_column({
+                type CT = #COL_TYPE2;
                 fn prototyper() -> GenericColumn {
                     GenericColumn {
                         meta: ColumnMeta {
@@ -1714,11 +2974,20 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                             stored_type_name: Cow::Borrowed(column_format::#COL_NAME),
                             version: #COL_VERSION,
                         },
-                        data: {
-                            type CT = #COL_TYPE2;
-                            Box::new(CT::new()) as Box<AnyCol>
-                        },
+                        data: Box::new(CT::new()) as Box<AnyCol>,
                         prototyper,
+                        reflection: v11::reflect::Reflection {
+                            len: {
+                                fn len(col: &AnyCol) -> usize {
+                                    let col: &CT = col.downcast_ref().unwrap();
+                                    col.inner().len()
+                                }
+                                len
+                            },
+                            get_dyn: #REFLECT_DEBUG,
+                            get_serialize: #REFLECT_SERIALIZE,
+                            set_from: #REFLECT_SET,
+                        },
                     }
                 }
                 prototyper
@@ -1732,6 +3001,11 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             new_generic_table().register();
         }
 
+        /// Like `register`, but returns an error if the domain is missing or locked.
+        pub fn try_register() -> Result<(), v11::Error> {
+            new_generic_table().try_register()
+        }
+
         impl<'u> Write<'u> {
             /// Borrow a `Read` lock from a `Write` lock.
             ///
@@ -1816,37 +3090,84 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
 
     if table.save && !table.derive.clone { panic!("#[save] requires #[row_derive(Clone)]"); }
 
+    // A snapshot can refer to rows that aren't in it, so this returns an error rather than
+    // panicking.
     let UPDATE_ROW = quote! {
         #(
             row.#FOREIGN_LOCAL_COL = #FOREIGN_NAME_NONCE.1
                 .remap(row.#FOREIGN_LOCAL_COL2)
-                .unwrap_or_else(|| {
-                    panic!("Row {:?} has no remapping. Available: {:#?}", row.#FOREIGN_LOCAL_COL3, #FOREIGN_NAME_NONCE2.1.remapped)
-                });
+                .ok_or("the extraction refers to a row that has no remapping")?;
         )*
     };
+    let EXTRACT_ROW = if FOREIGN_LOCAL_COL.is_empty() {
+        quote! { row }
+    } else {
+        quote! { mut row }
+    };
+    let CHECK_UNIQUE_EXTRACT = quote_if(UNIQUE, quote! {
+        self.check_unique_all(&rows)?;
+    });
+    // The rows are remapped and checked before any are added, so that a bad extraction leaves
+    // the table as it was.
+    let CHECK_EXTRACT = quote! {
+        let mut old_ids = Vec::with_capacity(extract.data.#COL0.inner().len());
+        let mut rows = Vec::with_capacity(old_ids.capacity());
+        for (old_id, #EXTRACT_ROW) in extract.into_iter() {
+            #UPDATE_ROW
+            old_ids.push(old_id);
+            rows.push(row);
+        }
+        #CHECK_UNIQUE_EXTRACT
+    };
+    let MERGE_EXTRACT_ROWS = if UNIQUE {
+        quote! { self.try_merge(rows)?; }
+    } else {
+        quote! { self.merge(rows); }
+    };
     let MERGE_EXTRACT = if table.sorted {
         quote! {
+            #CHECK_EXTRACT
             if no_trackers {
-                let rows = extract
-                    .into_iter()
-                    .map(|(_i, row)| row)
-                    .collect::<Vec<_>>();
-                self.merge(rows);
+                #MERGE_EXTRACT_ROWS
             } else {
                 // FIXME: This is terrible. But also it is annoying.
                 // Might have to modify merge_logged?
-                for (old_id, mut row) in extract.into_iter() {
-                    #UPDATE_ROW
+                for (old_id, row) in old_ids.into_iter().zip(rows) {
                     let new_id = self.merge_in_a_single_row(row);
                     remap.push((old_id, new_id));
                 }
             }
         }
+    } else if table.consistent {
+        quote! {
+            #CHECK_EXTRACT
+            // The rows go back at their old `RowId`s, with the gaps deleted, so that deltas still
+            // line up.
+            let in_place = keep_ids && self.len() == 0;
+            if in_place {
+                let mut sorted = old_ids.clone();
+                sorted.sort();
+                if sorted.windows(2).any(|w| w[0] == w[1]) {
+                    return Err("the extraction has the same row twice".into());
+                }
+            }
+            for (old_id, row) in old_ids.into_iter().zip(rows) {
+                let new_id = if in_place {
+                    self.put_row(old_id, row);
+                    self.event_add(old_id);
+                    old_id
+                } else {
+                    self.push(row)
+                };
+                if !no_trackers {
+                    remap.push((old_id, new_id));
+                }
+            }
+        }
     } else {
         quote! {
-            for (old_id, mut row) in extract.into_iter() {
-                #UPDATE_ROW
+            #CHECK_EXTRACT
+            for (old_id, row) in old_ids.into_iter().zip(rows) {
                 let new_id = self.push(row);
                 if !no_trackers {
                     remap.push((old_id, new_id));
@@ -1854,14 +3175,202 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
             }
         }
     };
-    out! { table.derive.clone => ["Extraction"] {
+    let MIGRATE_BOUND = quote_if(table.migrate, quote! {
+        Row: Migrate<V>,
+    });
+    let MIGRATE_CALL = quote_if(table.migrate, quote! {
+        if raw.schema != VERSION || raw.columns != Extraction::current_columns() {
+            <Row as Migrate<V>>::migrate(&mut raw)?;
+        }
+    });
+    let COL_NAME_STR2 = COL_NAME_STR;
+    let COL_NAME_STR3 = COL_NAME_STR;
+    let FROM_RAW = quote_if(table.save, quote! {
+        impl Extraction {
+            /// Deserializes the columns of a `RawExtraction`.
+            /// If the table has `#[migrate]`, an outdated extraction is migrated first.
+            pub fn from_raw<V>(mut raw: RawExtraction<V>) -> Result<Self, String>
+            where
+                V: for<'de> self::v11::serde::Deserializer<'de>,
+                #MIGRATE_BOUND
+            {
+                #MIGRATE_CALL
+                // Put the columns back in order.
+                let mut columns = Vec::with_capacity(raw.columns.len());
+                #(
+                    let i = raw.columns
+                        .iter()
+                        .position(|c| c.name == #COL_NAME_STR)
+                        .ok_or_else(|| format!("missing column {:?}", #COL_NAME_STR2))?;
+                    columns.push(raw.columns.remove(i));
+                )*
+                if let Some(extra) = raw.columns.first() {
+                    return Err(format!("unexpected column {:?}", extra.name));
+                }
+                let data = Owned {
+                    #(
+                        #COL_NAME: {
+                            let data = raw.data
+                                .remove(#COL_NAME_STR)
+                                .ok_or_else(|| format!("column {:?} has no data", #COL_NAME_STR2))?;
+                            <#COL_TYPE as self::v11::serde::Deserialize>::deserialize(data)
+                                .map_err(|e| format!("column {:?}: {}", #COL_NAME_STR3, e))?
+                        },
+                    )*
+                };
+                Ok(Extraction {
+                    extraction_fmt: raw.extraction_fmt,
+                    domain: raw.domain,
+                    name: raw.name,
+                    schema: raw.schema,
+                    columns,
+                    selection: raw.selection.iter().map(|&i| RowId::from_usize(i as usize)).collect(),
+                    data,
+                })
+            }
+        }
+    });
+    let REINDEX_TABLE = quote_if(HAS_CI, quote! { table.reindex(); });
+    let EXTRACT_SELECTION = quote! {
+        pub fn extract_selection(&self, selection: SelectRows<Row>) -> Extraction {
+            let n = match selection {
+                Select::All => self.iter().size_hint().0,
+                Select::These(xs) => xs.len(),
+            };
+            let selection: Vec<RowId> = selection.iter_or_all_with(|| {
+                self.iter().map(|row| row.uncheck())
+            }).collect();
+            let data = Owned {
+                #(
+                    #COL_NAME: {
+                        type T = #COL_TYPE;
+                        let mut out = T::new();
+                        out.inner_mut().reserve(n);
+                        for i in &selection {
+                            out.inner_mut().push(self.#COL_NAME2[*i].clone());
+                        }
+                        out
+                    },
+                )*
+            };
+            Extraction {
+                extraction_fmt: EXTRACTION_FMT,
+                domain: Cow::Borrowed(TABLE_DOMAIN.0),
+                name: Cow::Borrowed(TABLE_NAME.0),
+                schema: VERSION,
+                columns: Extraction::current_columns(),
+                selection,
+                data,
+            }
+        }
+    };
+    let BINARY = quote_if(table.binary, quote! {
+        impl Extraction {
+            /// Writes the extraction in v11's binary format. (See `v11::binary`.)
+            pub fn write_binary<W: ::std::io::Write>(&self, out: &mut W) -> ::std::io::Result<()> {
+                use self::v11::binary;
+                let mut out = binary::Writer::new(out);
+                out.header(&binary::Header {
+                    extraction_fmt: self.extraction_fmt,
+                    domain: self.domain.clone(),
+                    name: self.name.clone(),
+                    schema: self.schema,
+                    columns: self.columns.clone(),
+                })?;
+                let selection: Vec<RawType> = self.selection.iter().map(RowId::to_raw).collect();
+                out.slice(&selection)?;
+                #(out.col(&self.data.#COL_NAME)?;)*
+                Ok(())
+            }
+
+            /// Reads an extraction written by `write_binary`.
+            pub fn read_binary<R: ::std::io::Read>(input: &mut R) -> ::std::io::Result<Self> {
+                Self::from_binary(self::v11::binary::Reader::new(input))
+            }
+
+            /// Reads an extraction from a memory-mapped file written by `write_binary`.
+            /// `MappedCol`s keep using the file instead of copying it.
+            pub fn map_binary(mapping: ::std::sync::Arc<self::v11::mapped::Mapping>) -> ::std::io::Result<Self> {
+                Self::from_binary(self::v11::binary::Reader::mapped(mapping))
+            }
+
+            fn from_binary<R: ::std::io::Read>(mut input: self::v11::binary::Reader<R>) -> ::std::io::Result<Self> {
+                let header = input.header()?;
+                let selection: Vec<RawType> = input.vec()?;
+                let data = Owned {
+                    #(#COL_NAME: input.col()?,)*
+                };
+                #(
+                    if data.#COL_NAME.inner().len() != selection.len() {
+                        return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, "column length mismatch"));
+                    }
+                )*
+                Ok(Extraction {
+                    extraction_fmt: header.extraction_fmt,
+                    domain: header.domain,
+                    name: header.name,
+                    schema: header.schema,
+                    columns: header.columns,
+                    selection: selection.into_iter().map(RowId::new).collect(),
+                    data,
+                })
+            }
+        }
+
+        /// Fills the empty table from a file written by `Extraction::write_binary`, and locks it for reading.
+        /// The file is memory-mapped, and `MappedCol`s use it in place.
+        ///
+        /// No events are sent, and nothing is remapped, so the file must hold every row of a
+        /// table that had no holes, as written by `extract_selection(Select::All)`.
+        /// Other extractions are rejected.
+        ///
+        /// # Safety
+        /// The file must not be changed or truncated while the table uses it. See `Mapping::open`.
+        pub unsafe fn open_mapped<P: AsRef<::std::path::Path>>(universe: &Universe, path: P) -> ::std::io::Result<Read> {
+            use std::io::{Error, ErrorKind};
+            let mapping = self::v11::mapped::Mapping::open(path)?;
+            let extraction = Extraction::map_binary(mapping)?;
+            extraction.validate().map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            if extraction.selection.iter().enumerate().any(|(i, row)| row.to_usize() != i) {
+                return Err(Error::new(ErrorKind::InvalidData, "open_mapped can't renumber rows; the extraction has holes"));
+            }
+            {
+                let mut table = write(universe);
+                if table.len() != 0 {
+                    return Err(Error::new(ErrorKind::Other, "open_mapped needs an empty table"));
+                }
+                let data = extraction.data;
+                #(*table.#COL_NAME.deref_mut() = data.#COL_NAME2;)*
+                #REINDEX_TABLE
+                table.close();
+            }
+            Ok(read(universe))
+        }
+    });
+    // Only `consistent` tables can have holes.
+    let IGNORE_KEEP_IDS = quote_if(!table.consistent, quote! { let _ = keep_ids; });
+    // Handles can't be restored, so indirect tables can only be extracted.
+    let RESTORE_EXTRACT = quote_if(!IS_INDIRECT, quote! {
         impl<'u> Write<'u> {
             pub fn restore_extract(
+                self,
+                universe: &Universe,
+                extract: Extraction,
+                event: Event,
+            ) -> Result<(), #RESTORE_ERR> {
+                self.restore_extract_ids(universe, extract, event, false)
+            }
+
+            /// Like `restore_extract`, but if `keep_ids` is set and the table is empty, the rows
+            /// of a `consistent` table get their old `RowId`s back.
+            fn restore_extract_ids(
                 mut self,
                 universe: &Universe,
                 extract: Extraction,
                 event: Event,
-            ) -> Result<(), &'static str> {
+                keep_ids: bool,
+            ) -> Result<(), #RESTORE_ERR> {
+                #IGNORE_KEEP_IDS
                 extract.validate()?;
                 let mut remap: Vec<(RowId, RowId)>;
                 {
@@ -1907,46 +3416,376 @@ pub fn write_out<W: Write>(table: Table, mut out: W) -> ::std::io::Result<()> {
                 Ok(())
             }
         }
+    });
+    let PUT_ROW = quote_if(table.consistent, quote! {
+        impl<'u> Write<'u> {
+            /// Puts `row` at `i`, replacing the row that is there, if any.
+            /// If `i` is past the end of the table, the gap is filled with deleted rows.
+            /// No events are sent.
+            fn put_row(&mut self, i: RowId, mut row: Row) {
+                while self.len() < i.to_usize() {
+                    let filler = self.push_only_unchecked(row.clone());
+                    unsafe { self.delete_raw(filler.to_usize()); }
+                }
+                if self.len() == i.to_usize() {
+                    self.push_only_unchecked(row);
+                } else {
+                    self._table.free.remove(&i);
+                    self.swap_out_row(i, &mut row);
+                }
+            }
+        }
+    });
+    out! { table.derive.clone => ["Extraction"] {
+        #FROM_RAW
+        #BINARY
+
+        #RESTORE_EXTRACT
+        #PUT_ROW
 
         impl<'u> Read<'u> {
-            pub fn extract_selection(&self, selection: SelectRows<Row>) -> Extraction {
-                let n = match selection {
-                    Select::All => self.iter().size_hint().0,
-                    Select::These(xs) => xs.len(),
+            #EXTRACT_SELECTION
+        }
+        impl<'u> Write<'u> {
+            #EXTRACT_SELECTION
+        }
+    };};
+    let JOURNAL_EDIT_COL: &Vec<_> = &table.cols.iter()
+        .filter(|x| !x.track_edits)
+        .map(|x| i(pp::ident_to_string(x.name)))
+        .collect();
+    let JOURNAL_EDIT_COL2 = JOURNAL_EDIT_COL;
+    out! { table.journal => ["journal"] {
+        /// The rows that changed between two checkpoints, made by `Write::extract_delta`.
+        #DERIVE_SERDE
+        pub struct Delta {
+            /// The table was cleared before `rows` were added.
+            pub cleared: bool,
+            /// The rows that were deleted.
+            pub deleted: Vec<RowId>,
+            /// The rows that were created or edited. The `selection` has their `RowId`s.
+            pub rows: Extraction,
+        }
+
+        impl PendingDelta for Delta {
+            fn as_serialize(&self) -> &self::v11::erased_serde::Serialize { self }
+            fn put_back(&self, universe: &Universe) {
+                write(universe).return_delta(self);
+            }
+        }
+
+        impl<'u> Write<'u> {
+            /// Records that a row was edited, so that it is included in the next delta.
+            /// (Created, deleted, and edited rows are recorded automatically;
+            /// this is for changes that the columns can't see.)
+            pub fn mark_edited<I: CheckId>(&mut self, row: I) {
+                let i = row.check(self).uncheck();
+                self._table.journal.changed(i);
+            }
+
+            /// Moves the rows whose columns were edited into the journal.
+            /// Edits to `#[track_edits]` columns are left for `take_edits`,
+            /// so those rows may be in the next delta again.
+            fn journal_edits(&mut self) {
+                let mut rows: Vec<usize> = Vec::new();
+                #(rows.extend(self.#COL_NAME.deref().inner().edited());)*
+                #(self.#JOURNAL_EDIT_COL.deref_mut().inner_mut().clear_edits();)*
+                for i in rows {
+                    self._table.journal.changed(RowId::from_usize(i));
+                }
+            }
+
+            /// Returns `true` if nothing has changed since the last delta.
+            pub fn journal_is_empty(&self) -> bool {
+                self._table.journal.is_empty() #(&& self.#COL_NAME.deref().inner().edited().is_empty())*
+            }
+
+            /// Extracts the rows that changed since the last delta, and starts a new one.
+            /// If the delta can't be saved, give it to `return_delta`.
+            pub fn extract_delta(&mut self) -> Delta {
+                use std::mem;
+                self.journal_edits();
+                let journal = mem::replace(&mut self._table.journal, Default::default());
+                let changed: Vec<RowId> = journal.changed
+                    .into_iter()
+                    .filter(|&i| self.contains(i))
+                    .collect();
+                Delta {
+                    cleared: journal.cleared,
+                    deleted: journal.deleted.into_iter().collect(),
+                    rows: self.extract_selection(Select::These(&changed)),
+                }
+            }
+
+            /// Puts the changes of a `Delta` from `extract_delta` back into the journal,
+            /// because it couldn't be saved. Anything that changed since then still wins.
+            pub fn return_delta(&mut self, delta: &Delta) {
+                let mut journal = self::v11::journal::Journal::default();
+                journal.cleared = delta.cleared;
+                journal.deleted.extend(delta.deleted.iter().cloned());
+                journal.changed.extend(delta.rows.selection.iter().cloned());
+                self._table.journal.merge_earlier(journal);
+            }
+
+            /// Applies a `Delta` made by `extract_delta`.
+            /// Rows are put back at the same `RowId`s, so that foreign keys in other tables still line up.
+            /// No events are sent, and nothing is journaled.
+            pub fn apply_delta(&mut self, delta: Delta) -> Result<(), &'static str> {
+                use std::mem;
+                delta.rows.validate()?;
+                self.journal_edits();
+                let journal = mem::replace(&mut self._table.journal, Default::default());
+                if delta.cleared {
+                    self.clear_raw();
+                    self._table.free.clear();
+                }
+                for i in delta.deleted {
+                    if self.contains(i) {
+                        unsafe { self.delete_raw(i.to_usize()); }
+                    }
+                }
+                for (i, row) in delta.rows.into_iter() {
+                    self.put_row(i, row);
+                }
+                #(self.#JOURNAL_EDIT_COL2.deref_mut().inner_mut().clear_edits();)*
+                self._table.journal = journal;
+                Ok(())
+            }
+        }
+    };};
+    let JOURNAL_MARK = if table.journal {
+        quote! { (self.write._table.journal.changed.contains(&id), self.write._table.journal.deleted.contains(&id)) }
+    } else {
+        quote! { (false, false) }
+    };
+    let JOURNAL_RESTORE = quote_if(table.journal, quote! {
+        let journal = &mut self.write._table.journal;
+        if was.0 { journal.changed.insert(id); } else { journal.changed.remove(&id); }
+        if was.1 { journal.deleted.insert(id); } else { journal.deleted.remove(&id); }
+    });
+    let JOURNAL_EDITED = quote_if(table.journal, quote! { self.write._table.journal.changed(id); });
+    let TRANSACTION_TRY_PUSH = quote_if(UNIQUE, quote! {
+        /// Like `Write::try_push`.
+        pub fn try_push(&mut self, row: Row) -> Result<RowId, UniqueViolation> {
+            self.write.check_unique(&row)?;
+            Ok(self.push(row))
+        }
+
+        /// Like `replace`, but returns the violation instead of panicking.
+        pub fn try_replace<I: CheckId>(&mut self, row: I, new: Row) -> Result<Row, UniqueViolation> {
+            let id = row.check(&self.write).uncheck();
+            self.write.check_unique_except(&new, Some(id))?;
+            self.redo.clear();
+            Ok(self.replace_step(id, new))
+        }
+    });
+    let TRANSACTION_CHECK_REPLACE = quote_if(UNIQUE, quote! {
+        if let Err(e) = self.write.check_unique_except(&new, Some(id)) {
+            panic!("{}", e);
+        }
+    });
+    out! { table.consistent && table.derive.clone => ["transactions"] {
+        /// How to put a `Write` back the way it was before a change.
+        struct TransactionMark {
+            changes: Option<usize>,
+            pushed: bool,
+            delete: bool,
+            journal: (bool, bool),
+        }
+
+        enum TransactionStep {
+            Pushed { id: RowId, appended: bool, mark: TransactionMark },
+            Deleted { id: RowId, mark: TransactionMark },
+            Replaced { id: RowId, row: Row, journal: (bool, bool) },
+        }
+
+        enum TransactionRedo {
+            Push(Row),
+            Delete(RowId),
+            Replace(RowId, Row),
+        }
+
+        /// A `Write` lock that can undo its changes. (See `v11::transaction`.)
+        /// Made by `Write::transaction`.
+        pub struct Transaction<'u> {
+            write: Write<'u>,
+            undo: Vec<TransactionStep>,
+            redo: Vec<TransactionRedo>,
+        }
+
+        impl<'u> Write<'u> {
+            /// Starts recording changes, so that they can be undone.
+            pub fn transaction(self) -> Transaction<'u> {
+                Transaction {
+                    write: self,
+                    undo: Vec::new(),
+                    redo: Vec::new(),
+                }
+            }
+        }
+
+        impl<'u> Transaction<'u> {
+            fn mark(&self, id: RowId) -> TransactionMark {
+                TransactionMark {
+                    changes: match self.write._changes {
+                        Select::All => None,
+                        Select::These(ref rows) => Some(rows.len()),
+                    },
+                    pushed: self.write._pushed,
+                    delete: self.write._delete,
+                    journal: #JOURNAL_MARK,
+                }
+            }
+
+            fn restore(&mut self, id: RowId, mark: TransactionMark) {
+                if let (Some(len), &mut Select::These(ref mut rows)) = (mark.changes, &mut self.write._changes) {
+                    rows.truncate(len);
+                    let runs = &mut self.write._push_runs;
+                    runs.retain(|run| run.start < len);
+                    if let Some(run) = runs.last_mut() {
+                        run.end = run.end.min(len);
+                    }
+                }
+                self.write._pushed = mark.pushed;
+                self.write._delete = mark.delete;
+                self.restore_journal(id, mark.journal);
+            }
+
+            #[allow(unused_variables)]
+            fn restore_journal(&mut self, id: RowId, was: (bool, bool)) {
+                #JOURNAL_RESTORE
+            }
+
+            fn push_step(&mut self, row: Row) -> RowId {
+                let appended = self.write._table.free.is_empty();
+                let id = self.write.next_pushed();
+                let mark = self.mark(id);
+                let ret = self.write.push(row);
+                self.undo.push(TransactionStep::Pushed { id, appended, mark });
+                ret
+            }
+
+            fn delete_step(&mut self, id: RowId) {
+                let mark = self.mark(id);
+                self.write.delete(id);
+                self.undo.push(TransactionStep::Deleted { id, mark });
+            }
+
+            fn replace_step(&mut self, id: RowId, mut row: Row) -> Row {
+                let journal = self.mark(id).journal;
+                self.write.swap_out_row(id, &mut row);
+                #JOURNAL_EDITED
+                self.undo.push(TransactionStep::Replaced { id, row: row.clone(), journal });
+                row
+            }
+
+            /// Like `Write::push`.
+            pub fn push(&mut self, row: Row) -> RowId {
+                self.redo.clear();
+                self.push_step(row)
+            }
+
+            #TRANSACTION_TRY_PUSH
+
+            /// Like `Write::delete`.
+            pub fn delete<I: CheckId>(&mut self, row: I) {
+                self.redo.clear();
+                let id = row.check(&self.write).uncheck();
+                self.delete_step(id)
+            }
+
+            /// Replaces a row in place, returning the old row.
+            /// Panics if the new row would repeat a value of another row in a `#[unique]` column.
+            pub fn replace<I: CheckId>(&mut self, row: I, new: Row) -> Row {
+                let id = row.check(&self.write).uncheck();
+                #TRANSACTION_CHECK_REPLACE
+                self.redo.clear();
+                self.replace_step(id, new)
+            }
+
+            /// Remembers the row as it is, and returns the lock, so that the row's columns can be
+            /// changed, and the changes undone like a `replace`.
+            /// Only that row should be changed: other changes made through the lock aren't
+            /// recorded.
+            pub fn edit<I: CheckId>(&mut self, row: I) -> &mut Write<'u> {
+                self.redo.clear();
+                let id = row.check(&self.write).uncheck();
+                let journal = self.mark(id).journal;
+                let row = self.write.get_row(id);
+                self.undo.push(TransactionStep::Replaced { id, row, journal });
+                &mut self.write
+            }
+
+            /// Reverses the latest change. Returns `false` if there was nothing to undo.
+            pub fn undo(&mut self) -> bool {
+                let step = match self.undo.pop() {
+                    Some(step) => step,
+                    None => return false,
                 };
-                let selection: Vec<RowId> = selection.iter_or_all_with(|| {
-                    self.iter().map(|row| row.uncheck())
-                }).collect();
-                let data = Owned {
-                    #(
-                        #COL_NAME: {
-                            type T = #COL_TYPE;
-                            let mut out = T::new();
-                            out.inner_mut().reserve(n);
-                            for i in &selection {
-                                out.inner_mut().push(self.#COL_NAME2[*i].clone());
-                            }
-                            out
-                        },
-                    )*
+                let redo = match step {
+                    TransactionStep::Pushed { id, appended, mark } => {
+                        let row = self.write.get_row(id);
+                        if appended {
+                            self.write.truncate(id.to_usize());
+                        } else {
+                            // The slot goes back on the free list.
+                            unsafe { self.write.delete_raw(id.to_usize()); }
+                        }
+                        self.restore(id, mark);
+                        TransactionRedo::Push(row)
+                    },
+                    TransactionStep::Deleted { id, mark } => {
+                        self.restore(id, mark);
+                        TransactionRedo::Delete(id)
+                    },
+                    TransactionStep::Replaced { id, mut row, journal } => {
+                        self.write.swap_out_row(id, &mut row);
+                        self.restore_journal(id, journal);
+                        TransactionRedo::Replace(id, row)
+                    },
                 };
-                Extraction {
-                    extraction_fmt: EXTRACTION_FMT,
-                    domain: Cow::Borrowed(TABLE_DOMAIN.0),
-                    name: Cow::Borrowed(TABLE_NAME.0),
-                    schema: VERSION,
-                    columns: vec![
-                        #(
-                            ColumnMeta {
-                                name: Cow::Borrowed(#COL_NAME_STR),
-                                stored_type_name: Cow::Borrowed(column_format::#COL_NAME),
-                                version: #COL_VERSION,
-                            },
-                        )*
-                    ],
-                    selection,
-                    data,
+                self.redo.push(redo);
+                true
+            }
+
+            /// Repeats the latest undone change. Returns `false` if there was nothing to redo.
+            pub fn redo(&mut self) -> bool {
+                match self.redo.pop() {
+                    Some(TransactionRedo::Push(row)) => { self.push_step(row); },
+                    Some(TransactionRedo::Delete(id)) => self.delete_step(id),
+                    Some(TransactionRedo::Replace(id, row)) => { self.replace_step(id, row); },
+                    None => return false,
                 }
+                true
+            }
+
+            /// Flushes the changes, as `Write::flush` would.
+            pub fn commit(self, universe: &'u Universe, event: Event) {
+                self.write.flush(universe, event)
+            }
+
+            /// Undoes every change, returning the lock as it was when the transaction started.
+            pub fn rollback(mut self) -> Write<'u> {
+                while self.undo() {}
+                self.write
+            }
+        }
+
+        impl<'u> ::std::ops::Deref for Transaction<'u> {
+            type Target = Write<'u>;
+            fn deref(&self) -> &Write<'u> {
+                &self.write
+            }
+        }
+
+        impl<'u> v11::transaction::TableTransaction<'u> for Transaction<'u> {
+            fn undo(&mut self) -> bool { Transaction::undo(self) }
+            fn redo(&mut self) -> bool { Transaction::redo(self) }
+            fn commit_boxed(self: Box<Self>, universe: &'u Universe, event: Event) {
+                (*self).commit(universe, event)
+            }
+            fn rollback_boxed(self: Box<Self>) {
+                (*self).rollback();
             }
         }
     };};
@@ -5,7 +5,7 @@ use syntex_syntax::parse::token::{Token, DelimToken, BinOpToken};
 use syntex_syntax::parse::common::SeqSep;
 use syntex_syntax::diagnostics::plugin::DiagnosticBuilder;
 
-use super::table::{Table, Col, TableKind};
+use super::table::{Table, Col, TableKind, IndexKind, CompositeIndex};
 #[allow(unused_imports)]
 use super::{warn, error};
 
@@ -54,6 +54,7 @@ pub fn parse_table<'a>(parser: &mut Parser<'a>) -> Result<Table, DiagnosticBuild
                 "bag" => TableKind::Bag,
                 "list" => TableKind::List,
                 "sorted" => TableKind::Sorted,
+                "indirect" => TableKind::Indirect,
                 e => err!(parser, "Unknown table kind {:?}", e),
             }),
             "row_id" => table.row_id = meta_arg(&attr.value),
@@ -71,7 +72,27 @@ pub fn parse_table<'a>(parser: &mut Parser<'a>) -> Result<Table, DiagnosticBuild
                 }
                 table.row_derive.extend(items);
             },
+            "index" => if let MetaItemKind::List(items) = attr.value.node {
+                // #[index(name = "by_a_b", cols = "a, b")]
+                let mut index = CompositeIndex::default();
+                for item in &items {
+                    if let NestedMetaItemKind::MetaItem(ref item) = item.node {
+                        let arg = meta_arg(item);
+                        match format!("{}", item.name.as_str()).as_str() {
+                            "name" => index.name = arg,
+                            "cols" => index.cols = arg.split(',').map(|c| c.trim().to_owned()).collect(),
+                            e => err!(parser, "Unknown #[index] argument {:?}", e),
+                        }
+                    }
+                }
+                table.composite_indexes.push(index);
+            } else {
+                err!(parser, "table indexes should be of the form #[index(name = \"...\", cols = \"a, b\")]");
+            },
             "save" => table.save = true,
+            "migrate" => table.migrate = true,
+            "binary" => table.binary = true,
+            "journal" => table.journal = true,
             "version" => {
                 table.version = str::parse(meta_arg(&attr.value).as_str()).unwrap();
             },
@@ -111,17 +132,41 @@ pub fn parse_table<'a>(parser: &mut Parser<'a>) -> Result<Table, DiagnosticBuild
         parser.parse_seq_to_end(&Token::CloseDelim(DelimToken::Brace), commas, |parser| {
             // #[attrs] column_name: [ElementType; ColumnType<ElementType>],
             let mut indexed = false;
+            let mut index_kind = IndexKind::BTree;
+            let mut unique = false;
             let mut foreign = false;
             let mut foreign_auto = false;
             let mut sort_key = false;
+            let mut track_edits = false;
             let mut version = 0;
+            let mut unknown = None;
             let attrs = parser.parse_outer_attributes()?
                 .into_iter()
                 .filter(|attr| {
                     match format!("{}", attr.value.name).as_str() {
-                        "index" => indexed = true,
+                        "index" => {
+                            indexed = true;
+                            if let MetaItemKind::NameValue(_) = attr.value.node {
+                                index_kind = match meta_arg(&attr.value).as_str() {
+                                    "btree" => IndexKind::BTree,
+                                    "hash" => IndexKind::Hash,
+                                    e => {
+                                        unknown = Some(format!("Unknown index kind {:?}", e));
+                                        IndexKind::BTree
+                                    },
+                                };
+                            }
+                        },
+                        "unique" => {
+                            indexed = true;
+                            unique = true;
+                            if let MetaItemKind::NameValue(_) = attr.value.node {
+                                unknown = Some("#[unique] doesn't take a value".to_string());
+                            }
+                        },
                         "foreign" => foreign = true,
                         "sort_key" => sort_key = true,
+                        "track_edits" => track_edits = true,
                         "foreign_auto" => {
                             foreign = true;
                             foreign_auto = true;
@@ -134,6 +179,9 @@ pub fn parse_table<'a>(parser: &mut Parser<'a>) -> Result<Table, DiagnosticBuild
                     }
                     false
                 }).collect();
+            if let Some(unknown) = unknown {
+                err!(parser, "{}", unknown);
+            }
             let name = parser.parse_ident()?;
             parser.expect(&Token::Colon)?;
             parser.expect(&Token::OpenDelim(DelimToken::Bracket))?;
@@ -156,8 +204,11 @@ pub fn parse_table<'a>(parser: &mut Parser<'a>) -> Result<Table, DiagnosticBuild
                 element,
                 colty,
                 indexed,
+                index_kind,
+                unique,
                 foreign,
                 foreign_auto,
+                track_edits,
                 version,
             })
         })?
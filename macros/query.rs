@@ -0,0 +1,394 @@
+//! The `query!` macro.
+//!
+//! This is a real function-like procedural macro rather than a procedural masquerade, because
+//! the masquerade can only output items, and the query's body has to end up inside loops.
+//! The header is planned here; the universe expression and the body are spliced back in
+//! as they were written.
+
+use proc_macro::{TokenStream, TokenTree, Group, Delimiter, Ident, Literal, Punct, Spacing, Span};
+use syntex_syntax::parse::{ParseSess, new_parser_from_source_str};
+use syntex_syntax::parse::parser::Parser;
+use syntex_syntax::parse::token::Token;
+use syntex_syntax::print::pprust::token_to_string;
+
+struct Tok {
+    token: Token,
+    out: String,
+}
+
+impl Tok {
+    fn ident(&self) -> Option<String> {
+        match self.token {
+            Token::Ident(ref i) => Some(format!("{}", i.name.as_str())),
+            _ => None,
+        }
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        self.ident().as_ref().map(String::as_str) == Some(name)
+    }
+}
+
+/// `row in table`
+struct Binding {
+    mutable: bool,
+    row: String,
+    table: String,
+}
+
+/// A side of a join: `row` or `row.column`.
+struct Key {
+    binding: usize,
+    column: Option<String>,
+}
+
+enum Cond {
+    Join(Key, Key),
+    Filter(String),
+}
+
+fn tokenize(parser: &mut Parser) -> Vec<Tok> {
+    let mut ret = Vec::new();
+    while parser.token != Token::Eof {
+        let token = parser.token.clone();
+        let out = token_to_string(&token);
+        ret.push(Tok { token, out });
+        parser.bump();
+    }
+    ret
+}
+
+/// Splits `toks` on top-level tokens that match `sep`.
+fn split<'a, F: Fn(&Tok) -> bool>(toks: &'a [Tok], sep: F) -> Vec<&'a [Tok]> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, tok) in toks.iter().enumerate() {
+        match tok.token {
+            Token::OpenDelim(_) => depth += 1,
+            Token::CloseDelim(_) => depth -= 1,
+            _ if depth == 0 && sep(tok) => {
+                ret.push(&toks[start..i]);
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    ret.push(&toks[start..]);
+    ret
+}
+
+fn out(toks: &[Tok]) -> String {
+    toks.iter().map(|t| t.out.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_binding(toks: &[Tok]) -> Result<Binding, String> {
+    let (mutable, toks) = match toks.first() {
+        Some(t) if t.is_ident("mut") => (true, &toks[1..]),
+        _ => (false, toks),
+    };
+    match toks {
+        [row, kw, table] if kw.is_ident("in") && row.ident().is_some() && table.ident().is_some() => Ok(Binding {
+            mutable,
+            row: row.out.clone(),
+            table: table.out.clone(),
+        }),
+        _ => Err(format!("query!: expected `row in table` or `mut row in table`, got {:?}", out(toks))),
+    }
+}
+
+fn parse_key(toks: &[Tok], bindings: &[Binding]) -> Option<Key> {
+    let binding = |t: &Tok| bindings.iter().position(|b| b.row == t.out);
+    match toks {
+        [row] => Some(Key { binding: binding(row)?, column: None }),
+        [row, dot, col] if dot.token == Token::Dot && col.ident().is_some() => Some(Key {
+            binding: binding(row)?,
+            column: Some(col.out.clone()),
+        }),
+        _ => None,
+    }
+}
+
+fn parse_cond(toks: &[Tok], bindings: &[Binding]) -> Cond {
+    if let [lhs, rhs] = split(toks, |t| t.token == Token::EqEq)[..] {
+        if let (Some(l), Some(r)) = (parse_key(lhs, bindings), parse_key(rhs, bindings)) {
+            if l.binding != r.binding {
+                return Cond::Join(l, r);
+            }
+        }
+    }
+    Cond::Filter(out(toks))
+}
+
+/// The `JoinKey` of one side of a join.
+fn join_key(key: &Key, bindings: &[Binding]) -> String {
+    match key.column {
+        None => "&::v11::join::RowIds".to_owned(),
+        Some(ref col) => format!("&{}.{}", bindings[key.binding].table, col),
+    }
+}
+
+/// The value of one side of a join, for a row that's already been found.
+fn key_value(key: &Key, bindings: &[Binding]) -> String {
+    let b = &bindings[key.binding];
+    match key.column {
+        None => format!("::v11::index::Checkable::uncheck({})", b.row),
+        Some(ref col) => format!("{}.{}[{}]", b.table, col, b.row),
+    }
+}
+
+/// Writes out the query, with `__V11_QUERY_UNIVERSE` & `__V11_QUERY_BODY` standing in for
+/// the universe and body.
+fn write_out(parser: &mut Parser) -> Result<String, String> {
+    let toks = tokenize(parser);
+
+    let parts = split(&toks, |t| t.is_ident("where"));
+    let (head, conds) = match parts[..] {
+        [head] => (head, None),
+        [head, conds] => (head, Some(conds)),
+        _ => return Err("query!: more than one `where`".to_owned()),
+    };
+    let mut head = split(head, |t| t.token == Token::Comma);
+    if head.last().map(|b| b.is_empty()) == Some(true) {
+        head.pop();
+    }
+    if head.is_empty() {
+        return Err("query!: no tables".to_owned());
+    }
+    let bindings: Vec<Binding> = head.into_iter().map(parse_binding).collect::<Result<_, _>>()?;
+    let conds: Vec<Cond> = match conds {
+        None => vec![],
+        // `&&` binds tighter than `||`, so splitting on it would change the meaning.
+        Some(conds) if split(conds, |t| t.token == Token::OrOr).len() > 1 => {
+            return Err("query!: `||` in a `where` clause must be inside parentheses".to_owned());
+        },
+        Some(conds) => split(conds, |t| t.token == Token::AndAnd)
+            .into_iter()
+            .map(|c| parse_cond(c, &bindings))
+            .collect(),
+    };
+    let gather = bindings.iter().any(|b| b.mutable);
+
+    // Lock each table once.
+    let mut locks = String::new();
+    let mut locked: Vec<&str> = vec![];
+    for b in &bindings {
+        if locked.contains(&b.table.as_str()) { continue; }
+        locked.push(&b.table);
+        let write = bindings.iter().any(|o| o.table == b.table && o.mutable);
+        if write {
+            locks += &format!("let mut {t} = {t}::write(__v11_universe);\n", t=b.table);
+        } else {
+            locks += &format!("let {t} = {t}::read(__v11_universe);\n", t=b.table);
+        }
+    }
+
+    // For each table, the first join with an earlier table is used to find its rows.
+    // Every other join is checked like a filter.
+    let mut joined: Vec<Option<(&Key, &Key)>> = bindings.iter().map(|_| None).collect();
+    let mut filters: Vec<String> = vec![];
+    for cond in &conds {
+        match *cond {
+            Cond::Filter(ref f) => filters.push(format!("({})", f)),
+            Cond::Join(ref l, ref r) => {
+                let (earlier, later) = if l.binding < r.binding { (l, r) } else { (r, l) };
+                if joined[later.binding].is_none() {
+                    joined[later.binding] = Some((earlier, later));
+                } else {
+                    filters.push(format!("{} == {}", key_value(l, &bindings), key_value(r, &bindings)));
+                }
+            },
+        }
+    }
+
+    let mut loops = String::new();
+    let mut closing = String::new();
+    let mut i = 0;
+    while i < bindings.len() {
+        let b = &bindings[i];
+        match joined.get(i + 1) {
+            Some(&Some((earlier, later))) if i == 0 && earlier.binding == 0 => {
+                let next = &bindings[1];
+                loops += &format!(
+                    "for ({}, {}) in ::v11::join::merge_join(&{}, {}, &{}, {}) {{\n",
+                    b.row, next.row,
+                    b.table, join_key(earlier, &bindings),
+                    next.table, join_key(later, &bindings),
+                );
+                closing += "}\n";
+                i += 2;
+                continue;
+            },
+            _ => (),
+        }
+        match joined[i] {
+            Some((earlier, later)) => loops += &format!(
+                "for {} in ::v11::query::matching(&{}, {}, {}) {{\n",
+                b.row, b.table, join_key(later, &bindings), key_value(earlier, &bindings),
+            ),
+            None => loops += &format!("for {} in {}.iter() {{\n", b.row, b.table),
+        }
+        closing += "}\n";
+        i += 1;
+    }
+    let filter = if filters.is_empty() { "true".to_owned() } else { filters.join(" && ") };
+
+    let rows = bindings.iter().map(|b| format!("{},", b.row)).collect::<String>();
+    let body = if gather {
+        let unchecked = bindings
+            .iter()
+            .map(|b| format!("::v11::index::Checkable::uncheck({}),", b.row))
+            .collect::<String>();
+        format!(
+            "let mut __v11_rows = Vec::new();\n\
+            {loops} if {filter} {{ __v11_rows.push(({unchecked})); }} {closing}\n\
+            for ({rows}) in __v11_rows __V11_QUERY_BODY",
+            loops=loops, filter=filter, unchecked=unchecked, closing=closing, rows=rows,
+        )
+    } else {
+        format!("{} if {} __V11_QUERY_BODY {}", loops, filter, closing)
+    };
+
+    Ok(format!(
+        "{{\n\
+            let __v11_universe = &__V11_QUERY_UNIVERSE;\n\
+            {locks}\
+            {body}\n\
+        }}",
+        locks=locks, body=body,
+    ))
+}
+
+/// Replaces the placeholder `name` with `with`.
+fn splice(stream: TokenStream, name: &str, with: &TokenTree) -> TokenStream {
+    stream.into_iter().map(|tt| match tt {
+        TokenTree::Ident(ref i) if i.to_string() == name => with.clone(),
+        TokenTree::Group(ref g) => {
+            let mut spliced = Group::new(g.delimiter(), splice(g.stream(), name, with));
+            spliced.set_span(g.span());
+            TokenTree::Group(spliced)
+        },
+        tt => tt,
+    }).collect()
+}
+
+/// `compile_error!(msg)`, pointing at `span`.
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut msg = Literal::string(msg);
+    msg.set_span(span);
+    let mut args = Group::new(Delimiter::Parenthesis, TokenTree::Literal(msg).into());
+    args.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(args),
+    ].into_iter().collect()
+}
+
+/// Points each `break` in the body at `'__v11_query`, rather than at the innermost of the
+/// generated loops. `break`s that belong to the body's own loops are left alone.
+/// Returns whether there were any.
+fn label_breaks(stream: TokenStream, out: &mut Vec<TokenTree>) -> bool {
+    let mut found = false;
+    // Whether we're between a loop's keyword and its block.
+    let mut loop_header = false;
+    let mut stream = stream.into_iter().peekable();
+    while let Some(tt) = stream.next() {
+        match tt {
+            TokenTree::Ident(ref i) if ["for", "while", "loop"].contains(&i.to_string().as_str()) => {
+                loop_header = true;
+            },
+            TokenTree::Ident(ref i) if i.to_string() == "break" && !loop_header => {
+                out.push(tt.clone());
+                let labeled = match stream.peek() {
+                    Some(&TokenTree::Punct(ref p)) => p.as_char() == '\'',
+                    _ => false,
+                };
+                if !labeled {
+                    found = true;
+                    out.push(TokenTree::Punct(Punct::new('\'', Spacing::Joint)));
+                    out.push(TokenTree::Ident(Ident::new("__v11_query", Span::call_site())));
+                }
+                continue;
+            },
+            TokenTree::Punct(ref p) if p.as_char() == ';' => loop_header = false,
+            TokenTree::Group(ref g) if g.delimiter() == Delimiter::Brace && loop_header => {
+                // The loop's own block.
+                loop_header = false;
+            },
+            TokenTree::Group(ref g) => {
+                let mut inner = vec![];
+                found |= label_breaks(g.stream(), &mut inner);
+                let mut labeled = Group::new(g.delimiter(), inner.into_iter().collect());
+                labeled.set_span(g.span());
+                out.push(TokenTree::Group(labeled));
+                continue;
+            },
+            _ => (),
+        }
+        out.push(tt);
+    }
+    found
+}
+
+/// Checks that `universe` is a single expression.
+fn is_expr(universe: &TokenStream) -> bool {
+    let sess = ParseSess::new();
+    let mut parser = new_parser_from_source_str(&sess, "<query! macro>".to_owned(), universe.to_string());
+    let parsed = match parser.parse_expr() {
+        Ok(_) => parser.token == Token::Eof,
+        Err(mut e) => {
+            e.cancel();
+            false
+        },
+    };
+    parsed
+}
+
+/// `query!(universe, for (...) { ... })`
+pub fn query(input: TokenStream) -> TokenStream {
+    let usage = "query!: expected `query!(universe, for (row in table, ...) { ... })`";
+    let mut input: Vec<TokenTree> = input.into_iter().collect();
+    // The universe can be any expression, including ones with commas that aren't in brackets
+    // (such as a turbofish), so the rest is taken from the end.
+    if input.len() < 5 {
+        return compile_error(usage, Span::call_site());
+    }
+    let rest = input.split_off(input.len() - 4);
+    let universe: TokenStream = input.into_iter().collect();
+    let (header, body) = match (&rest[0], &rest[1], &rest[2], &rest[3]) {
+        (&TokenTree::Punct(ref comma), &TokenTree::Ident(ref kw), &TokenTree::Group(ref header), &TokenTree::Group(ref body))
+            if comma.as_char() == ',' && kw.to_string() == "for"
+            && header.delimiter() == Delimiter::Parenthesis && body.delimiter() == Delimiter::Brace
+            => (header.clone(), body.clone()),
+        _ => return compile_error(usage, Span::call_site()),
+    };
+    if !is_expr(&universe) {
+        return compile_error("query!: the universe should be an expression", Span::call_site());
+    }
+    let sess = ParseSess::new();
+    let mut parser = new_parser_from_source_str(&sess, "<query! macro>".to_owned(), header.stream().to_string());
+    let out = match write_out(&mut parser) {
+        Ok(out) => out,
+        Err(msg) => return compile_error(&msg, header.span()),
+    };
+    let out: TokenStream = match out.parse() {
+        Ok(out) => out,
+        Err(_) => return compile_error("query!: couldn't understand the `where` clause", header.span()),
+    };
+    let mut labeled = vec![];
+    let has_breaks = label_breaks(body.stream(), &mut labeled);
+    let mut body_group = Group::new(Delimiter::Brace, labeled.into_iter().collect());
+    body_group.set_span(body.span());
+    let universe = TokenTree::Group(Group::new(Delimiter::Parenthesis, universe));
+    let out = splice(out, "__V11_QUERY_UNIVERSE", &universe);
+    let out = splice(out, "__V11_QUERY_BODY", &TokenTree::Group(body_group));
+    if !has_breaks {
+        return out;
+    }
+    // A `break` leaves the whole query.
+    let label: TokenStream = "'__v11_query:".parse().unwrap();
+    label.into_iter().chain(Some(TokenTree::Group(Group::new(Delimiter::Brace, out)))).collect()
+}
@@ -1,5 +1,6 @@
 use syntex_syntax::ast::{Ident, Ty, Attribute, NestedMetaItem};
 use syntex_syntax::ptr::P;
+use syntex_syntax::print::pprust as pp;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TableKind {
@@ -8,6 +9,7 @@ pub enum TableKind {
     Bag,
     List,
     Sorted,
+    Indirect,
 }
 
 #[derive(Default, Debug)]
@@ -29,11 +31,15 @@ pub struct Table {
     pub kind: Option<TableKind>,
     pub cols: Vec<Col>,
     pub add_trackers: Vec<String>,
+    pub composite_indexes: Vec<CompositeIndex>,
 
     // Modifiers
     pub row_id: String,
     pub version: u32,
     pub save: bool,
+    pub migrate: bool,
+    pub binary: bool,
+    pub journal: bool,
     pub derive: Derives,
     pub sort_key: Option<Ident>,
 
@@ -72,6 +78,9 @@ impl Table {
             TableKind::Sorted => {
                 self.sorted = true;
             },
+            TableKind::Indirect => {
+                self.secret = true;
+            },
         }
         for col in &self.cols {
             if col.indexed { panic!("Indexes are NYI"); }
@@ -112,14 +121,85 @@ impl Table {
         if self.cols.is_empty() {
             return Some("No columns");
         }
+        if self.migrate && !self.save {
+            return Some("#[migrate] requires #[save]");
+        }
+        if !self.consistent && self.cols.iter().any(|c| c.track_edits) {
+            // Only consistent tables have trackers to deliver the edits to.
+            return Some("#[track_edits] requires #[kind = \"consistent\"]");
+        }
+        if self.journal && !(self.save && self.consistent) {
+            return Some("#[journal] requires #[save] and #[kind = \"consistent\"]");
+        }
+        if self.binary && !self.derive.clone {
+            return Some("#[binary] requires #[row_derive(Clone)]");
+        }
+        if self.save && self.kind == Some(TableKind::Indirect) {
+            return Some("indirect tables don't support #[save]");
+        }
+        if self.kind == Some(TableKind::Indirect) && self.row_id != "u64" && self.row_id != "usize" {
+            // Half of the bits are for the slot, and half for its generation.
+            return Some("indirect tables need a 64-bit #[row_id]");
+        }
+        if self.binary && self.kind == Some(TableKind::Indirect) {
+            return Some("indirect tables don't support #[binary]");
+        }
         if self.derive.copy && !self.derive.clone {
             return Some("deriving copy, but not clone");
         }
-        if !self.consistent && !self.add_trackers.is_empty() {
-            return Some("only consistent tables can have trackers");
+        if !self.consistent && self.kind != Some(TableKind::Bag) && !self.add_trackers.is_empty() {
+            return Some("only consistent and bag tables can have trackers");
+        }
+        for index in &self.composite_indexes {
+            if index.name.is_empty() {
+                return Some("#[index(...)] needs a name");
+            }
+            if index.cols.len() < 2 || index.cols.len() > 4 {
+                return Some("#[index(...)] needs between 2 and 4 columns");
+            }
+            if self.composite_indexes.iter().filter(|i| i.name == index.name).count() > 1 {
+                return Some("#[index(...)] names must be unique");
+            }
+            for col in &index.cols {
+                if !self.cols.iter().any(|c| c.name.to_string() == *col) {
+                    return Some("#[index(...)] refers to a column that doesn't exist");
+                }
+            }
+            if self.cols.iter().any(|c| c.name.to_string() == index.name) {
+                return Some("#[index(...)] can't have the same name as a column");
+            }
+        }
+        for col in &self.cols {
+            // This only gives a clearer error. Under another name, the index wrappers still
+            // reject `IStr` elements, which aren't `Copy`.
+            let colty = pp::ty_to_string(&*col.colty);
+            let element = pp::ty_to_string(&*col.element);
+            let interned = |ty: &str| ty.ends_with("InternCol") || ty.ends_with("IStr");
+            if interned(&colty) || interned(&element) {
+                if col.indexed || self.in_composite_index(col) {
+                    return Some("InternCol columns index their own codes, and can't have #[index] or #[unique]");
+                }
+            }
+        }
+        if self.has_unique() {
+            match self.kind {
+                Some(TableKind::Append) | Some(TableKind::Consistent) | Some(TableKind::Sorted) => (),
+                _ => return Some("#[unique] is only supported on append, consistent, and sorted tables"),
+            }
         }
         None
     }
+
+    /// Whether the column is part of an `#[index(...)]`.
+    pub fn in_composite_index(&self, col: &Col) -> bool {
+        let name = col.name.to_string();
+        self.composite_indexes.iter().any(|i| i.cols.contains(&name))
+    }
+
+    /// Whether any column is `#[unique]`.
+    pub fn has_unique(&self) -> bool {
+        self.cols.iter().any(|c| c.unique)
+    }
 }
 
 #[derive(Debug)]
@@ -129,8 +209,26 @@ pub struct Col {
     pub element: P<Ty>,
     pub colty: P<Ty>,
     pub indexed: bool,
+    pub index_kind: IndexKind,
+    pub unique: bool,
     pub foreign: bool,
     pub foreign_auto: bool,
+    pub track_edits: bool,
     pub version: usize,
-    // just use BTreeMap for now; might want HashMap later tho
+}
+
+/// The data structure used by an `#[index]`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IndexKind {
+    /// `#[index]` or `#[index = "btree"]`
+    BTree,
+    /// `#[index = "hash"]`
+    Hash,
+}
+
+/// A table-level `#[index(name = "...", cols = "a, b")]`.
+#[derive(Debug, Default)]
+pub struct CompositeIndex {
+    pub name: String,
+    pub cols: Vec<String>,
 }
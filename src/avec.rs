@@ -1,22 +1,34 @@
+//! Paged storage, for columns that grow a lot at run-time.
 // FIXME: This needs a non-slice-based sorting algorithm. (Or maybe a merge-sort would work nicely?)
 
+use std::fmt;
+use std::mem;
+use std::slice;
+use std::vec;
+
+/// Like a `Vec`, but the elements are stored in pages of `1 << bits` elements.
+/// Growing it only ever allocates a new page, so existing elements are never moved.
 pub struct ArrayVec<D> {
     bits: usize,
     elements: usize,
     data: Vec<Vec<D>>,
 }
 impl<D> ArrayVec<D> {
+    /// Creates an `ArrayVec` whose pages are about 16KiB.
     pub fn new_auto() -> ArrayVec<D> {
         // We want `size_of::<D>() * (1 << bits) ≈ 16KiB`
         // So `bits ≈ log2(16KiB / size_of::<D>())
         let desired_size = (1024 * 16) as f32;
         use std::mem::size_of;
-        let bits = (desired_size / size_of::<D>() as f32).log2().round() as usize;
-        assert!(bits > 0);
-        assert!(bits < 64);
-        let actual_size = 1 << bits;
-        assert!(actual_size > 1);
-        assert!(actual_size <= desired_size * 2);
+        let bits = if size_of::<D>() == 0 {
+            // The pages don't take up any room at all!
+            16
+        } else {
+            let bits = (desired_size / size_of::<D>() as f32).log2().round();
+            // Very large elements get a page each.
+            if bits < 0.0 { 0 } else { bits as usize }
+        };
+        assert!(bits < 32);
         ArrayVec::new(bits)
     }
 
@@ -28,55 +40,219 @@ impl<D> ArrayVec<D> {
         }
     }
 
+    /// The number of elements in each page.
+    pub fn page_size(&self) -> usize { 1 << self.bits }
+
+    /// Splits an index into `(page, index within page)`.
     pub fn index(&self, i: usize) -> (usize, usize) {
         let low = i & ((1 << self.bits) - 1);
         let hig = i >> self.bits;
         (hig, low)
     }
 
+    pub fn len(&self) -> usize { self.elements }
+    pub fn is_empty(&self) -> bool { self.elements == 0 }
+
     pub fn push(&mut self, d: D) {
-        let size_per = 1 << self.bits;
-        if self.data.is_empty() || self.data.last().len() >= size_per {
+        let (hig, _) = self.index(self.elements);
+        if hig == self.data.len() {
+            let size_per = self.page_size();
             self.data.push(Vec::with_capacity(size_per));
         }
-        self.data.last().push(d);
+        self.data[hig].push(d);
+        self.elements += 1;
     }
 
-    pub fn get(&self, i: usize) -> &D {
-        let (hig, low) = self.index(i);
-        self.data[hig][low]
+    /// Makes room for `n` more elements. Only the list of pages is allocated.
+    pub fn reserve(&mut self, n: usize) {
+        let size_per = self.page_size();
+        let pages = (self.elements + n + size_per - 1) >> self.bits;
+        if pages > self.data.len() {
+            let more = pages - self.data.len();
+            self.data.reserve(more);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.elements { return; }
+        let size_per = self.page_size();
+        let pages = (len + size_per - 1) >> self.bits;
+        self.data.truncate(pages);
+        if let Some(last) = self.data.last_mut() {
+            let keep = len - ((pages - 1) << self.bits);
+            last.truncate(keep);
+        }
+        self.elements = len;
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.elements = 0;
+    }
+
+    pub fn get(&self, i: usize) -> Option<&D> {
+        if i >= self.elements { return None; }
+        unsafe { Some(self.get_unchecked(i)) }
+    }
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut D> {
+        if i >= self.elements { return None; }
+        unsafe { Some(self.get_unchecked_mut(i)) }
     }
-    pub fn get_mut(&self, i: usize) -> &mut D {
+    pub unsafe fn get_unchecked(&self, i: usize) -> &D {
         let (hig, low) = self.index(i);
-        self.data[hig][low]
+        self.data.get_unchecked(hig).get_unchecked(low)
     }
-    pub fn set(&self, i: usize, d: D) {
+    pub unsafe fn get_unchecked_mut(&mut self, i: usize) -> &mut D {
         let (hig, low) = self.index(i);
-        self.data[hig][low] = d;
+        self.data.get_unchecked_mut(hig).get_unchecked_mut(low)
+    }
+    pub fn set(&mut self, i: usize, d: D) {
+        *self.get_mut(i).expect("index out of bounds") = d;
+    }
+
+    /// Swaps two elements, which may be on different pages.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        assert!(a < self.elements && b < self.elements, "index out of bounds");
+        let (ha, la) = self.index(a);
+        let (hb, lb) = self.index(b);
+        if ha == hb {
+            self.data[ha].swap(la, lb);
+            return;
+        }
+        let ((lo, lo_i), (hi, hi_i)) = if ha < hb { ((ha, la), (hb, lb)) } else { ((hb, lb), (ha, la)) };
+        let (before, after) = self.data.split_at_mut(hi);
+        mem::swap(&mut before[lo][lo_i], &mut after[0][hi_i]);
+    }
+
+    /// Returns an iterator over each page's elements.
+    pub fn pages(&self) -> Pages<D> {
+        Pages { pages: self.data.iter() }
     }
 
-    pub fn iter<'a>(&'a self) -> ArrayVecIter<'a, &D> {
+    /// Returns an iterator over each page's elements.
+    pub fn pages_mut(&mut self) -> PagesMut<D> {
+        PagesMut { pages: self.data.iter_mut() }
+    }
+
+    pub fn iter(&self) -> ArrayVecIter<D> {
         ArrayVecIter {
-            iters: self.data.map(Vec::as_slice).collect(),
+            pages: self.pages(),
+            page: [].iter(),
         }
     }
 }
+impl<D: fmt::Debug> fmt::Debug for ArrayVec<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the pages of an `ArrayVec`.
+pub struct Pages<'a, D: 'a> {
+    pages: slice::Iter<'a, Vec<D>>,
+}
+impl<'a, D: 'a> Iterator for Pages<'a, D> {
+    type Item = &'a [D];
+    fn next(&mut self) -> Option<&'a [D]> { self.pages.next().map(Vec::as_slice) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.pages.size_hint() }
+}
+
+/// An iterator over the pages of an `ArrayVec`.
+pub struct PagesMut<'a, D: 'a> {
+    pages: slice::IterMut<'a, Vec<D>>,
+}
+impl<'a, D: 'a> Iterator for PagesMut<'a, D> {
+    type Item = &'a mut [D];
+    fn next(&mut self) -> Option<&'a mut [D]> { self.pages.next().map(Vec::as_mut_slice) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.pages.size_hint() }
+}
 
 pub struct ArrayVecIter<'a, D: 'a> {
-    iters: Vec<&'a [D]>,
+    pages: Pages<'a, D>,
+    page: slice::Iter<'a, D>,
 }
 impl<'a, D: 'a> Iterator for ArrayVecIter<'a, D> {
+    type Item = &'a D;
+    fn next(&mut self) -> Option<&'a D> {
+        loop {
+            if let Some(d) = self.page.next() {
+                return Some(d);
+            }
+            self.page = self.pages.next()?.iter();
+        }
+    }
+}
+
+pub struct IntoIter<D> {
+    pages: vec::IntoIter<Vec<D>>,
+    page: vec::IntoIter<D>,
+}
+impl<D> Iterator for IntoIter<D> {
     type Item = D;
     fn next(&mut self) -> Option<D> {
-        if self.iters.is_empty() { return None }
         loop {
-            match self.iters[0].split_first() {
-                None => self.iters.remove(0),
-                Some(head, tail) => {
-                    self.iters[0] = tail;
-                    return Some(head);
-                },
+            if let Some(d) = self.page.next() {
+                return Some(d);
             }
+            self.page = self.pages.next()?.into_iter();
+        }
+    }
+}
+impl<D> IntoIterator for ArrayVec<D> {
+    type Item = D;
+    type IntoIter = IntoIter<D>;
+    fn into_iter(self) -> IntoIter<D> {
+        IntoIter {
+            pages: self.data.into_iter(),
+            page: Vec::new().into_iter(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ArrayVec;
+
+    #[test]
+    fn auto_size() {
+        assert_eq!(ArrayVec::<u8>::new_auto().page_size(), 16 * 1024);
+        assert_eq!(ArrayVec::<u32>::new_auto().page_size(), 4 * 1024);
+        assert_eq!(ArrayVec::<[u8; 1 << 16]>::new_auto().page_size(), 1);
+        assert_eq!(ArrayVec::<()>::new_auto().page_size(), 1 << 16);
+    }
+
+    #[test]
+    fn push_does_not_relocate() {
+        let mut av = ArrayVec::new(2);
+        av.push(0);
+        let first: *const i32 = av.get(0).unwrap();
+        for i in 1..100 {
+            av.push(i);
+        }
+        assert_eq!(first, av.get(0).unwrap() as *const i32);
+        assert_eq!(av.len(), 100);
+        assert_eq!(av.pages().count(), 25);
+        assert!(av.pages().all(|p| p.len() == 4));
+        assert_eq!(av.iter().cloned().collect::<Vec<_>>(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn swap_and_truncate() {
+        let mut av = ArrayVec::new(2);
+        for i in 0..10 {
+            av.push(i);
         }
+        av.swap(1, 9);
+        assert_eq!(av.get(1), Some(&9));
+        assert_eq!(av.get(9), Some(&1));
+        // On the same page.
+        av.swap(3, 2);
+        assert_eq!(av.get(2), Some(&3));
+        av.swap(2, 3);
+        av.truncate(5);
+        assert_eq!(av.len(), 5);
+        assert_eq!(av.pages().map(|p| p.len()).collect::<Vec<_>>(), vec![4, 1]);
+        av.push(5);
+        assert_eq!(av.into_iter().collect::<Vec<_>>(), vec![0, 9, 2, 3, 4, 5]);
     }
 }
@@ -0,0 +1,357 @@
+//! v11's own binary format for `Extraction`s, for tables with `#[binary]`.
+//!
+//! The file starts with a header holding the `extraction_fmt`, domain, name, schema, and
+//! `ColumnMeta`s of the extraction, which is followed by the `RowId`s of the rows,
+//! and then each column as a length-prefixed block.
+//! `VecCol`s of `Pod` elements are written and read with a single copy,
+//! and `BoolCol`s are written as their packed bits.
+//! The elements of each block are aligned to 16 bytes from the start of the file,
+//! so that a `MappedCol` can use them where they are in a memory-mapped file.
+//!
+//! Numbers are stored in the byte order of the machine that wrote the file;
+//! reading the file on a machine with the other byte order fails.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::slice;
+use std::borrow::Cow;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::Storable;
+use crate::columns::{Col, TCol};
+use crate::storage::{VecCol, BoolCol, BitVec};
+use crate::map_index::{BTreeIndex, HashIndex};
+use crate::mapped::Mapping;
+use crate::edits::EditTracked;
+use crate::tables::{ColumnMeta, GetTableName};
+
+const MAGIC: &'static [u8; 4] = b"v11x";
+const BYTE_ORDER: u32 = 0x01020304;
+const ALIGN: u64 = 16;
+
+/// Plain old data: a `Copy` type with no padding, and no invalid bit patterns,
+/// so that its bytes can be written and read back as they are.
+pub unsafe trait Pod: Copy + Storable {}
+macro_rules! pod {
+    ($($ty:ty),*) => {$(
+        unsafe impl Pod for $ty {}
+    )*};
+}
+pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/// A column that can be written as a block of v11's binary format.
+pub trait BinaryCol: TCol + Sized {
+    fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()>;
+    fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self>;
+}
+
+/// The start of an extraction in v11's binary format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub extraction_fmt: u32,
+    pub domain: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+    pub schema: u32,
+    pub columns: Vec<ColumnMeta>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn as_bytes<E: Pod>(data: &[E]) -> &[u8] {
+    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * mem::size_of::<E>()) }
+}
+
+/// The size in bytes of `count` elements, if it fits in memory.
+fn byte_len<E>(count: u64) -> io::Result<usize> {
+    (mem::size_of::<E>() as u64)
+        .checked_mul(count)
+        .filter(|&n| n <= ::std::isize::MAX as u64)
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid("length is too large"))
+}
+
+/// Reading a stream grows the buffer by at most this many bytes at a time,
+/// so that a bad length fails at the end of the file, rather than by allocating it all up front.
+const CHUNK: usize = 1 << 20;
+
+fn padding(pos: u64) -> u64 {
+    (ALIGN - pos % ALIGN) % ALIGN
+}
+
+/// Writes v11's binary format, keeping track of the position for alignment.
+pub struct Writer<W: Write> {
+    out: W,
+    pos: u64,
+}
+impl<W: Write> Writer<W> {
+    pub fn new(out: W) -> Self {
+        Writer { out, pos: 0 }
+    }
+
+    pub fn into_inner(self) -> W { self.out }
+
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn u32(&mut self, n: u32) -> io::Result<()> { self.bytes(as_bytes(&[n])) }
+    fn u64(&mut self, n: u64) -> io::Result<()> { self.bytes(as_bytes(&[n])) }
+
+    fn str(&mut self, s: &str) -> io::Result<()> {
+        self.u64(s.len() as u64)?;
+        self.bytes(s.as_bytes())
+    }
+
+    /// Writes a block of `Pod` elements: their count, their size, and then the aligned elements.
+    pub fn slice<E: Pod>(&mut self, data: &[E]) -> io::Result<()> {
+        self.u64(data.len() as u64)?;
+        self.u64(mem::size_of::<E>() as u64)?;
+        let pad = [0u8; ALIGN as usize];
+        let n = padding(self.pos) as usize;
+        self.bytes(&pad[..n])?;
+        self.bytes(as_bytes(data))
+    }
+
+    pub fn header(&mut self, header: &Header) -> io::Result<()> {
+        self.bytes(MAGIC)?;
+        self.u32(BYTE_ORDER)?;
+        self.u32(header.extraction_fmt)?;
+        self.str(&header.domain)?;
+        self.str(&header.name)?;
+        self.u32(header.schema)?;
+        self.u64(header.columns.len() as u64)?;
+        for col in &header.columns {
+            self.str(&col.name)?;
+            self.str(&col.stored_type_name)?;
+            self.u64(col.version as u64)?;
+        }
+        Ok(())
+    }
+
+    pub fn col<C: BinaryCol, T: GetTableName>(&mut self, col: &Col<C, T>) -> io::Result<()> {
+        col.inner().write_block(self)
+    }
+}
+
+/// Reads v11's binary format, from a stream, or from a memory-mapped file.
+pub struct Reader<R: Read> {
+    input: R,
+    pos: u64,
+    mapping: Option<Arc<Mapping>>,
+}
+impl<R: Read> Reader<R> {
+    pub fn new(input: R) -> Self {
+        Reader { input, pos: 0, mapping: None }
+    }
+
+    /// Returns the file being read, if it is memory-mapped.
+    pub fn mapping(&self) -> Option<&Arc<Mapping>> { self.mapping.as_ref() }
+
+    /// The number of bytes read so far.
+    pub fn position(&self) -> u64 { self.pos }
+
+    fn bytes(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if let Some(ref mapping) = self.mapping {
+            let start = self.pos as usize;
+            let src = mapping.as_slice().get(start..start + buf.len()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "end of mapped file")
+            })?;
+            buf.copy_from_slice(src);
+        } else {
+            self.input.read_exact(buf)?;
+        }
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Skips over some bytes. This is free for a memory-mapped file.
+    pub fn skip(&mut self, n: u64) -> io::Result<()> {
+        if let Some(ref mapping) = self.mapping {
+            if n > self.remaining(mapping) {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of mapped file"));
+            }
+        } else if io::copy(&mut (&mut self.input).take(n), &mut io::sink())? != n {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of file"));
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    fn remaining(&self, mapping: &Mapping) -> u64 {
+        (mapping.as_slice().len() as u64).saturating_sub(self.pos)
+    }
+
+    fn pod<E: Pod>(&mut self) -> io::Result<E> {
+        let mut ret: Vec<E> = self.elements(1)?;
+        Ok(ret.pop().unwrap())
+    }
+
+    /// Reads `count` elements. The count comes from the file, so it isn't trusted.
+    fn elements<E: Pod>(&mut self, count: u64) -> io::Result<Vec<E>> {
+        let total = byte_len::<E>(count)?;
+        let count = count as usize;
+        let step = match self.mapping {
+            Some(ref mapping) => {
+                if total as u64 > self.remaining(mapping) {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of mapped file"));
+                }
+                count
+            },
+            None => ::std::cmp::max(1, CHUNK / ::std::cmp::max(1, mem::size_of::<E>())),
+        };
+        let mut ret: Vec<E> = Vec::new();
+        while ret.len() < count {
+            let n = ::std::cmp::min(step, count - ret.len());
+            ret.reserve_exact(n);
+            unsafe {
+                let len = ret.len();
+                let bytes = slice::from_raw_parts_mut(ret.as_mut_ptr().add(len) as *mut u8, n * mem::size_of::<E>());
+                self.bytes(bytes)?;
+                ret.set_len(len + n);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn str(&mut self) -> io::Result<Cow<'static, str>> {
+        let len: u64 = self.pod()?;
+        let bytes: Vec<u8> = self.elements(len)?;
+        String::from_utf8(bytes)
+            .map(Cow::Owned)
+            .map_err(|_| invalid("string is not UTF-8"))
+    }
+
+    /// Reads the start of a block written by `Writer::slice`, up to the elements.
+    /// Returns the number of elements, whose total size is known to fit in a `usize`.
+    pub fn block_start<E: Pod>(&mut self) -> io::Result<usize> {
+        let count: u64 = self.pod()?;
+        let size: u64 = self.pod()?;
+        if size != mem::size_of::<E>() as u64 {
+            return Err(invalid("element size mismatch"));
+        }
+        byte_len::<E>(count)?;
+        let pad = padding(self.pos);
+        self.skip(pad)?;
+        Ok(count as usize)
+    }
+
+    /// Reads a block written by `Writer::slice`.
+    pub fn vec<E: Pod>(&mut self) -> io::Result<Vec<E>> {
+        let count = self.block_start::<E>()?;
+        self.elements(count as u64)
+    }
+
+    pub fn header(&mut self) -> io::Result<Header> {
+        let mut magic = [0u8; 4];
+        self.bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a v11 binary extraction"));
+        }
+        if self.pod::<u32>()? != BYTE_ORDER {
+            return Err(invalid("byte order mismatch"));
+        }
+        let extraction_fmt = self.pod()?;
+        let domain = self.str()?;
+        let name = self.str()?;
+        let schema = self.pod()?;
+        let count: u64 = self.pod()?;
+        let mut columns = Vec::new();
+        for _ in 0..count {
+            columns.push(ColumnMeta {
+                name: self.str()?,
+                stored_type_name: self.str()?,
+                version: self.pod::<u64>()? as usize,
+            });
+        }
+        Ok(Header { extraction_fmt, domain, name, schema, columns })
+    }
+
+    pub fn col<C: BinaryCol, T: GetTableName>(&mut self) -> io::Result<Col<C, T>> {
+        let mut col = Col::new();
+        *col.inner_mut() = C::read_block(self)?;
+        Ok(col)
+    }
+}
+impl Reader<io::Empty> {
+    /// Reads a memory-mapped file. `MappedCol`s will use the file instead of copying it.
+    pub fn mapped(mapping: Arc<Mapping>) -> Self {
+        Reader { input: io::empty(), pos: 0, mapping: Some(mapping) }
+    }
+}
+
+impl<E: Pod> BinaryCol for VecCol<E> {
+    fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()> {
+        out.slice(&self.data)
+    }
+
+    fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self> {
+        Ok(VecCol { data: input.vec()? })
+    }
+}
+
+impl BinaryCol for BoolCol {
+    fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()> {
+        let mut bits = self.data.clone();
+        if self.ref_idx < bits.len() {
+            bits.set(self.ref_idx, self.ref_val);
+        }
+        out.u64(bits.len() as u64)?;
+        out.slice(bits.storage())
+    }
+
+    fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self> {
+        let len: u64 = input.pod()?;
+        let mut words: Vec<u32> = input.vec()?;
+        let len = len as usize;
+        // Each word holds 32 bits, and only the last one may be partly used.
+        let capacity = words.len() * 32;
+        if len > capacity || capacity - len >= 32 {
+            return Err(invalid("bit count doesn't match the words"));
+        }
+        if len % 32 != 0 {
+            // The unused bits must be clear.
+            *words.last_mut().unwrap() &= (1 << (len % 32)) - 1;
+        }
+        let mut data = BitVec::default();
+        unsafe {
+            mem::swap(data.storage_mut(), &mut words);
+            data.set_len(len);
+        }
+        Ok(BoolCol { data, .. BoolCol::default() })
+    }
+}
+
+impl<C: BinaryCol> BinaryCol for EditTracked<C> {
+    fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()> {
+        self.inner.write_block(out)
+    }
+
+    fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self> {
+        Ok(Self::from_inner(C::read_block(input)?))
+    }
+}
+
+macro_rules! binary_index {
+    ($($index:ident [$($bound:tt)*];)*) => {$(
+        impl<C: BinaryCol, T: GetTableName> BinaryCol for $index<C, T>
+        where C::Element: $($bound)*
+        {
+            fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()> {
+                self.inner.write_block(out)
+            }
+
+            fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self> {
+                Ok(Self::from_inner(C::read_block(input)?))
+            }
+        }
+    )*};
+}
+binary_index! {
+    BTreeIndex [Hash + Ord + Copy];
+    HashIndex [Hash + Eq + Copy];
+}
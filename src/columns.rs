@@ -5,6 +5,7 @@
 
 use std::ops::{Index, IndexMut};
 use std::marker::PhantomData;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 use crate::Storable;
 use crate::tables::{GetTableName, LockedTable, GenericRowId, CheckedRowId};
 
@@ -36,6 +37,8 @@ pub trait TCol: AnyCol {
     unsafe fn unchecked_swap(&mut self, a: usize, b: usize);
     /// Callback for when an element is deleted.
     unsafe fn deleted(&mut self, _i: usize) {}
+    /// Returns the rows containing `e`, if the column has an index that can find them.
+    fn lookup<'a>(&'a self, _e: Self::Element) -> Option<Box<Iterator<Item=usize> + 'a>> { None }
 
     fn checked_index(&self, i: usize) -> &Self::Element {
         if i >= self.len() {
@@ -46,6 +49,31 @@ pub trait TCol: AnyCol {
 
     type IntoIter: Iterator<Item=Self::Element>;
     fn into_iter(self) -> Self::IntoIter;
+
+    /// Serializes the column. By default this is a sequence of the elements.
+    #[doc(hidden)]
+    fn serialize_col<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where Self: Sized, Self::Element: Serialize
+    {
+        crate::serial::serialize_elements(self, serializer)
+    }
+
+    /// Deserializes what `serialize_col` wrote.
+    #[doc(hidden)]
+    fn deserialize_col<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    where Self: Sized, Self::Element: Deserialize<'de>
+    {
+        crate::serial::deserialize_elements(deserializer)
+    }
+}
+
+/// A column that keeps its elements in a single slice, in row order, so that they can be
+/// worked on in bulk.
+///
+/// Indexed columns don't implement this, because changing the slice would bypass the index.
+pub trait ContiguousCol: TCol {
+    fn as_slice(&self) -> &[Self::Element];
+    fn as_mut_slice(&mut self) -> &mut [Self::Element];
 }
 
 
@@ -128,6 +156,10 @@ pub struct MutA<'a, T: 'a>(&'a mut T);
 /// `RefA`, `MutA`, and `EditA` are wrappers that expose one interface to the world, but have a
 /// hidden interface for `table!` to use.
 pub struct EditA<'a, T: 'a>(&'a mut T);
+/// A `KeyA` is a column that can be `Index`ed, but not changed, because other rows depend on its
+/// values. This is used for `#[unique]` columns, indexed `#[sort_key]`s, and the columns of an
+/// `#[index(...)]`. Unlike `EditA`, it has no `set`.
+pub struct KeyA<'a, T: 'a>(&'a mut T);
 
 #[doc(hidden)]
 impl<'a, T: 'a> RefA<'a, T> {
@@ -146,6 +178,12 @@ impl<'a, T: 'a> EditA<'a, T> {
     pub fn deref(&self) -> &T { self.0 }
     pub fn deref_mut(&mut self) -> &mut T { self.0 }
 }
+#[doc(hidden)]
+impl<'a, T: 'a> KeyA<'a, T> {
+    pub fn new(t: &'a mut T) -> Self { KeyA(t) }
+    pub fn deref(&self) -> &T { self.0 }
+    pub fn deref_mut(&mut self) -> &mut T { self.0 }
+}
 
 // Forward indexing operations to `Col`.
 impl<'a, I, T: Index<I> + 'a> Index<I> for RefA<'a, T> {
@@ -163,16 +201,88 @@ impl<'a, I, T: Index<I> + 'a> Index<I> for EditA<'a, T> {
     #[inline]
     fn index(&self, i: I) -> &T::Output { &self.0[i] }
 }
+impl<'a, I, T: Index<I> + 'a> Index<I> for KeyA<'a, T> {
+    type Output = T::Output;
+    #[inline]
+    fn index(&self, i: I) -> &T::Output { &self.0[i] }
+}
 
 impl<'a, I, T: IndexMut<I> + 'a> IndexMut<I> for MutA<'a, T> {
     #[inline]
     fn index_mut(&mut self, i: I) -> &mut T::Output { &mut self.0[i] }
 }
 
+mod slices {
+    use super::*;
+    use crate::storage::{SoaChunkCol, SoaChunks, SoaChunksMut};
+
+    macro_rules! slice_on {
+        ($ty:ident) => {
+            impl<'a, C, T> $ty<'a, Col<C, T>>
+            where
+                C: ContiguousCol + 'a,
+                T: GetTableName,
+            {
+                /// Returns every element of the column. Row `i` is at index `i`.
+                pub fn as_slice(&self) -> &[C::Element] {
+                    self.deref().inner().as_slice()
+                }
+            }
+        };
+    }
+
+    slice_on!(RefA);
+    slice_on!(MutA);
+    slice_on!(EditA);
+    slice_on!(KeyA);
+
+    impl<'a, C, T> MutA<'a, Col<C, T>>
+    where
+        C: ContiguousCol + 'a,
+        T: GetTableName,
+    {
+        /// Returns every element of the column, for changing. Row `i` is at index `i`.
+        pub fn as_mut_slice(&mut self) -> &mut [C::Element] {
+            self.deref_mut().inner_mut().as_mut_slice()
+        }
+    }
+
+    macro_rules! chunks_on {
+        ($ty:ident) => {
+            impl<'a, E, T> $ty<'a, Col<SoaChunkCol<E>, T>>
+            where
+                E: Storable + Copy + Default,
+                T: GetTableName,
+            {
+                /// Returns an iterator over each chunk of the column. See `SoaChunkCol`.
+                pub fn chunks(&self) -> SoaChunks<E> {
+                    self.deref().inner().chunks()
+                }
+            }
+        };
+    }
+
+    chunks_on!(RefA);
+    chunks_on!(MutA);
+
+    impl<'a, E, T> MutA<'a, Col<SoaChunkCol<E>, T>>
+    where
+        E: Storable + Copy + Default,
+        T: GetTableName,
+    {
+        /// Returns an iterator over each chunk of the column, for changing. See `SoaChunkCol`.
+        pub fn chunks_mut(&mut self) -> SoaChunksMut<E> {
+            self.deref_mut().inner_mut().chunks_mut()
+        }
+    }
+}
+
 mod searching {
     use super::*;
     use std::hash::Hash;
-    use crate::map_index::{Indexes, BTreeIndex};
+    use crate::map_index::{Indexes, BTreeIndex, HashIndexes, HashIndex};
+    use crate::dictionary::InternCol;
+    use crate::index::Checkable;
 
     macro_rules! search_on {
         ($ty:ident) => {
@@ -200,4 +310,120 @@ mod searching {
     search_on!(RefA);
     search_on!(MutA);
     search_on!(EditA);
+    search_on!(KeyA);
+
+    macro_rules! hash_search_on {
+        ($ty:ident) => {
+            impl<'a, C, T> $ty<'a, Col<HashIndex<C, T>, T>>
+            where
+                C: TCol + 'a,
+                T: GetTableName,
+                C::Element: Hash + Eq + Copy,
+            {
+                pub fn find<'b>(&'a self, e: C::Element) -> HashIndexes<'b, T>
+                where 'a: 'b
+                {
+                    self.deref().inner().find(e)
+                }
+            }
+        };
+    }
+
+    hash_search_on!(RefA);
+    hash_search_on!(MutA);
+    hash_search_on!(EditA);
+    hash_search_on!(KeyA);
+
+    macro_rules! intern_search_on {
+        ($ty:ident) => {
+            impl<'a, T> $ty<'a, Col<InternCol, T>>
+            where
+                T: GetTableName,
+            {
+                /// Returns the rows containing `s`. See `InternCol::find`.
+                pub fn find<'b>(&'b self, s: &str) -> impl Iterator<Item=GenericRowId<T>> + 'b {
+                    self.deref().inner().find(s).map(GenericRowId::from_usize)
+                }
+            }
+        };
+    }
+
+    intern_search_on!(RefA);
+    intern_search_on!(MutA);
+    intern_search_on!(EditA);
+    intern_search_on!(KeyA);
+
+    macro_rules! set_on {
+        ($ty:ident, $index:ident, $($bound:tt)*) => {
+            impl<'a, C, T> $ty<'a, Col<$index<C, T>, T>>
+            where
+                C: TCol + 'a,
+                T: GetTableName,
+                C::Element: $($bound)*,
+            {
+                /// Replaces an element, and moves its row to the new value's place in the index.
+                pub fn set<I: Checkable<Row=T>>(&mut self, i: I, mut e: C::Element) {
+                    let col = self.deref_mut().inner_mut();
+                    let i = i.uncheck().to_usize();
+                    if i >= col.len() {
+                        panic!("Index out of range: Size is {}, but index is {}", col.len(), i);
+                    }
+                    unsafe { col.unchecked_swap_out(i, &mut e); }
+                }
+
+                /// Modifies an element in place. The index is updated once `f` returns.
+                pub fn modify<I, F, R>(&mut self, i: I, f: F) -> R
+                where
+                    I: Checkable<Row=T>,
+                    F: FnOnce(&mut C::Element) -> R,
+                {
+                    let mut guard = WriteBack::new(self.deref_mut().inner_mut(), i.uncheck().to_usize());
+                    f(&mut guard)
+                }
+            }
+        };
+    }
+
+    set_on!(MutA, BTreeIndex, Hash + Ord + Copy);
+    set_on!(EditA, BTreeIndex, Hash + Ord + Copy);
+    set_on!(MutA, HashIndex, Hash + Eq + Copy);
+    set_on!(EditA, HashIndex, Hash + Eq + Copy);
+}
+
+/// A copy of an element of an indexed column. It is written back to the column when dropped,
+/// so that the index sees the change.
+pub struct WriteBack<'a, C: TCol + 'a>
+where C::Element: Copy
+{
+    col: &'a mut C,
+    i: usize,
+    value: C::Element,
+}
+impl<'a, C: TCol + 'a> WriteBack<'a, C>
+where C::Element: Copy
+{
+    #[doc(hidden)]
+    pub fn new(col: &'a mut C, i: usize) -> Self {
+        let value = *col.checked_index(i);
+        WriteBack { col, i, value }
+    }
+}
+impl<'a, C: TCol + 'a> ::std::ops::Deref for WriteBack<'a, C>
+where C::Element: Copy
+{
+    type Target = C::Element;
+    fn deref(&self) -> &C::Element { &self.value }
+}
+impl<'a, C: TCol + 'a> ::std::ops::DerefMut for WriteBack<'a, C>
+where C::Element: Copy
+{
+    fn deref_mut(&mut self) -> &mut C::Element { &mut self.value }
+}
+impl<'a, C: TCol + 'a> Drop for WriteBack<'a, C>
+where C::Element: Copy
+{
+    fn drop(&mut self) {
+        let mut value = self.value;
+        unsafe { self.col.unchecked_swap_out(self.i, &mut value); }
+    }
 }
@@ -0,0 +1,368 @@
+//! Dictionary-encoded strings, for columns with many repeats of a few values.
+//!
+//! A column of `[IStr; InternCol]` stores a `u32` code for each row, and a dictionary of the
+//! distinct strings. Indexing it gives an `IStr`, which derefs to `str`.
+//! Extractions write the dictionary out once, followed by the codes.
+//!
+//! The column also keeps the rows of each code, so `find` only looks at the rows that match.
+//! This is instead of `#[index]`, which `InternCol`s can't have. Deleted rows are dropped from it.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, btree_set, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
+use std::mem;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::vec;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::Error;
+
+use crate::columns::TCol;
+
+/// A shared string. Cloning one doesn't copy the string.
+///
+/// An `InternCol` keeps one `IStr` for each distinct string in its dictionary,
+/// and hands out references to it.
+#[derive(Clone)]
+pub struct IStr(Arc<str>);
+impl IStr {
+    pub fn new(s: &str) -> IStr { IStr(Arc::from(s)) }
+
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+impl Default for IStr {
+    fn default() -> IStr { IStr::new("") }
+}
+impl<'a> From<&'a str> for IStr {
+    fn from(s: &'a str) -> IStr { IStr::new(s) }
+}
+impl From<String> for IStr {
+    fn from(s: String) -> IStr { IStr(Arc::from(s)) }
+}
+impl Deref for IStr {
+    type Target = str;
+    fn deref(&self) -> &str { &self.0 }
+}
+impl AsRef<str> for IStr {
+    fn as_ref(&self) -> &str { &self.0 }
+}
+impl Borrow<str> for IStr {
+    fn borrow(&self) -> &str { &self.0 }
+}
+impl PartialEq for IStr {
+    fn eq(&self, other: &IStr) -> bool { Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0 }
+}
+impl Eq for IStr {}
+impl Hash for IStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Must agree with `Borrow<str>`.
+        self.as_str().hash(state)
+    }
+}
+impl PartialOrd for IStr {
+    fn partial_cmp(&self, other: &IStr) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for IStr {
+    fn cmp(&self, other: &IStr) -> Ordering { self.as_str().cmp(other.as_str()) }
+}
+impl PartialEq<str> for IStr {
+    fn eq(&self, other: &str) -> bool { self.as_str() == other }
+}
+impl<'a> PartialEq<&'a str> for IStr {
+    fn eq(&self, other: &&'a str) -> bool { self.as_str() == *other }
+}
+impl fmt::Debug for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Debug::fmt(self.as_str(), f) }
+}
+impl fmt::Display for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(self.as_str(), f) }
+}
+impl Serialize for IStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+impl<'de> Deserialize<'de> for IStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IStr, D::Error> {
+        String::deserialize(deserializer).map(IStr::from)
+    }
+}
+
+/// Stores each row's string as a `u32` code into a dictionary of the strings in the column.
+///
+/// Strings stay in the dictionary until the column is cleared,
+/// but only the ones that are still used are serialized.
+///
+/// Rows are numbered with `u32`s, so the column can't have more than `u32::MAX` rows.
+#[derive(Debug)]
+pub struct InternCol {
+    codes: Vec<u32>,
+    dictionary: Vec<IStr>,
+    lookup: HashMap<IStr, u32>,
+    /// The rows holding each code.
+    rows: Vec<BTreeSet<u32>>,
+    // As in `BoolCol`, `unchecked_index_mut` hands out a reference to this,
+    // and it is encoded the next time the column is changed.
+    ref_val: IStr,
+    ref_idx: usize,
+}
+impl InternCol {
+    fn flush(&mut self) {
+        if self.ref_idx >= self.codes.len() { return }
+        let i = mem::replace(&mut self.ref_idx, ::std::usize::MAX);
+        let val = mem::replace(&mut self.ref_val, IStr::default());
+        let code = self.encode(&val);
+        self.set_code(i, code);
+    }
+
+    fn encode(&mut self, s: &IStr) -> u32 {
+        if let Some(&code) = self.lookup.get(s.as_str()) {
+            return code;
+        }
+        let code = self.dictionary.len();
+        if code > ::std::u32::MAX as usize {
+            panic!("InternCol dictionary is full");
+        }
+        let code = code as u32;
+        self.dictionary.push(s.clone());
+        self.lookup.insert(s.clone(), code);
+        self.rows.push(BTreeSet::new());
+        code
+    }
+
+    fn set_code(&mut self, i: usize, code: u32) {
+        // A deleted row isn't in `rows`, even if its code doesn't change.
+        let old = self.codes[i];
+        self.rows[old as usize].remove(&(i as u32));
+        self.rows[code as usize].insert(i as u32);
+        self.codes[i] = code;
+    }
+
+    /// The strings that have been stored in the column, indexed by their code.
+    pub fn dictionary(&self) -> &[IStr] { &self.dictionary }
+
+    /// Returns the code of `s`, if it has been stored in the column.
+    pub fn code_of(&self, s: &str) -> Option<u32> {
+        self.lookup.get(s).cloned()
+    }
+
+    /// Returns the rows containing `s`, in order. The string is looked up once,
+    /// and then only the rows with its code are visited.
+    pub fn find(&self, s: &str) -> InternRows {
+        let rows = self.code_of(s).map(|code| self.rows[code as usize].iter().peekable());
+        let pending = self.ref_idx < self.codes.len();
+        InternRows {
+            rows,
+            ref_idx: self.ref_idx,
+            ref_matches: pending && self.ref_val == *s,
+            ref_done: false,
+        }
+    }
+}
+impl TCol for InternCol {
+    type Element = IStr;
+
+    fn new() -> InternCol {
+        InternCol {
+            codes: Vec::new(),
+            dictionary: Vec::new(),
+            lookup: HashMap::new(),
+            rows: Vec::new(),
+            ref_val: IStr::default(),
+            ref_idx: ::std::usize::MAX,
+        }
+    }
+
+    fn len(&self) -> usize { self.codes.len() }
+    fn truncate(&mut self, len: usize) {
+        self.flush();
+        while self.codes.len() > len {
+            let code = self.codes.pop().unwrap();
+            self.rows[code as usize].remove(&(self.codes.len() as u32));
+        }
+    }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element {
+        if i == self.ref_idx {
+            &self.ref_val
+        } else {
+            self.dictionary.get_unchecked(*self.codes.get_unchecked(i) as usize)
+        }
+    }
+    unsafe fn unchecked_index_mut(&mut self, i: usize) -> &mut Self::Element {
+        self.flush();
+        self.ref_idx = i;
+        self.ref_val = self.dictionary[self.codes[i] as usize].clone();
+        &mut self.ref_val
+    }
+    fn reserve(&mut self, n: usize) { self.codes.reserve(n) }
+    fn clear(&mut self) {
+        self.codes.clear();
+        self.dictionary.clear();
+        self.lookup.clear();
+        self.rows.clear();
+        self.ref_val = IStr::default();
+        self.ref_idx = ::std::usize::MAX;
+    }
+    fn push(&mut self, v: Self::Element) {
+        let code = self.encode(&v);
+        let i = self.codes.len();
+        if i >= ::std::u32::MAX as usize {
+            panic!("InternCol is full");
+        }
+        self.codes.push(code);
+        self.rows[code as usize].insert(i as u32);
+    }
+    unsafe fn unchecked_swap_out(&mut self, i: usize, new: &mut Self::Element) {
+        self.flush();
+        let code = self.encode(new);
+        *new = self.dictionary[self.codes[i] as usize].clone();
+        self.set_code(i, code);
+    }
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) {
+        self.flush();
+        let (code_a, code_b) = (self.codes[a], self.codes[b]);
+        self.set_code(a, code_b);
+        self.set_code(b, code_a);
+    }
+    unsafe fn deleted(&mut self, i: usize) {
+        self.flush();
+        self.rows[self.codes[i] as usize].remove(&(i as u32));
+    }
+
+    fn lookup<'a>(&'a self, e: Self::Element) -> Option<Box<Iterator<Item=usize> + 'a>> {
+        Some(Box::new(self.find(&e)))
+    }
+
+    type IntoIter = vec::IntoIter<IStr>;
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.flush();
+        let dictionary = self.dictionary;
+        self.codes
+            .into_iter()
+            .map(|c| dictionary[c as usize].clone())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn serialize_col<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Renumber the strings that are in use.
+        let mut encoded = Encoded {
+            dictionary: Vec::new(),
+            codes: Vec::with_capacity(self.len()),
+        };
+        let mut lookup = HashMap::new();
+        for i in 0..self.len() {
+            let s = unsafe { self.unchecked_index(i) };
+            let dictionary = &mut encoded.dictionary;
+            let code = *lookup.entry(s.as_str()).or_insert_with(|| {
+                dictionary.push(s.clone());
+                dictionary.len() as u32 - 1
+            });
+            encoded.codes.push(code);
+        }
+        encoded.serialize(serializer)
+    }
+
+    fn deserialize_col<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = Encoded::deserialize(deserializer)?;
+        let mut col = InternCol::new();
+        // Repeated strings get the same code.
+        let codes: Vec<u32> = encoded.dictionary.iter().map(|s| col.encode(s)).collect();
+        col.codes.reserve(encoded.codes.len());
+        for c in encoded.codes {
+            let code = codes.get(c as usize).cloned().ok_or_else(|| {
+                D::Error::custom(format!("code {} is not in the dictionary of {} strings", c, codes.len()))
+            })?;
+            col.rows[code as usize].insert(col.codes.len() as u32);
+            col.codes.push(code);
+        }
+        Ok(col)
+    }
+}
+
+/// How an `InternCol` is serialized.
+#[derive(Serialize, Deserialize)]
+struct Encoded {
+    dictionary: Vec<IStr>,
+    codes: Vec<u32>,
+}
+
+/// An iterator over the rows of an `InternCol` containing a string.
+pub struct InternRows<'a> {
+    rows: Option<Peekable<btree_set::Iter<'a, u32>>>,
+    // The row being written to through `unchecked_index_mut` isn't in `rows` yet, and may not
+    // belong there any more.
+    ref_idx: usize,
+    ref_matches: bool,
+    ref_done: bool,
+}
+impl<'a> Iterator for InternRows<'a> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            let next = self.rows.as_mut().and_then(|rows| rows.peek()).map(|&&r| r as usize);
+            if self.ref_matches && !self.ref_done && next.map_or(true, |r| self.ref_idx <= r) {
+                self.ref_done = true;
+                if next == Some(self.ref_idx) {
+                    self.rows.as_mut().map(|rows| rows.next());
+                }
+                return Some(self.ref_idx);
+            }
+            let r = *self.rows.as_mut()?.next()? as usize;
+            if r == self.ref_idx && !self.ref_matches {
+                continue;
+            }
+            return Some(r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IStr, InternCol};
+    use crate::columns::TCol;
+
+    #[test]
+    fn istr() {
+        let a = IStr::new("apple");
+        let b = IStr::new(&"apple".to_owned());
+        assert_eq!(a, b);
+        assert_eq!(a, "apple");
+        assert!(IStr::new("banana") > a);
+    }
+
+    #[test]
+    fn codes() {
+        let mut col = InternCol::new();
+        for s in &["red", "green", "red", "blue", "red"] {
+            col.push(IStr::new(s));
+        }
+        assert_eq!(col.dictionary().len(), 3);
+        assert_eq!(col.code_of("red"), Some(0));
+        assert_eq!(col.find("red").collect::<Vec<_>>(), vec![0, 2, 4]);
+        unsafe {
+            *col.unchecked_index_mut(2) = IStr::new("green");
+            assert_eq!(*col.unchecked_index(2), "green");
+            assert_eq!(col.find("red").collect::<Vec<_>>(), vec![0, 4]);
+            assert_eq!(col.find("green").collect::<Vec<_>>(), vec![1, 2]);
+            col.unchecked_swap(0, 3);
+            assert_eq!(col.find("red").collect::<Vec<_>>(), vec![3, 4]);
+            assert_eq!(col.find("blue").collect::<Vec<_>>(), vec![0]);
+        }
+        col.truncate(4);
+        assert_eq!(col.find("red").collect::<Vec<_>>(), vec![3]);
+        unsafe {
+            col.deleted(1);
+            assert_eq!(col.find("green").collect::<Vec<_>>(), vec![2]);
+            // Filling the hole with the same string puts the row back.
+            col.unchecked_swap_out(1, &mut IStr::new("green"));
+            assert_eq!(col.find("green").collect::<Vec<_>>(), vec![1, 2]);
+        }
+        let all: Vec<IStr> = col.into_iter().collect();
+        assert_eq!(all, vec!["blue", "green", "green", "red"]);
+    }
+}
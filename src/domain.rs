@@ -6,11 +6,12 @@
 
 use std::fmt;
 use std::collections::HashMap;
-use std::sync::{RwLock, Arc};
+use std::sync::{RwLock, RwLockWriteGuard, Arc};
 
 use crate::intern;
 use crate::intern::PBox;
-use crate::property::{GlobalPropertyId, PropertyName, DomainedPropertyId};
+use crate::error::Error;
+use crate::property::{GlobalPropertyId, PropertyName, DomainedPropertyId, PropertySerial};
 
 /// A single-level namespace.
 /// # Usage
@@ -334,13 +335,20 @@ impl DomainInstance {
     }
 
     pub fn get_generic_table(&self, name: TableName) -> &RwLock<GenericTable> {
-        self.tables.get(&name).unwrap_or_else(|| {
-            println!("Tables in this domain:");
-            for t in self.tables.keys() {
-                println!("{}", t);
-            }
-            panic!("Table {:?} is not in domain {:?}", name, self.name);
-        })
+        self.try_get_generic_table(name.0).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `get_generic_table`, but returns an error if the table isn't in this domain.
+    pub fn try_get_generic_table(&self, name: &str) -> Result<&RwLock<GenericTable>, Error> {
+        self.tables.get(name).ok_or_else(|| self.unknown_table(name))
+    }
+
+    pub(crate) fn unknown_table(&self, name: &str) -> Error {
+        Error::UnknownTable {
+            domain: self.name.0.to_owned(),
+            table: name.to_owned(),
+            known: self.tables_registration_order.iter().map(|t| t.0.to_owned()).collect(),
+        }
     }
 }
 
@@ -348,6 +356,8 @@ pub trait Producer: 'static + Send + Sync {
     fn produce(&self) -> PBox;
     fn domain(&self) -> DomainName;
     fn name(&self) -> PropertyName;
+    /// Properties declared with `#[save]` can be serialized.
+    fn serial(&self) -> Option<PropertySerial> { None }
 }
 pub struct FmtProducer(pub(crate) Box<Producer>);
 impl fmt::Debug for FmtProducer {
@@ -403,6 +413,11 @@ pub fn clone_globals() -> GlobalsLock {
     V11_GLOBALS.read().unwrap().clone()
 }
 
+/// Locks the globals for writing, without panicking if the lock is poisoned.
+pub(crate) fn try_write_globals(globals: &GlobalsLock) -> Result<RwLockWriteGuard<GlobalProperties>, Error> {
+    globals.write().map_err(|_| Error::Poisoned("The global registry".to_owned()))
+}
+
 /// Should be called within each dynamic library.
 pub fn sync_globals(globals: GlobalsLock) {
     let mut old = V11_GLOBALS.write().unwrap();
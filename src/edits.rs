@@ -0,0 +1,160 @@
+//! Recording which rows of a column were changed in place, for `#[track_edits]`.
+
+use std::hash::Hash;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+use crate::columns::TCol;
+use crate::storage::BitVec;
+use crate::map_index::{BTreeIndex, HashIndex};
+use crate::tables::GetTableName;
+
+/// A `TCol` wrapper that sets a bit whenever an element is mutably indexed or replaced.
+/// Pushed rows start out clean; deleted rows are forgotten. Moving elements around isn't an edit,
+/// so swapped elements keep their bits.
+pub struct EditTracked<C: TCol> {
+    pub(crate) inner: C,
+    edited: BitVec,
+}
+impl<C: TCol> EditTracked<C> {
+    pub(crate) fn from_inner(inner: C) -> Self {
+        let edited = BitVec::from_elem(inner.len(), false);
+        EditTracked { inner, edited }
+    }
+}
+impl<C: TCol> TCol for EditTracked<C> {
+    type Element = C::Element;
+
+    fn new() -> Self {
+        EditTracked {
+            inner: C::new(),
+            edited: BitVec::new(),
+        }
+    }
+
+    fn len(&self) -> usize { self.inner.len() }
+    fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len);
+        self.edited.truncate(len);
+    }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element { self.inner.unchecked_index(i) }
+    unsafe fn unchecked_index_mut(&mut self, i: usize) -> &mut Self::Element {
+        self.edited.set(i, true);
+        self.inner.unchecked_index_mut(i)
+    }
+    fn reserve(&mut self, n: usize) {
+        self.inner.reserve(n);
+        self.edited.reserve(n);
+    }
+    fn clear(&mut self) {
+        self.inner.clear();
+        self.edited.truncate(0);
+    }
+    fn push(&mut self, v: Self::Element) {
+        self.inner.push(v);
+        self.edited.push(false);
+    }
+
+    unsafe fn unchecked_swap_out(&mut self, i: usize, new: &mut Self::Element) {
+        self.edited.set(i, true);
+        self.inner.unchecked_swap_out(i, new);
+    }
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) {
+        let edited_a = self.edited.get(a).unwrap_or(false);
+        let edited_b = self.edited.get(b).unwrap_or(false);
+        self.edited.set(a, edited_b);
+        self.edited.set(b, edited_a);
+        self.inner.unchecked_swap(a, b);
+    }
+    unsafe fn deleted(&mut self, i: usize) {
+        self.edited.set(i, false);
+        self.inner.deleted(i);
+    }
+
+    type IntoIter = C::IntoIter;
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+
+    fn serialize_col<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where Self: Sized, Self::Element: Serialize
+    {
+        self.inner.serialize_col(serializer)
+    }
+
+    fn deserialize_col<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    where Self: Sized, Self::Element: Deserialize<'de>
+    {
+        C::deserialize_col(deserializer).map(Self::from_inner)
+    }
+}
+
+/// Columns with `#[track_edits]`, possibly under an index.
+pub trait TrackEdits {
+    /// Returns `true` if the element at `i` was changed since the edits were last cleared.
+    fn is_edited(&self, i: usize) -> bool;
+    /// Returns the positions of the changed elements, in order.
+    fn edited(&self) -> Vec<usize>;
+    fn clear_edits(&mut self);
+}
+impl<C: TCol> TrackEdits for EditTracked<C> {
+    fn is_edited(&self, i: usize) -> bool {
+        self.edited.get(i).unwrap_or(false)
+    }
+
+    fn edited(&self) -> Vec<usize> {
+        let len = self.edited.len();
+        let mut ret = Vec::new();
+        for (w, &word) in self.edited.storage().iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                ret.push(w * 32 + word.trailing_zeros() as usize);
+                word &= word - 1;
+            }
+        }
+        ret.retain(|&i| i < len);
+        ret
+    }
+
+    fn clear_edits(&mut self) {
+        self.edited.clear();
+    }
+}
+impl<C: TCol, T: GetTableName> TrackEdits for BTreeIndex<EditTracked<C>, T>
+where C::Element: Hash + Ord + Copy
+{
+    fn is_edited(&self, i: usize) -> bool { self.inner.is_edited(i) }
+    fn edited(&self) -> Vec<usize> { self.inner.edited() }
+    fn clear_edits(&mut self) { self.inner.clear_edits() }
+}
+impl<C: TCol, T: GetTableName> TrackEdits for HashIndex<EditTracked<C>, T>
+where C::Element: Hash + Eq + Copy
+{
+    fn is_edited(&self, i: usize) -> bool { self.inner.is_edited(i) }
+    fn edited(&self) -> Vec<usize> { self.inner.edited() }
+    fn clear_edits(&mut self) { self.inner.clear_edits() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EditTracked, TrackEdits};
+    use crate::columns::TCol;
+    use crate::storage::VecCol;
+
+    #[test]
+    fn swaps_move_the_marks() {
+        let mut col = EditTracked::<VecCol<u8>>::new();
+        for i in 0..4 {
+            col.push(i);
+        }
+        unsafe {
+            *col.unchecked_index_mut(1) = 10;
+            col.unchecked_swap(1, 3);
+            col.unchecked_swap(0, 2);
+        }
+        assert_eq!(col.edited(), vec![3]);
+        unsafe {
+            assert_eq!(*col.unchecked_index(3), 10);
+        }
+    }
+}
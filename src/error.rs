@@ -0,0 +1,63 @@
+//! Errors for looking up domains, tables, columns, and properties that might not exist.
+//!
+//! The usual functions panic, because a wrong name is a bug when it comes from the code.
+//! Their `try_` variants return these errors instead, for names that come from somewhere else,
+//! like a script or a plugin.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The domain was never registered, or isn't in this `Universe`.
+    UnknownDomain(String),
+    /// The domain can't be changed, because a `Universe` was already made with it.
+    DomainLocked { domain: String, name: String },
+    /// `known` lists the tables that are in the domain.
+    UnknownTable { domain: String, table: String, known: Vec<String> },
+    /// A table was registered twice, with different columns. `new` and `existing` describe them.
+    TableMismatch { domain: String, table: String, new: String, existing: String },
+    UnknownColumn { table: String, column: String },
+    ColumnType { table: String, column: String, stored: String, requested: String },
+    /// The column can't be changed through `DynColumnMut::set_from`.
+    ReadOnlyColumn(String),
+    RowOutOfRange { row: usize, len: usize },
+    /// The row is in the free-list of a consistent table.
+    RowDeleted(usize),
+    Deserialize(String),
+    PropertyNotRegistered(String),
+    PropertyNotInUniverse(String),
+    PropertyNotInitialized(String),
+    /// The property was added to its domain after the `Universe` was made.
+    PropertyAddedLate(String),
+    PropertyType(String),
+    /// A domain, table, or property name that isn't a valid identifier.
+    InvalidName(String),
+    /// A lock was poisoned by a panic. See `Universe::repair`.
+    Poisoned(String),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownDomain(ref domain) => write!(f, "Unregistered domain {}", domain),
+            Error::DomainLocked { ref domain, ref name } => write!(f, "Adding {}/{} to a locked domain", domain, name),
+            Error::UnknownTable { ref domain, ref table, ref known } => write!(f, "Table {:?} is not in domain {:?}, which has {:?}", table, domain, known),
+            Error::TableMismatch { ref new, ref existing, .. } => write!(f, "Tried to register {} on top of an existing table with different structure, {}", new, existing),
+            Error::UnknownColumn { ref table, ref column } => write!(f, "Table {} doesn't have a {} column.", table, column),
+            Error::ColumnType { ref table, ref column, ref stored, ref requested } => write!(f, "Column {}/{} has datatype {:?}, not {:?}", table, column, stored, requested),
+            Error::ReadOnlyColumn(ref column) => write!(f, "Column {} can't be set dynamically", column),
+            Error::RowOutOfRange { row, len } => write!(f, "Index out of range: Size is {}, but index is {}", len, row),
+            Error::RowDeleted(row) => write!(f, "Row {} was deleted", row),
+            Error::Deserialize(ref msg) => write!(f, "{}", msg),
+            Error::PropertyNotRegistered(ref prop) => write!(f, "The property {} was not registered.", prop),
+            Error::PropertyNotInUniverse(ref prop) => write!(f, "The property {} is not in this Universe's domain.", prop),
+            Error::PropertyNotInitialized(ref prop) => write!(f, "The property {} was never initialized.", prop),
+            Error::PropertyAddedLate(ref prop) => write!(f, "The property {} was added to the domain AFTER this Universe was created.", prop),
+            Error::PropertyType(ref prop) => write!(f, "Downcast of property {} failed.", prop),
+            Error::InvalidName(ref name) => write!(f, "Invalid name {:?}", name),
+            Error::Poisoned(ref what) => write!(f, "{} is poisoned", what),
+        }
+    }
+}
+impl ::std::error::Error for Error {}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
@@ -0,0 +1,264 @@
+//! Generational handles, used by `#[kind = "indirect"]` tables.
+//!
+//! A handle packs a slot number into the low half of the table's `#[row_id]` type,
+//! and the slot's generation into the high half. Deleting a row bumps the generation of its slot,
+//! so any old handle to it stops resolving, even after the slot is reused.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+
+use num_traits::{ToPrimitive, FromPrimitive};
+
+use crate::tables::{GetTableName, LockedTable};
+use crate::index::{GenericRowId, CheckedRowId, Checkable};
+
+/// A generational handle to a row. Unlike a `RowId`, it keeps referring to the same row as other
+/// rows are deleted, and it can not be made to refer to a different row.
+pub struct GenericHandle<T: GetTableName> {
+    #[doc(hidden)]
+    pub raw: T::Idx,
+    #[doc(hidden)]
+    pub table: PhantomData<T>,
+}
+impl<T: GetTableName> GenericHandle<T> {
+    fn slot_bits() -> u32 { (mem::size_of::<T::Idx>() * 8 / 2) as u32 }
+    fn slot_mask() -> u64 { (1u64 << Self::slot_bits()) - 1 }
+    fn generation_mask() -> u64 {
+        let bits = (mem::size_of::<T::Idx>() * 8) as u32 - Self::slot_bits();
+        if bits >= 64 { !0 } else { (1u64 << bits) - 1 }
+    }
+
+    #[doc(hidden)]
+    pub fn new(slot: usize, generation: u64) -> Self {
+        let slot = slot as u64;
+        if slot > Self::slot_mask() {
+            panic!("table {} is out of handle slots", T::get_name());
+        }
+        let raw = ((generation & Self::generation_mask()) << Self::slot_bits()) | slot;
+        Self::from_raw(T::Idx::from_u64(raw).unwrap())
+    }
+
+    pub fn from_raw(raw: T::Idx) -> Self {
+        GenericHandle {
+            raw,
+            table: PhantomData,
+        }
+    }
+    pub fn to_raw(&self) -> T::Idx { self.raw }
+
+    pub fn slot(&self) -> usize { (self.raw.to_u64().unwrap() & Self::slot_mask()) as usize }
+    pub fn generation(&self) -> u64 { self.raw.to_u64().unwrap() >> Self::slot_bits() }
+}
+impl<T: GetTableName> Clone for GenericHandle<T> {
+    fn clone(&self) -> Self { Self::from_raw(self.raw) }
+}
+impl<T: GetTableName> Copy for GenericHandle<T> {}
+impl<T: GetTableName> PartialEq for GenericHandle<T> {
+    fn eq(&self, other: &Self) -> bool { self.raw == other.raw }
+}
+impl<T: GetTableName> Eq for GenericHandle<T> {}
+impl<T: GetTableName> ::std::hash::Hash for GenericHandle<T> {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) { self.raw.hash(state) }
+}
+impl<T: GetTableName> fmt::Debug for GenericHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[#{}/{}]", T::get_name().0, self.slot(), self.generation())
+    }
+}
+use serde::ser::{Serialize, Serializer};
+impl<T: GetTableName> Serialize for GenericHandle<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        self.raw.serialize(serializer)
+    }
+}
+use serde::de::{Deserialize, Deserializer};
+impl<'de, T: GetTableName> Deserialize<'de> for GenericHandle<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = T::Idx::deserialize(deserializer)?;
+        Ok(GenericHandle::from_raw(raw))
+    }
+}
+
+impl<T: GetTableName> Checkable for GenericHandle<T> {
+    type Row = T;
+    /// Panics if the handle's row has been deleted.
+    fn check<L>(self, table: &L) -> CheckedRowId<L>
+    where L: LockedTable<Row=Self::Row>
+    {
+        match table.resolve_handle(self) {
+            Some(i) => i.check(table),
+            None => panic!("stale handle {:?}", self),
+        }
+    }
+
+    /// Returns `false` if the handle's row has been deleted, even if its slot has been reused.
+    fn is_valid<L>(&self, table: &L) -> bool
+    where L: LockedTable<Row=Self::Row>
+    {
+        table.resolve_handle(*self).is_some()
+    }
+
+    /// Handles can't be turned into a `RowId` without their table, so this panics.
+    /// Use `handle.check(&table).uncheck()`, or `table.resolve(handle)`.
+    fn uncheck(self) -> GenericRowId<T> {
+        panic!("can't uncheck handle {:?} without its table", self)
+    }
+}
+
+/// Maps handles to the rows of an `#[kind = "indirect"]` table.
+#[doc(hidden)]
+pub struct Handles<T: GetTableName> {
+    /// The current generation of each slot, and the row it refers to.
+    slots: Vec<(u64, Option<usize>)>,
+    /// The slot of each row.
+    owners: Vec<usize>,
+    /// Slots that aren't referring to any row.
+    free: Vec<usize>,
+    table: PhantomData<T>,
+}
+impl<T: GetTableName> Default for Handles<T> {
+    fn default() -> Self {
+        Handles {
+            slots: vec![],
+            owners: vec![],
+            free: vec![],
+            table: PhantomData,
+        }
+    }
+}
+impl<T: GetTableName> fmt::Debug for Handles<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Handles>")
+    }
+}
+#[doc(hidden)]
+impl<T: GetTableName> Handles<T> {
+    /// Returns a handle for a row that was just pushed onto the end of the table.
+    pub fn insert(&mut self, row: usize) -> GenericHandle<T> {
+        assert_eq!(row, self.owners.len(), "handles out of sync");
+        let slot = if let Some(slot) = self.free.pop() {
+            slot
+        } else {
+            self.slots.push((0, None));
+            self.slots.len() - 1
+        };
+        self.slots[slot].1 = Some(row);
+        self.owners.push(slot);
+        GenericHandle::new(slot, self.slots[slot].0)
+    }
+
+    /// Returns the row the handle refers to, or `None` if the row was deleted.
+    pub fn resolve(&self, handle: GenericHandle<T>) -> Option<GenericRowId<T>> {
+        match self.slots.get(handle.slot()) {
+            Some(&(generation, Some(row))) if generation == handle.generation() => {
+                Some(GenericRowId::from_usize(row))
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the handle of a row.
+    pub fn handle(&self, row: usize) -> GenericHandle<T> {
+        let slot = self.owners[row];
+        GenericHandle::new(slot, self.slots[slot].0)
+    }
+
+    /// Follows the table's `swap_remove` of a row.
+    pub fn swap_remove(&mut self, row: usize) {
+        let slot = self.owners.swap_remove(row);
+        self.release(slot);
+        if let Some(&moved) = self.owners.get(row) {
+            self.slots[moved].1 = Some(row);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        while self.owners.len() > len {
+            let slot = self.owners.pop().unwrap();
+            self.release(slot);
+        }
+    }
+
+    pub fn clear(&mut self) { self.truncate(0) }
+
+    fn release(&mut self, slot: usize) {
+        let s = &mut self.slots[slot];
+        s.1 = None;
+        if s.0 == GenericHandle::<T>::generation_mask() {
+            // The generation would wrap around, bringing old handles back to life,
+            // so the slot is never used again.
+            return;
+        }
+        s.0 += 1;
+        self.free.push(slot);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Universe;
+    use crate::tables::{TableName, GenericTable, Guarantee};
+    use crate::domain::DomainName;
+
+    struct TestName;
+    impl GetTableName for TestName {
+        type Idx = u16;
+        fn get_domain() -> DomainName { DomainName("test_domain") }
+        fn get_name() -> TableName { TableName("test_table") }
+        fn get_guarantee() -> Guarantee { Default::default() }
+        fn get_generic_table(_: &Universe) -> &::std::sync::RwLock<GenericTable> { unimplemented!() }
+        fn new_generic_table() -> GenericTable { unimplemented!() }
+    }
+
+    #[test]
+    fn packing() {
+        let h: GenericHandle<TestName> = GenericHandle::new(3, 5);
+        assert_eq!(h.slot(), 3);
+        assert_eq!(h.generation(), 5);
+        assert_eq!(h.to_raw(), 5 << 8 | 3);
+        let h: GenericHandle<TestName> = GenericHandle::new(3, 256 + 7);
+        assert_eq!(h.generation(), 7);
+    }
+
+    #[test]
+    fn stale() {
+        let mut handles: Handles<TestName> = Handles::default();
+        let a = handles.insert(0);
+        let b = handles.insert(1);
+        let c = handles.insert(2);
+        handles.swap_remove(0);
+        assert_eq!(handles.resolve(a), None);
+        assert_eq!(handles.resolve(b), Some(GenericRowId::from_usize(1)));
+        assert_eq!(handles.resolve(c), Some(GenericRowId::from_usize(0)));
+        assert_eq!(handles.handle(0), c);
+        let d = handles.insert(2);
+        assert_eq!(d.slot(), a.slot());
+        assert!(d != a);
+        assert_eq!(handles.resolve(a), None);
+        assert_eq!(handles.resolve(d), Some(GenericRowId::from_usize(2)));
+    }
+
+    #[test]
+    fn generations_run_out() {
+        let mut handles: Handles<TestName> = Handles::default();
+        let first = handles.insert(0);
+        for _ in 0..255 {
+            handles.clear();
+            handles.insert(0);
+        }
+        let last = handles.handle(0);
+        assert_eq!((last.slot(), last.generation()), (first.slot(), 255));
+        handles.clear();
+        let next = handles.insert(0);
+        assert!(next.slot() != first.slot());
+        assert_eq!(handles.resolve(first), None);
+    }
+}
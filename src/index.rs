@@ -498,6 +498,12 @@ pub trait Checkable {
     type Row: GetTableName;
     fn check<'a, L>(self, table: &'a L) -> CheckedRowId<'a, L>
     where L: LockedTable<Row=Self::Row>;
+    /// Returns whether `check` would succeed.
+    fn is_valid<L>(&self, _table: &L) -> bool
+    where L: LockedTable<Row=Self::Row>
+    {
+        true
+    }
     fn uncheck(self) -> GenericRowId<Self::Row>;
 }
 impl<T: GetTableName> Checkable for GenericRowId<T> {
@@ -519,6 +525,12 @@ impl<T: GetTableName> Checkable for GenericRowId<T> {
         }
     }
 
+    fn is_valid<L>(&self, table: &L) -> bool
+    where L: LockedTable<Row=Self::Row>
+    {
+        self.to_usize() < table.len() && !table.is_deleted(*self)
+    }
+
     fn uncheck(self) -> GenericRowId<T> { self }
 }
 impl<'a, T: LockedTable + 'a> Checkable for CheckedRowId<'a, T> {
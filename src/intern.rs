@@ -19,6 +19,15 @@ pub fn check_name(name: &str) {
     }
 }
 
+/// The same rules as `check_name`, without the panicking.
+pub fn is_valid_name(name: &str) -> bool {
+    match name.chars().next() {
+        Some(c) if (c >= 'A' && c <= 'Z') || (c >= 'a' && c <= 'z') => (),
+        _ => return false,
+    }
+    name.chars().all(|c| c == '_' || (c >= 'A' && c <= 'Z') || (c >= 'a' && c <= 'z') || (c >= '0' && c <= '9'))
+}
+
 // FIXME: mopa?
 use std::any::Any;
 pub type PBox = Box<Any + Send + Sync>;
@@ -32,29 +41,44 @@ impl<I> Iterator for VoidIter<I> {
 
 
 /// Conversions for the results of `lock.(try_)?{read,write}`.
+/// A table that `Universe::repair` fixed isn't treated as poisoned.
 pub mod wrangle_lock {
+    use std::ops::Deref;
     use std::sync::{LockResult, TryLockResult, TryLockError, PoisonError};
+    use crate::tables::GenericTable;
 
     pub fn map_result<G, F, R>(result: LockResult<G>, f: F) -> LockResult<R>
     where
+        G: Deref<Target=GenericTable>,
         F: FnOnce(G) -> R
     {
         match result {
             Ok(t) => Ok(f(t)),
-            Err(poison) => Err(PoisonError::new(f(poison.into_inner()))),
+            Err(poison) => {
+                let t = poison.into_inner();
+                if t.repaired {
+                    Ok(f(t))
+                } else {
+                    Err(PoisonError::new(f(t)))
+                }
+            },
         }
     }
 
     pub fn map_try_result<G, F, R>(result: TryLockResult<G>, f: F) -> TryLockResult<R>
     where
+        G: Deref<Target=GenericTable>,
         F: FnOnce(G) -> R
     {
         match result {
             Ok(t) => Ok(f(t)),
-            Err(e) => Err(match e {
-                TryLockError::Poisoned(poison) => TryLockError::Poisoned(PoisonError::new(f(poison.into_inner()))),
-                TryLockError::WouldBlock => TryLockError::WouldBlock,
-            }),
+            Err(e) => match e {
+                TryLockError::Poisoned(poison) => match map_result(Err(poison), f) {
+                    Ok(t) => Ok(t),
+                    Err(poison) => Err(TryLockError::Poisoned(poison)),
+                },
+                TryLockError::WouldBlock => Err(TryLockError::WouldBlock),
+            },
         }
     }
 }
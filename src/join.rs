@@ -0,0 +1,313 @@
+//! Joins between two locked tables.
+//!
+//! Each side of a join is a table lock, and a `JoinKey` that gives its rows their keys.
+//! Columns of `Read` and `Write` locks are `JoinKey`s,
+//! and `RowIds` joins on the rows' own `RowId`s.
+//!
+//! ```ignore
+//! // `ships.port` is a `#[foreign]` column of `ports::RowId`s.
+//! for (ship, port) in join::merge_join(&ships, &ships.port, &ports, &join::RowIds) {
+//!     println!("{} is docked at {}", ships.name[ship], ports.name[port]);
+//! }
+//! ```
+//!
+//! If the right-hand key can look rows up (because it is an `#[index]`ed column, or `RowIds`),
+//! each left row is looked up, and the results are in the order of the left rows.
+//! Otherwise, for an inner join where the left-hand key can look rows up, each right row is
+//! looked up, and the results are in the order of the right rows.
+//! Otherwise both sides are sorted by their keys (cheaply, if they are `#[sort_key]`s) and
+//! merged with a `JoinCore`, and the results are in the order of the left keys.
+//!
+//! The joins are lazy: the rows are looked up or merged as the results are iterated over.
+//! Rows that are out of bounds or deleted are skipped, whatever the keys return.
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+use std::vec;
+
+use num_traits::FromPrimitive;
+
+use crate::joincore::{JoinCore, Join};
+use crate::columns::{TCol, Col, RefA, MutA, EditA, KeyA};
+use crate::tables::{GetTableName, LockedTable};
+use crate::index::{GenericRowId, CheckedRowId};
+
+/// Gives each row of a table a key to join on.
+pub trait JoinKey<L: LockedTable> {
+    type Key: Ord + Copy;
+
+    /// Returns the key of a row.
+    fn key(&self, table: &L, i: usize) -> Self::Key;
+
+    /// Returns the rows with the given key, if they can be found without searching the table.
+    fn lookup<'s>(&'s self, _table: &'s L, _key: Self::Key) -> Option<Box<Iterator<Item=usize> + 's>> { None }
+}
+
+/// Joins on the rows' own `RowId`s. This is used to join a `#[foreign]` column to its table.
+pub struct RowIds;
+impl<L: LockedTable> JoinKey<L> for RowIds {
+    type Key = GenericRowId<L::Row>;
+
+    fn key(&self, _table: &L, i: usize) -> Self::Key { GenericRowId::from_usize(i) }
+
+    fn lookup<'s>(&'s self, table: &'s L, key: Self::Key) -> Option<Box<Iterator<Item=usize> + 's>> {
+        let i = key.to_usize();
+        let found = if i < table.len() && !table.is_deleted(key) { Some(i) } else { None };
+        Some(Box::new(found.into_iter()))
+    }
+}
+
+macro_rules! column_key {
+    ($($ty:ident),*) => {$(
+        impl<'a, C, L> JoinKey<L> for $ty<'a, Col<C, L::Row>>
+        where
+            C: TCol + 'a,
+            C::Element: Ord + Copy,
+            L: LockedTable,
+        {
+            type Key = C::Element;
+
+            fn key(&self, _table: &L, i: usize) -> C::Element { *self.deref().inner().checked_index(i) }
+
+            fn lookup<'s>(&'s self, _table: &'s L, key: C::Element) -> Option<Box<Iterator<Item=usize> + 's>> {
+                self.deref().inner().lookup(key)
+            }
+        }
+    )*};
+}
+column_key!(RefA, MutA, EditA, KeyA);
+
+#[derive(Copy, Clone, PartialEq)]
+enum Kind {
+    Inner,
+    LeftOuter,
+    Anti,
+}
+
+/// Returns a `CheckedRowId` for row `i`, if it is in bounds and hasn't been deleted.
+pub(crate) fn checked<L: LockedTable>(table: &L, i: usize) -> Option<CheckedRowId<L>> {
+    if i >= table.len() || table.is_deleted(GenericRowId::from_usize(i)) {
+        return None;
+    }
+    let i = <L::Row as GetTableName>::Idx::from_usize(i)?;
+    // We just checked.
+    Some(unsafe { CheckedRowId::fab(i, table) })
+}
+
+/// Returns the rows of `table` that haven't been deleted.
+pub(crate) fn live_rows<'a, L: LockedTable>(table: &'a L) -> impl Iterator<Item=CheckedRowId<'a, L>> + 'a {
+    (0..table.len()).filter_map(move |i| checked(table, i))
+}
+
+/// Whether the key of the first row of `x` can be looked up in `y`.
+fn can_look_up<X, Y, KX, KY>(x: &X, x_key: &KX, y: &Y, y_key: &KY) -> bool
+where
+    X: LockedTable,
+    Y: LockedTable,
+    KX: JoinKey<X>,
+    KY: JoinKey<Y, Key=KX::Key>,
+{
+    match live_rows(x).next() {
+        Some(i) => y_key.lookup(y, x_key.key(x, i.to_usize())).is_some(),
+        None => false,
+    }
+}
+
+/// Rows of the right side of a merge, with the same key.
+struct Group<K> {
+    key: K,
+    rows: Range<usize>,
+}
+impl<K> fmt::Debug for Group<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Group({:?})", self.rows)
+    }
+}
+
+/// Splits the sorted right side of a merge into `Group`s.
+struct Groups<K> {
+    rows: Rc<Vec<(K, usize)>>,
+    at: usize,
+}
+impl<K: Ord + Copy> Iterator for Groups<K> {
+    type Item = Group<K>;
+    fn next(&mut self) -> Option<Group<K>> {
+        let start = self.at;
+        let key = self.rows.get(start)?.0;
+        while self.at < self.rows.len() && self.rows[self.at].0 == key {
+            self.at += 1;
+        }
+        Some(Group { key, rows: start..self.at })
+    }
+}
+
+enum Plan<K: Ord + Copy> {
+    /// Each left row is looked up on the right.
+    LookupRight(Range<usize>),
+    /// Each right row is looked up on the left. This is only done for inner joins.
+    LookupLeft(Range<usize>),
+    /// Both sides are sorted by their keys, and merged.
+    Merge {
+        left: vec::IntoIter<(K, usize)>,
+        right: JoinCore<Groups<K>>,
+        rows: Rc<Vec<(K, usize)>>,
+    },
+}
+
+/// The joined `(left, right)` rows. Unmatched left rows get `None`.
+struct Pairs<'a, A, B, KA, KB>
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    a: &'a A,
+    a_key: &'a KA,
+    b: &'a B,
+    b_key: &'a KB,
+    kind: Kind,
+    plan: Plan<KA::Key>,
+    /// The row whose matches are being found, and whether it has had any.
+    row: Option<(usize, bool)>,
+    found: Option<Box<Iterator<Item=usize> + 'a>>,
+    group: Range<usize>,
+}
+impl<'a, A, B, KA, KB> Pairs<'a, A, B, KA, KB>
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    fn new(a: &'a A, a_key: &'a KA, b: &'a B, b_key: &'a KB, kind: Kind) -> Self {
+        let plan = if can_look_up(a, a_key, b, b_key) {
+            Plan::LookupRight(0..a.len())
+        } else if kind == Kind::Inner && can_look_up(b, b_key, a, a_key) {
+            Plan::LookupLeft(0..b.len())
+        } else {
+            // Sorting is stable, and fast if the keys are already sorted.
+            let mut left: Vec<_> = live_rows(a).map(|i| (a_key.key(a, i.to_usize()), i.to_usize())).collect();
+            let mut right: Vec<_> = live_rows(b).map(|j| (b_key.key(b, j.to_usize()), j.to_usize())).collect();
+            left.sort_by_key(|p| p.0);
+            right.sort_by_key(|p| p.0);
+            let rows = Rc::new(right);
+            Plan::Merge {
+                left: left.into_iter(),
+                right: JoinCore::new(Groups { rows: rows.clone(), at: 0 }),
+                rows,
+            }
+        };
+        Pairs {
+            a, a_key, b, b_key, kind, plan,
+            row: None,
+            found: None,
+            group: 0..0,
+        }
+    }
+}
+impl<'a, A, B, KA, KB> Iterator for Pairs<'a, A, B, KA, KB>
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    type Item = (CheckedRowId<'a, A>, Option<CheckedRowId<'a, B>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (a, a_key, b, b_key) = (self.a, self.a_key, self.b, self.b_key);
+        loop {
+            if let Some((row, matched)) = self.row {
+                let found = match self.plan {
+                    Plan::Merge { ref rows, .. } => self.group.next().map(|k| rows[k].1),
+                    _ => self.found.as_mut().and_then(|found| found.next()),
+                };
+                if let Some(found) = found {
+                    let (i, j) = match self.plan {
+                        Plan::LookupLeft(_) => (found, row),
+                        _ => (row, found),
+                    };
+                    // Keys can return any row, so they're checked here.
+                    if let (Some(i), Some(j)) = (checked(a, i), checked(b, j)) {
+                        if self.kind == Kind::Anti {
+                            self.row = None;
+                            continue;
+                        }
+                        self.row = Some((row, true));
+                        return Some((i, Some(j)));
+                    }
+                    continue;
+                }
+                self.row = None;
+                if !matched && self.kind != Kind::Inner {
+                    // Only `LookupLeft` has right rows here, and it's only used for inner joins.
+                    if let Some(i) = checked(a, row) {
+                        return Some((i, None));
+                    }
+                }
+            }
+            match self.plan {
+                Plan::LookupRight(ref mut left) => {
+                    let i = left.next()?;
+                    if checked(a, i).is_none() { continue; }
+                    self.found = b_key.lookup(b, a_key.key(a, i));
+                    self.row = Some((i, false));
+                },
+                Plan::LookupLeft(ref mut right) => {
+                    let j = right.next()?;
+                    if checked(b, j).is_none() { continue; }
+                    self.found = a_key.lookup(a, b_key.key(b, j));
+                    self.row = Some((j, false));
+                },
+                Plan::Merge { ref mut left, ref mut right, .. } => {
+                    let (key, i) = left.next()?;
+                    self.group = match right.join(key, |k: KA::Key, g: &Group<KA::Key>| k.cmp(&g.key)) {
+                        Join::Match(g) => g.rows.clone(),
+                        Join::Next | Join::Stop => 0..0,
+                    };
+                    self.row = Some((i, false));
+                },
+            }
+        }
+    }
+}
+
+/// Pairs each row of `a` with each row of `b` that has the same key. (An inner join.)
+pub fn merge_join<'a, A, B, KA, KB>(a: &'a A, a_key: &'a KA, b: &'a B, b_key: &'a KB)
+    -> impl Iterator<Item=(CheckedRowId<'a, A>, CheckedRowId<'a, B>)> + 'a
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    Pairs::new(a, a_key, b, b_key, Kind::Inner)
+        .filter_map(|(i, j)| j.map(|j| (i, j)))
+}
+
+/// Like `merge_join`, but rows of `a` without a match are also returned, paired with `None`.
+pub fn left_join<'a, A, B, KA, KB>(a: &'a A, a_key: &'a KA, b: &'a B, b_key: &'a KB)
+    -> impl Iterator<Item=(CheckedRowId<'a, A>, Option<CheckedRowId<'a, B>>)> + 'a
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    Pairs::new(a, a_key, b, b_key, Kind::LeftOuter)
+}
+
+/// Returns the rows of `a` that don't have a match in `b`.
+pub fn anti_join<'a, A, B, KA, KB>(a: &'a A, a_key: &'a KA, b: &'a B, b_key: &'a KB)
+    -> impl Iterator<Item=CheckedRowId<'a, A>> + 'a
+where
+    A: LockedTable + 'a,
+    B: LockedTable + 'a,
+    KA: JoinKey<A> + 'a,
+    KB: JoinKey<B, Key=KA::Key> + 'a,
+{
+    Pairs::new(a, a_key, b, b_key, Kind::Anti)
+        .map(|(i, _)| i)
+}
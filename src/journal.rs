@@ -0,0 +1,66 @@
+//! Change logs for `#[journal]` tables, used by `Universe::save_delta`.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::tables::GetTableName;
+use crate::index::GenericRowId;
+
+/// The rows of a table that have changed since the last checkpoint.
+pub struct Journal<T: GetTableName> {
+    /// The whole table was cleared. Every row in `changed` was created after that.
+    pub cleared: bool,
+    /// Rows that were created or edited.
+    pub changed: BTreeSet<GenericRowId<T>>,
+    /// Rows that were deleted. A deleted row whose slot was reused is in `changed` instead.
+    pub deleted: BTreeSet<GenericRowId<T>>,
+}
+impl<T: GetTableName> Default for Journal<T> {
+    fn default() -> Self {
+        Journal {
+            cleared: false,
+            changed: BTreeSet::new(),
+            deleted: BTreeSet::new(),
+        }
+    }
+}
+impl<T: GetTableName> fmt::Debug for Journal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Journal {{ cleared: {}, changed: {}, deleted: {} }}", self.cleared, self.changed.len(), self.deleted.len())
+    }
+}
+impl<T: GetTableName> Journal<T> {
+    pub fn is_empty(&self) -> bool {
+        !self.cleared && self.changed.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Records a created or edited row.
+    pub fn changed(&mut self, row: GenericRowId<T>) {
+        self.deleted.remove(&row);
+        self.changed.insert(row);
+    }
+
+    pub fn deleted(&mut self, row: GenericRowId<T>) {
+        self.changed.remove(&row);
+        self.deleted.insert(row);
+    }
+
+    pub fn cleared(&mut self) {
+        self.cleared = true;
+        self.changed.clear();
+        self.deleted.clear();
+    }
+
+    /// Puts back changes that were taken out of the journal before anything in it now happened,
+    /// such as the changes of a delta that couldn't be saved.
+    pub fn merge_earlier(&mut self, earlier: Journal<T>) {
+        if self.cleared { return; }
+        let later = ::std::mem::replace(self, earlier);
+        for row in later.deleted {
+            self.deleted(row);
+        }
+        for row in later.changed {
+            self.changed(row);
+        }
+    }
+}
@@ -33,10 +33,15 @@ pub extern crate num_traits;
 extern crate lazy_static;
 #[macro_use]
 extern crate mopa;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "rayon")]
+pub extern crate rayon;
 
 use std::sync::*;
 
 
+pub mod error;
 #[macro_use]
 pub mod domain;
 #[macro_use]
@@ -48,8 +53,19 @@ pub mod property;
 pub mod intern;
 pub mod columns;
 pub mod index;
+pub mod handle;
 pub mod map_index;
+pub mod edits;
 pub mod storage;
+pub mod binary;
+pub mod mapped;
+pub mod dictionary;
+pub mod journal;
+pub mod transaction;
+pub mod reflect;
+pub mod query;
+pub mod par;
+pub mod avec;
 pub mod tracking;
 pub mod event;
 
@@ -58,9 +74,11 @@ pub mod context;
 
 // Util. Buncha these could become crates!
 pub mod joincore;
+pub mod join;
 mod assert_sorted;
 pub mod any_slice;
 mod serial;
+mod snapshot;
 
 // FIXME: #[cfg(rustdoc)] will be a thing eventually.
 #[cfg(feature = "doc")]
@@ -103,6 +121,7 @@ impl<T> Storable for T where T: 'static + Send + Sync + Sized /* + !Drop */ {}
 pub type GuardedUniverse = Arc<RwLock<Universe>>;
 
 use crate::domain::{DomainName, MaybeDomain};
+pub use crate::error::Error;
 
 /**
  * A context object whose reference should be passed around everywhere.
@@ -146,6 +165,38 @@ impl Universe {
         }
         out
     }
+
+    /// Recovers from a panic in code that held table locks.
+    ///
+    /// A consistent table's `Write` that is dropped by a panic undoes its unflushed pushes,
+    /// but its lock is still poisoned. And if a tracker panicked during a flush,
+    /// the other trackers may not have heard about the rows.
+    /// This lets the consistent tables be locked again despite the poison,
+    /// and finishes the interrupted flushes, starting with the tracker that panicked.
+    ///
+    /// Other kinds of tables stay poisoned, since there's no telling what a panic left half done.
+    pub fn repair(&self) {
+        let mut repairers = Vec::new();
+        for domain in &self.domains {
+            let domain = match *domain {
+                MaybeDomain::Unset(_) => continue,
+                MaybeDomain::Domain(ref i) => i,
+            };
+            for name in &domain.tables_registration_order {
+                let mut gt = match domain.tables[name].write() {
+                    Ok(gt) => gt,
+                    Err(poison) => poison.into_inner(),
+                };
+                if let Some(repair) = gt.table.get_repairer() {
+                    gt.set_repaired(true);
+                    repairers.push(repair);
+                }
+            }
+        }
+        for repair in repairers {
+            repair(self);
+        }
+    }
 }
 use std::fmt;
 impl fmt::Debug for Universe {
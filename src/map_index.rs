@@ -1,16 +1,46 @@
 //! Columns can be made searchable using `#[index]`.
-//! Such columns have their mutability restricted.
+//! Such columns have their mutability restricted;
+//! elements are changed using `table.column.set(row, element)` or `table.column.modify(row, f)`.
 //! The index can be searched using `table.column.find(&element)`.
+//!
+//! `#[index]` (or `#[index = "btree"]`) uses a `BTreeIndex`, which can also search ranges.
+//! `#[index = "hash"]` uses a `HashIndex`, which only needs `Hash + Eq`, and can only `find`.
+//!
+//! A table can also have a `CompositeIndex` over several columns, using
+//! `#[index(name = "by_a_b", cols = "a, b")]`. It is searched with `table.by_a_b().find((a, b))`.
 
-use std::collections::{BTreeMap, btree_map};
+use std::collections::{BTreeMap, btree_map, BTreeSet, btree_set, HashMap};
+use std::collections::Bound::Included;
 use std::hash::Hash;
 
 use num_traits::NumCast;
 
+use std::fmt;
+
 use crate::columns::TCol;
-use crate::tables::GetTableName;
+use crate::tables::{GetTableName, TableName};
 use crate::index::GenericRowId;
 
+/// A row could not be added because a `#[unique]` column already has its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniqueViolation {
+    pub table: TableName,
+    pub column: &'static str,
+    /// The row that already has the value. This is `None` if the value was duplicated within
+    /// the rows being added.
+    pub existing: Option<usize>,
+}
+impl fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unique column {}.{} already has that value", self.table, self.column)?;
+        if let Some(existing) = self.existing {
+            write!(f, " (at row {})", existing)?;
+        }
+        Ok(())
+    }
+}
+impl ::std::error::Error for UniqueViolation {}
+
 /// An iterator over the rows containing a searched-for element.
 pub struct Indexes<'a, C: TCol + 'a, T: GetTableName + 'a> {
     range: btree_map::Range<'a, (C::Element, T::Idx), ()>,
@@ -29,7 +59,7 @@ impl<'a, C: TCol + 'a, T: GetTableName + 'a> Iterator for Indexes<'a, C, T> {
 pub struct BTreeIndex<C: TCol, T: GetTableName>
 where C::Element: Hash + Ord + Copy
 {
-    inner: C,
+    pub(crate) inner: C,
     /// Unfortunately it duplicates the elements, but at least it is very easy to implement and does
     /// limited allocation.
     index: BTreeMap<(C::Element, T::Idx), ()>,
@@ -57,8 +87,18 @@ where C::Element: Hash + Ord + Copy
             range: self.index.range((lo, zero)..(hi, max))
         }
     }
+
+    /// Indexes an already filled column.
+    pub(crate) fn from_inner(inner: C) -> Self {
+        let mut index = BTreeMap::new();
+        for i in 0..inner.len() {
+            let v = unsafe { *inner.unchecked_index(i) };
+            index.insert((v, NumCast::from(i).unwrap()), ());
+        }
+        BTreeIndex { inner, index }
+    }
 }
-use serde::{Serialize, Serializer};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
 impl<C: TCol, T: GetTableName> Serialize for BTreeIndex<C, T>
 where
     C::Element: Hash + Ord + Copy,
@@ -133,8 +173,359 @@ where C::Element: Hash + Ord + Copy
         self.inner.deleted(i);
     }
 
+    fn lookup<'a>(&'a self, e: Self::Element) -> Option<Box<Iterator<Item=usize> + 'a>> {
+        Some(Box::new(self.find(e).map(|i| i.to_usize())))
+    }
+
+    type IntoIter = C::IntoIter;
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+
+    fn serialize_col<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where Self: Sized, Self::Element: Serialize
+    {
+        self.inner.serialize_col(serializer)
+    }
+
+    fn deserialize_col<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    where Self: Sized, Self::Element: Deserialize<'de>
+    {
+        C::deserialize_col(deserializer).map(Self::from_inner)
+    }
+}
+
+/// An iterator over the rows containing a searched-for element.
+pub struct HashIndexes<'a, T: GetTableName + 'a> {
+    rows: Option<btree_set::Iter<'a, T::Idx>>,
+}
+impl<'a, T: GetTableName + 'a> Iterator for HashIndexes<'a, T> {
+    type Item = GenericRowId<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows
+            .as_mut()
+            .and_then(|rows| rows.next())
+            .map(|&i| GenericRowId::new(i))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.rows {
+            Some(ref rows) => rows.size_hint(),
+            None => (0, Some(0)),
+        }
+    }
+}
+
+/// A `TCol` wrapper that does indexing using a `HashMap`. The element must be `Hash + Eq`.
+pub struct HashIndex<C: TCol, T: GetTableName>
+where C::Element: Hash + Eq + Copy
+{
+    pub(crate) inner: C,
+    /// The rows containing each element. A set, so that a row can be removed without a scan
+    /// when an element is shared by many rows.
+    index: HashMap<C::Element, BTreeSet<T::Idx>>,
+}
+impl<C: TCol, T: GetTableName> HashIndex<C, T>
+where C::Element: Hash + Eq + Copy
+{
+    /// Returns an iterator yielding the rows containing `key`, in order.
+    pub fn find(&self, key: C::Element) -> HashIndexes<T> {
+        HashIndexes {
+            rows: self.index.get(&key).map(|rows| rows.iter()),
+        }
+    }
+
+    /// Indexes an already filled column.
+    pub(crate) fn from_inner(inner: C) -> Self {
+        let mut ret = HashIndex {
+            inner,
+            index: HashMap::new(),
+        };
+        for i in 0..ret.inner.len() {
+            let v = unsafe { *ret.inner.unchecked_index(i) };
+            ret.index_insert(v, i);
+        }
+        ret
+    }
+
+    fn index_insert(&mut self, v: C::Element, i: usize) {
+        let native_i = NumCast::from(i).unwrap();
+        self.index
+            .entry(v)
+            .or_insert_with(BTreeSet::new)
+            .insert(native_i);
+    }
+
+    fn index_remove(&mut self, v: C::Element, i: usize) {
+        let native_i: T::Idx = NumCast::from(i).unwrap();
+        let now_empty = if let Some(rows) = self.index.get_mut(&v) {
+            rows.remove(&native_i);
+            rows.is_empty()
+        } else {
+            false
+        };
+        if now_empty {
+            self.index.remove(&v);
+        }
+    }
+}
+impl<C: TCol, T: GetTableName> Serialize for HashIndex<C, T>
+where
+    C::Element: Hash + Eq + Copy,
+    C: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer
+    {
+        ::erased_serde::serialize(&self.inner, serializer)
+    }
+}
+impl<C: TCol, T: GetTableName> TCol for HashIndex<C, T>
+where C::Element: Hash + Eq + Copy
+{
+    type Element = C::Element;
+
+    fn new() -> Self where Self: Sized {
+        HashIndex {
+            inner: C::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize { self.inner.len() }
+    fn truncate(&mut self, new_len: usize) {
+        unsafe {
+            for i in new_len..self.len() {
+                self.deleted(i);
+            }
+        }
+        self.inner.truncate(new_len);
+    }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element { self.inner.unchecked_index(i) }
+    unsafe fn unchecked_index_mut(&mut self, _i: usize) -> &mut Self::Element { panic!("tried to mutably reference indexed column"); }
+    fn reserve(&mut self, n: usize) { self.inner.reserve(n); }
+    fn clear(&mut self) {
+        self.inner.clear();
+        self.index.clear();
+    }
+    fn push(&mut self, v: Self::Element) {
+        let i = self.inner.len();
+        self.inner.push(v);
+        self.index_insert(v, i);
+    }
+
+    unsafe fn unchecked_swap_out(&mut self, i: usize, new: &mut Self::Element) {
+        let old = *self.unchecked_index(i);
+        self.index_remove(old, i);
+        self.index_insert(*new, i);
+        self.inner.unchecked_swap_out(i, new);
+    }
+
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) {
+        // Otherwise the row would be removed once, and inserted twice.
+        if a == b { return; }
+        let old_a = *self.unchecked_index(a);
+        let old_b = *self.unchecked_index(b);
+        self.index_remove(old_a, a);
+        self.index_remove(old_b, b);
+        self.index_insert(old_a, b);
+        self.index_insert(old_b, a);
+        self.inner.unchecked_swap(a, b);
+    }
+
+    unsafe fn deleted(&mut self, i: usize) {
+        let old = *self.unchecked_index(i);
+        self.index_remove(old, i);
+        self.inner.deleted(i);
+    }
+
+    fn lookup<'a>(&'a self, e: Self::Element) -> Option<Box<Iterator<Item=usize> + 'a>> {
+        Some(Box::new(self.find(e).map(|i| i.to_usize())))
+    }
+
     type IntoIter = C::IntoIter;
     fn into_iter(self) -> Self::IntoIter {
         self.inner.into_iter()
     }
+
+    fn serialize_col<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where Self: Sized, Self::Element: Serialize
+    {
+        self.inner.serialize_col(serializer)
+    }
+
+    fn deserialize_col<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    where Self: Sized, Self::Element: Deserialize<'de>
+    {
+        C::deserialize_col(deserializer).map(Self::from_inner)
+    }
+}
+
+/// One element of a `CompositeIndex`'s key. `Min` and `Max` sort around every value, so that a
+/// prefix of the key can be searched for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Part<E> {
+    Min,
+    Is(E),
+    Max,
+}
+
+/// A tuple of column elements that can be the key of a `CompositeIndex`.
+pub trait CompositeKey: Copy {
+    /// The key, with each element wrapped in a `Part`.
+    type Parts: Ord + Copy;
+    fn parts(self) -> Self::Parts;
+}
+
+/// A leading part of a `CompositeKey`, such as `(a,)` or `(a, b)` for the key `(a, b, c)`.
+/// The whole key is also a prefix of itself.
+pub trait Prefix<K: CompositeKey> {
+    /// The lowest and highest keys starting with this prefix.
+    fn bounds(self) -> (K::Parts, K::Parts);
+}
+
+macro_rules! composite_key {
+    ($($E:ident $e:ident),*) => {
+        impl<$($E: Ord + Copy),*> CompositeKey for ($($E,)*) {
+            type Parts = ($(Part<$E>,)*);
+            fn parts(self) -> Self::Parts {
+                let ($($e,)*) = self;
+                ($(Part::Is($e),)*)
+            }
+        }
+    };
+}
+composite_key!(A a, B b);
+composite_key!(A a, B b, C c);
+composite_key!(A a, B b, C c, D d);
+
+macro_rules! prefix {
+    ([$($P:ident $p:ident),*] [$($R:ident),*]) => {
+        impl<$($P: Ord + Copy,)* $($R: Ord + Copy,)*> Prefix<($($P,)* $($R,)*)> for ($($P,)*) {
+            fn bounds(self) -> (($(Part<$P>,)* $(Part<$R>,)*), ($(Part<$P>,)* $(Part<$R>,)*)) {
+                let ($($p,)*) = self;
+                (
+                    ($(Part::Is($p),)* $(Part::<$R>::Min,)*),
+                    ($(Part::Is($p),)* $(Part::<$R>::Max,)*),
+                )
+            }
+        }
+    };
+}
+prefix!([A a] [B]);
+prefix!([A a, B b] []);
+prefix!([A a] [B, C]);
+prefix!([A a, B b] [C]);
+prefix!([A a, B b, C c] []);
+prefix!([A a] [B, C, D]);
+prefix!([A a, B b] [C, D]);
+prefix!([A a, B b, C c] [D]);
+prefix!([A a, B b, C c, D d] []);
+
+/// An iterator over the rows found in a `CompositeIndex`.
+pub struct CompositeIndexes<'a, K: CompositeKey + 'a, T: GetTableName + 'a> {
+    range: btree_map::Range<'a, (K::Parts, T::Idx), ()>,
+}
+impl<'a, K: CompositeKey + 'a, T: GetTableName + 'a> Iterator for CompositeIndexes<'a, K, T> {
+    type Item = GenericRowId<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range
+            .next()
+            .map(|v| (v.0).1)
+            .map(GenericRowId::new)
+    }
+}
+
+/// An index over a tuple of columns, declared with `#[index(name = "...", cols = "...")]`.
+/// The columns have their mutability restricted, the same as `#[index]` columns.
+pub struct CompositeIndex<K: CompositeKey, T: GetTableName> {
+    index: BTreeMap<(K::Parts, T::Idx), ()>,
+}
+impl<K: CompositeKey, T: GetTableName> Default for CompositeIndex<K, T> {
+    fn default() -> Self {
+        CompositeIndex {
+            index: BTreeMap::new(),
+        }
+    }
+}
+impl<K: CompositeKey, T: GetTableName> fmt::Debug for CompositeIndex<K, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<CompositeIndex of {} rows>", self.index.len())
+    }
+}
+impl<K: CompositeKey, T: GetTableName> CompositeIndex<K, T> {
+    /// Returns an iterator yielding the rows containing `key`.
+    pub fn find(&self, key: K) -> CompositeIndexes<K, T> {
+        let key = key.parts();
+        self.between(key, key)
+    }
+
+    /// Returns an iterator yielding the rows whose key starts with `prefix`, such as `(a,)`.
+    pub fn prefix<P: Prefix<K>>(&self, prefix: P) -> CompositeIndexes<K, T> {
+        let (lo, hi) = prefix.bounds();
+        self.between(lo, hi)
+    }
+
+    /// Returns an iterator yielding the rows whose key is in `lo ..= hi`, in key order.
+    pub fn range(&self, lo: K, hi: K) -> CompositeIndexes<K, T> {
+        self.between(lo.parts(), hi.parts())
+    }
+
+    fn between(&self, lo: K::Parts, hi: K::Parts) -> CompositeIndexes<K, T> {
+        use num_traits::{Zero, Bounded};
+        let zero = T::Idx::zero();
+        let max = T::Idx::max_value();
+        CompositeIndexes {
+            range: self.index.range((Included((lo, zero)), Included((hi, max)))),
+        }
+    }
+
+    pub fn len(&self) -> usize { self.index.len() }
+    pub fn is_empty(&self) -> bool { self.index.is_empty() }
+
+    #[doc(hidden)]
+    pub fn insert(&mut self, key: K, i: usize) {
+        let native_i = NumCast::from(i).unwrap();
+        self.index.insert((key.parts(), native_i), ());
+    }
+
+    #[doc(hidden)]
+    pub fn remove(&mut self, key: K, i: usize) {
+        let native_i = NumCast::from(i).unwrap();
+        self.index.remove(&(key.parts(), native_i));
+    }
+
+    #[doc(hidden)]
+    pub fn clear(&mut self) { self.index.clear() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Universe;
+    use crate::domain::DomainName;
+    use crate::storage::VecCol;
+    use crate::tables::{Guarantee, GenericTable};
+
+    struct TestName;
+    impl GetTableName for TestName {
+        type Idx = usize;
+        fn get_domain() -> DomainName { DomainName("test_domain") }
+        fn get_name() -> TableName { TableName("test_table") }
+        fn get_guarantee() -> Guarantee { Guarantee { consistent: false, sorted: false, append_only: false } }
+        fn get_generic_table(_: &Universe) -> &::std::sync::RwLock<GenericTable> { unimplemented!() }
+        fn new_generic_table() -> GenericTable { unimplemented!() }
+    }
+
+    #[test]
+    fn swap_with_self() {
+        let mut col: HashIndex<VecCol<u8>, TestName> = HashIndex::new();
+        col.push(3);
+        col.push(4);
+        unsafe {
+            col.unchecked_swap(0, 0);
+            col.unchecked_swap(0, 1);
+        }
+        assert_eq!(col.find(3).map(|i| i.to_usize()).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(col.find(4).map(|i| i.to_usize()).collect::<Vec<_>>(), vec![0]);
+    }
 }
@@ -0,0 +1,209 @@
+//! Columns backed by a memory-mapped file in v11's binary format.
+//!
+//! `$table::open_mapped(universe, path)` maps a file written by `Extraction::write_binary`,
+//! and fills an empty table from it. It is `unsafe`, because the file must be left alone
+//! for as long as the table uses it; see `Mapping::open`. The elements of `MappedCol`s are not copied:
+//! they are read from the mapping until the column is modified,
+//! at which point the column is copied onto the heap.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::slice;
+use std::sync::Arc;
+
+use crate::columns::TCol;
+use crate::binary::{Pod, BinaryCol, Reader, Writer};
+
+/// A read-only file in memory.
+pub struct Mapping {
+    ptr: *const u8,
+    len: usize,
+    /// Used if the file couldn't be mapped.
+    fallback: Vec<u8>,
+}
+// The mapping is never written to.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+impl Mapping {
+    /// Maps the file at `path`. On platforms without `mmap` the file is read into memory instead.
+    ///
+    /// # Safety
+    /// The file must not be changed or truncated while the `Mapping` is alive, by this process or
+    /// any other. Changes may show up in the mapping, and reading past the end of a truncated
+    /// file raises `SIGBUS`.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Arc<Mapping>> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(Arc::new(Self::read(file)?));
+        }
+        Self::map(file, len).map(Arc::new)
+    }
+
+    #[cfg(unix)]
+    fn map(file: File, len: usize) -> io::Result<Mapping> {
+        use std::os::unix::io::AsRawFd;
+        use std::ptr;
+        let ptr = unsafe {
+            ::libc::mmap(ptr::null_mut(), len, ::libc::PROT_READ, ::libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+        };
+        if ptr == ::libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *const u8,
+            len,
+            fallback: Vec::new(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn map(file: File, _len: usize) -> io::Result<Mapping> {
+        Self::read(file)
+    }
+
+    fn read(mut file: File) -> io::Result<Mapping> {
+        let mut fallback = Vec::new();
+        file.read_to_end(&mut fallback)?;
+        Ok(Mapping {
+            ptr: ::std::ptr::null(),
+            len: 0,
+            fallback,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        if self.ptr.is_null() {
+            &self.fallback
+        } else {
+            unsafe { slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+}
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            if !self.ptr.is_null() {
+                ::libc::munmap(self.ptr as *mut ::libc::c_void, self.len);
+            }
+        }
+    }
+}
+impl fmt::Debug for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mapping({} bytes)", self.as_slice().len())
+    }
+}
+
+#[derive(Debug)]
+struct Region {
+    mapping: Arc<Mapping>,
+    offset: usize,
+    len: usize,
+}
+
+/// A column of `Pod` elements that may be backed by a memory-mapped file.
+/// It is copied onto the heap the first time it is modified.
+/// Otherwise, it works like a `VecCol`.
+#[derive(Debug)]
+pub struct MappedCol<E: Pod> {
+    mapped: Option<Region>,
+    owned: Vec<E>,
+}
+impl<E: Pod> MappedCol<E> {
+    pub fn as_slice(&self) -> &[E] {
+        match self.mapped {
+            Some(ref region) => unsafe {
+                let ptr = region.mapping.as_slice().as_ptr().offset(region.offset as isize);
+                slice::from_raw_parts(ptr as *const E, region.len)
+            },
+            None => &self.owned,
+        }
+    }
+
+    /// Returns `true` if the elements are still read from the file.
+    pub fn is_mapped(&self) -> bool { self.mapped.is_some() }
+
+    fn owned(&mut self) -> &mut Vec<E> {
+        if self.mapped.is_some() {
+            self.owned = self.as_slice().to_vec();
+            self.mapped = None;
+        }
+        &mut self.owned
+    }
+}
+impl<E: Pod> TCol for MappedCol<E> {
+    type Element = E;
+
+    fn new() -> Self {
+        MappedCol {
+            mapped: None,
+            owned: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize { self.as_slice().len() }
+    fn truncate(&mut self, len: usize) {
+        match self.mapped {
+            Some(ref mut region) => if len < region.len { region.len = len },
+            None => self.owned.truncate(len),
+        }
+    }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element { self.as_slice().get_unchecked(i) }
+    unsafe fn unchecked_index_mut(&mut self, i: usize) -> &mut Self::Element { self.owned().get_unchecked_mut(i) }
+    fn reserve(&mut self, n: usize) {
+        // Reserving doesn't modify anything, so the mapping is kept.
+        if self.mapped.is_none() {
+            self.owned.reserve(n)
+        }
+    }
+    fn clear(&mut self) {
+        self.mapped = None;
+        self.owned.clear();
+    }
+    fn push(&mut self, v: Self::Element) { self.owned().push(v) }
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) { self.owned().swap(a, b) }
+
+    type IntoIter = ::std::vec::IntoIter<Self::Element>;
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.owned();
+        ::std::mem::replace(&mut self.owned, Vec::new()).into_iter()
+    }
+}
+
+impl<E: Pod> BinaryCol for MappedCol<E> {
+    fn write_block<W: Write>(&self, out: &mut Writer<W>) -> io::Result<()> {
+        out.slice(self.as_slice())
+    }
+
+    fn read_block<R: Read>(input: &mut Reader<R>) -> io::Result<Self> {
+        let mapping = match input.mapping() {
+            Some(mapping) => mapping.clone(),
+            None => return Ok(MappedCol {
+                mapped: None,
+                owned: input.vec()?,
+            }),
+        };
+        let len = input.block_start::<E>()?;
+        let offset = input.position();
+        input.skip((len * ::std::mem::size_of::<E>()) as u64)?;
+        let offset = offset as usize;
+        if (mapping.as_slice().as_ptr() as usize + offset) % ::std::mem::align_of::<E>() != 0 {
+            // The mapping is page-aligned, so this only happens with the fallback.
+            let bytes = &mapping.as_slice()[offset..];
+            let mut owned = Vec::with_capacity(len);
+            unsafe {
+                ::std::ptr::copy_nonoverlapping(bytes.as_ptr(), owned.as_mut_ptr() as *mut u8, len * ::std::mem::size_of::<E>());
+                owned.set_len(len);
+            }
+            return Ok(MappedCol { mapped: None, owned });
+        }
+        Ok(MappedCol {
+            mapped: Some(Region { mapping, offset, len }),
+            owned: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,144 @@
+//! Visiting rows in parallel, using `rayon`. This needs v11's `rayon` feature.
+//!
+//! Every table has `par_iter()`, which is like `iter()`: it gives the `CheckedRowId` of each row,
+//! skipping the deleted rows of consistent tables.
+//!
+//! `Write` locks also have `par_iter_mut()`. This splits the lock like `editing()` does,
+//! and the rows can then be zipped with the `Edit`'s columns. Each column is split into
+//! disjoint `&mut` slices, so that rows can be changed on several threads at once.
+//! This only works with columns that are `ContiguousCol`s, like `VecCol`.
+//!
+//! ```ignored
+//! use v11::rayon::prelude::*;
+//!
+//! let mut particles = particles::write(universe);
+//! let (edit, rows) = particles.par_iter_mut();
+//! rows.zip((&mut *edit.position, &*edit.velocity)).for_each(|(_row, (pos, vel))| {
+//!     *pos += *vel;
+//! });
+//! ```
+
+use crate::tables::{GetTableName, LockedTable};
+use crate::index::FreeList;
+
+/// Returned by `par_iter()`.
+pub struct ParRows<'a, T: LockedTable + 'a> {
+    table: &'a T,
+}
+impl<'a, T: LockedTable + 'a> ParRows<'a, T> {
+    #[doc(hidden)]
+    pub fn new(table: &'a T) -> Self {
+        ParRows { table }
+    }
+}
+
+/// Returned by `par_iter_mut()`. Use `zip` to get at the columns.
+pub struct ParRowsMut<'w, T: GetTableName + 'w> {
+    len: usize,
+    free: &'w FreeList<T>,
+}
+impl<'w, T: GetTableName + 'w> ParRowsMut<'w, T> {
+    #[doc(hidden)]
+    pub fn new(len: usize, free: &'w FreeList<T>) -> Self {
+        ParRowsMut { len, free }
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod rayon_impls {
+    use super::*;
+    use rayon::prelude::*;
+    use rayon::iter::plumbing::UnindexedConsumer;
+    use rayon::iter::MultiZip;
+    use rayon::slice;
+
+    use crate::columns::{Col, RefA, MutA, ContiguousCol};
+    use crate::index::{GenericRowId, CheckedRowId};
+    use crate::join::checked;
+
+    impl<'a, T: LockedTable + Sync + 'a> ParallelIterator for ParRows<'a, T> {
+        type Item = CheckedRowId<'a, T>;
+
+        fn drive_unindexed<C>(self, consumer: C) -> C::Result
+        where C: UnindexedConsumer<Self::Item>
+        {
+            let table = self.table;
+            (0..table.len())
+                .into_par_iter()
+                .filter_map(move |i| checked(table, i))
+                .drive_unindexed(consumer)
+        }
+    }
+
+    /// Columns of a table that can be split up between threads.
+    /// This is implemented for `&MutA`, `&mut MutA`, and `&RefA` columns of `ContiguousCol`s,
+    /// and for tuples of up to four of them.
+    pub trait ParColumns<T: GetTableName> {
+        type Item: Send;
+        type Iter: IndexedParallelIterator<Item=Self::Item>;
+        fn into_par_columns(self) -> Self::Iter;
+    }
+
+    impl<'a, 'u: 'a, C: ContiguousCol, T: GetTableName> ParColumns<T> for &'a mut MutA<'u, Col<C, T>> {
+        type Item = &'a mut C::Element;
+        type Iter = slice::IterMut<'a, C::Element>;
+        fn into_par_columns(self) -> Self::Iter {
+            self.as_mut_slice().par_iter_mut()
+        }
+    }
+
+    impl<'a, 'u: 'a, C: ContiguousCol, T: GetTableName> ParColumns<T> for &'a MutA<'u, Col<C, T>> {
+        type Item = &'a C::Element;
+        type Iter = slice::Iter<'a, C::Element>;
+        fn into_par_columns(self) -> Self::Iter {
+            self.as_slice().par_iter()
+        }
+    }
+
+    impl<'a, 'u: 'a, C: ContiguousCol, T: GetTableName> ParColumns<T> for &'a RefA<'u, Col<C, T>> {
+        type Item = &'a C::Element;
+        type Iter = slice::Iter<'a, C::Element>;
+        fn into_par_columns(self) -> Self::Iter {
+            self.as_slice().par_iter()
+        }
+    }
+
+    macro_rules! par_columns_tuple {
+        ($($col:ident),*) => {
+            impl<T: GetTableName, $($col: ParColumns<T>),*> ParColumns<T> for ($($col,)*) {
+                type Item = ($($col::Item,)*);
+                type Iter = MultiZip<($($col::Iter,)*)>;
+                #[allow(non_snake_case)]
+                fn into_par_columns(self) -> Self::Iter {
+                    let ($($col,)*) = self;
+                    ($($col.into_par_columns(),)*).into_par_iter()
+                }
+            }
+        };
+    }
+    par_columns_tuple!(A, B);
+    par_columns_tuple!(A, B, C);
+    par_columns_tuple!(A, B, C, D);
+
+    impl<'w, T: GetTableName + 'w> ParRowsMut<'w, T> {
+        /// Pairs each row with its elements of `columns`, skipping deleted rows.
+        ///
+        /// # Panics
+        /// If the columns aren't as long as the table.
+        pub fn zip<'c, C>(self, columns: C) -> impl ParallelIterator<Item=(GenericRowId<T>, C::Item)> + 'c
+        where
+            'w: 'c,
+            C: ParColumns<T> + 'c,
+        {
+            let free = self.free;
+            let columns = columns.into_par_columns();
+            assert_eq!(columns.len(), self.len, "columns aren't from this table");
+            columns
+                .enumerate()
+                .filter(move |&(i, _)| !free.contains_key(&GenericRowId::from_usize(i)))
+                .map(|(i, item)| (GenericRowId::from_usize(i), item))
+        }
+    }
+}
+#[cfg(feature = "rayon")]
+pub use self::rayon_impls::ParColumns;
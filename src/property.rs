@@ -3,8 +3,12 @@ use std::any::Any;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::fmt;
 
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
 use crate::Universe;
-use crate::intern;
+use crate::error::Error;
+use crate::intern::{self, PBox};
 use crate::domain::*;
 
 #[derive(Hash, PartialEq, Eq, Debug, Clone, Copy)]
@@ -78,11 +82,36 @@ pub trait ToPropRef<V: Sync>: Sync {
  *     assert_eq!(90, universe.get(THING));
  * }
  * ```
+ *
+ * Put `#[save]` before any other attributes to include the property in `Universe::save_snapshot`.
+ * Its type must implement `Serialize` and `DeserializeOwned`.
  * */
 // FIXME: Better documentation.
 #[macro_export]
 macro_rules! property {
     // Default-initialized properties
+    (
+        #[save]
+        $(#[$ATTR:meta])*
+        static $DOMAIN:ident/$NAME:ident: $TYPE:ty
+    ) => {
+        property! {
+            #[save]
+            $(#[$ATTR])*
+            static $DOMAIN/$NAME: $TYPE = Default::default();
+        }
+    };
+    (
+        #[save]
+        $(#[$ATTR:meta])*
+        pub static $DOMAIN:ident/$NAME:ident: $TYPE:ty
+    ) => {
+        property! {
+            #[save]
+            $(#[$ATTR])*
+            pub static $DOMAIN/$NAME: $TYPE = Default::default();
+        }
+    };
     (
         $(#[$ATTR:meta])*
         static $DOMAIN:ident/$NAME:ident: $TYPE:ty
@@ -103,6 +132,36 @@ macro_rules! property {
     };
 
     // expression-initialized properties
+    (
+        #[save]
+        $(#[$ATTR:meta])*
+        static $DOMAIN:ident/$NAME:ident: $TYPE:ty = $INIT:expr;
+    ) => {
+        $(#[$ATTR])*
+        static $NAME: &'static $crate::property::ToPropRef<$TYPE> = &$NAME::PropRef as &$crate::property::ToPropRef<$TYPE>;
+
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        #[allow(dead_code)]
+        mod $NAME {
+            property!(@mod [save] $DOMAIN/$NAME: $TYPE = $INIT;);
+        }
+    };
+    (
+        #[save]
+        $(#[$ATTR:meta])*
+        pub static $DOMAIN:ident/$NAME:ident: $TYPE:ty = $INIT:expr;
+    ) => {
+        $(#[$ATTR])*
+        pub static $NAME: &'static $crate::property::ToPropRef<$TYPE> = &$NAME::PropRef as &$crate::property::ToPropRef<$TYPE>;
+
+        #[doc(hidden)]
+        #[allow(non_snake_case)]
+        #[allow(dead_code)]
+        pub mod $NAME {
+            property!(@mod [save] $DOMAIN/$NAME: $TYPE = $INIT;);
+        }
+    };
     (
         $(#[$ATTR:meta])*
         static $DOMAIN:ident/$NAME:ident: $TYPE:ty = $INIT:expr;
@@ -114,7 +173,7 @@ macro_rules! property {
         #[allow(non_snake_case)]
         #[allow(dead_code)]
         mod $NAME {
-            property!(@mod $DOMAIN/$NAME: $TYPE = $INIT;);
+            property!(@mod [] $DOMAIN/$NAME: $TYPE = $INIT;);
         }
     };
     (
@@ -128,11 +187,18 @@ macro_rules! property {
         #[allow(non_snake_case)]
         #[allow(dead_code)]
         pub mod $NAME {
-            property!(@mod $DOMAIN/$NAME: $TYPE = $INIT;);
+            property!(@mod [] $DOMAIN/$NAME: $TYPE = $INIT;);
         }
     };
 
-    (@mod $DOMAIN:ident/$NAME:ident: $TYPE:ty = $INIT:expr;) => {
+    (@serial [save]) => {
+        fn serial(&self) -> Option<$crate::property::PropertySerial> {
+            Some($crate::property::PropertySerial::of::<Type>())
+        }
+    };
+    (@serial []) => {};
+
+    (@mod [$($SAVE:ident)*] $DOMAIN:ident/$NAME:ident: $TYPE:ty = $INIT:expr;) => {
         #[allow(unused_imports)]
         use super::*;
 
@@ -197,6 +263,7 @@ macro_rules! property {
             }
             fn domain(&self) -> DomainName { DOMAIN_NAME }
             fn name(&self) -> PropertyName { NAME }
+            property!(@serial [$($SAVE)*]);
         }
 
         const DOMAIN_NAME: DomainName = DomainName(stringify!($DOMAIN));
@@ -264,8 +331,13 @@ impl<V> Prop<V> {
     fn get_global_index(&self) -> GlobalPropertyId { self.index.global_index }
 
     pub fn init(&mut self, producer: Box<crate::domain::Producer>) {
+        self.try_init(producer).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `init`, but returns an error if the name is invalid, or the domain is missing or locked.
+    pub fn try_init(&mut self, producer: Box<crate::domain::Producer>) -> Result<(), Error> {
         let globals = clone_globals();
-        let pmap: &mut GlobalProperties = &mut *globals.write().unwrap();
+        let pmap: &mut GlobalProperties = &mut *crate::domain::try_write_globals(&globals)?;
         // We must acquire the global lock at the beginning of this function. If we wait, and a
         // property is being registered from multiple threads simultaneously, there will be duplicate
         // registrations. This must happen before the if below.
@@ -276,13 +348,16 @@ impl<V> Prop<V> {
         // 3: A twin PropRef was already registered.
         if self.get_global_index() != unset::GLOBAL_PROPERTY_ID {
             // This handles the first case.
-            return;
+            return Ok(());
         }
-        self.check_name();
+        self.try_check_name()?;
         let mut first_instance = false;
-        let domain_info = pmap.domains.get_mut(&self.domain_name).unwrap_or_else(|| panic!("Property {} is for an undefined domain", self));
+        let domain_info = pmap.domains.get_mut(&self.domain_name).ok_or_else(|| Error::UnknownDomain(self.domain_name.0.to_owned()))?;
         if crate::domain::check_lock() && domain_info.locked() {
-            panic!("Adding {:?} on a locked domain", self);
+            return Err(Error::DomainLocked {
+                domain: self.domain_name.0.to_owned(),
+                name: self.name.0.to_owned(),
+            });
         }
         let global_index = {
             let next_id = GlobalPropertyId(pmap.gid2producer.len());
@@ -308,6 +383,7 @@ impl<V> Prop<V> {
         }
         // FIXME: Shouldn't we panic if we're adding something to a domain that was already used to
         // make a universe?
+        Ok(())
     }
 
     #[cold]
@@ -315,11 +391,14 @@ impl<V> Prop<V> {
         // 4: A twin PropRef was already registered, but we weren't. To keep things easy, we'll
         //    just silently fix ourselves.
         // We don't need any sanity checks here. If a twin is registered, then they're already
-        // sane. If not, then we panic.
+        // sane. If not, then we stay unregistered, and the lookup fails.
         let globals = clone_globals();
         let pmap: &mut GlobalProperties = &mut *globals.write().unwrap();
+        let global_index = match pmap.name2gid.get(&self.name) {
+            Some(&gid) => gid,
+            None => return,
+        };
         let domain_info = pmap.domains.get_mut(&self.domain_name).unwrap_or_else(|| panic!("Property {} is for an undefined domain", self));
-        let global_index = *pmap.name2gid.get(&self.name).unwrap_or_else(|| panic!("Property {:?} was never registered", self));
         let domained_index = *domain_info.name2did.get(&self.name).expect("gid & did both registered" /* name2gid panic logically occludes/equals this */);
         self.index = PropertyIndex {
             domain_id: domain_info.id,
@@ -329,15 +408,18 @@ impl<V> Prop<V> {
         };
     }
 
-    fn check_name(&self) {
-        intern::check_name(self.domain_name.0);
+    fn try_check_name(&self) -> Result<(), Error> {
+        let invalid = || Error::InvalidName(self.name.0.to_owned());
+        if !intern::is_valid_name(self.domain_name.0) {
+            return Err(Error::InvalidName(self.domain_name.0.to_owned()));
+        }
         let mut parts = self.name.0.splitn(2, '/');
-        let domain_name = parts.next().expect("str::split failed?");
-        let name = parts.next().unwrap_or_else(|| panic!("{:?} is not in the format 'domain/name'", self.name.0));
-        if domain_name != self.domain_name.0 {
-            panic!("Domain names do not match: {:?}, {:?}", domain_name, self.domain_name);
+        let domain_name = parts.next().ok_or_else(invalid)?;
+        let name = parts.next().ok_or_else(invalid)?;
+        if domain_name != self.domain_name.0 || !intern::is_valid_name(name) {
+            return Err(invalid());
         }
-        intern::check_name(name);
+        Ok(())
     }
 }
 impl<V> fmt::Display for Prop<V> {
@@ -347,6 +429,36 @@ impl<V> fmt::Display for Prop<V> {
 }
 
 
+/// Saves and loads the value of a `#[save]` property, for `Universe::save_snapshot`.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct PropertySerial {
+    pub save: fn(&PBox, &mut FnMut(&::erased_serde::Serialize)),
+    pub load: fn(&PBox, &mut ::erased_serde::Deserializer) -> Result<(), ::erased_serde::Error>,
+}
+impl PropertySerial {
+    pub fn of<V>() -> Self
+    where V: Serialize + DeserializeOwned + Any + Send + Sync
+    {
+        fn save<V: Serialize + Any + Send + Sync>(val: &PBox, f: &mut FnMut(&::erased_serde::Serialize)) {
+            let val: &RwLock<V> = val.downcast_ref().expect("property downcast failed");
+            f(&*val.read().unwrap());
+        }
+        fn load<V: DeserializeOwned + Any + Send + Sync>(
+            val: &PBox,
+            deserializer: &mut ::erased_serde::Deserializer,
+        ) -> Result<(), ::erased_serde::Error> {
+            let val: &RwLock<V> = val.downcast_ref().expect("property downcast failed");
+            *val.write().unwrap() = ::erased_serde::deserialize(deserializer)?;
+            Ok(())
+        }
+        PropertySerial {
+            save: save::<V>,
+            load: load::<V>,
+        }
+    }
+}
+
 /// Property manipulation methods.
 impl Universe {
     /// Returns a copy of the value of the given property. Only works for properties that are `Copy`.
@@ -376,43 +488,56 @@ impl Universe {
     pub fn write<V: Any + Sync>(&self, prop: &ToPropRef<V>) -> RwLockWriteGuard<V> {
         self[prop].write().unwrap()
     }
-}
-impl<'a, V: Any + Sync> ::std::ops::Index<&'a ToPropRef<V>> for Universe {
-    type Output = RwLock<V>;
-    fn index(&self, prop: &'a ToPropRef<V>) -> &RwLock<V> {
+
+    /// Like `universe[prop]`, but returns an error if the property isn't in this `Universe`.
+    pub fn try_index<V: Any + Sync>(&self, prop: &ToPropRef<V>) -> Result<&RwLock<V>, Error> {
         let prop: &Prop<V> = unsafe {
             // FIXME: Just don't call Prop.register() at the same time as this!
             prop.get()
         };
+        let name = || prop.to_string();
         let domain = self.domains.get(prop.get_domain_id().0);
         let domain_instance: &DomainInstance = match domain {
             None if prop.get_domain_id() == unset::DOMAIN_ID => {
-                panic!("The property {:?} was not registered.", prop)
+                return Err(Error::PropertyNotRegistered(format!("{:?}", prop)));
             },
             Some(&MaybeDomain::Unset(_))
             | None /* Must be some new fangled domain this Universe doesn't care about */
             => {
-                panic!("The property {} is not in this Universe's domain.", prop)
+                return Err(Error::PropertyNotInUniverse(name()));
             },
             Some(&MaybeDomain::Domain(ref e)) => e,
         };
         let domained_index = prop.get_index_within_domain().0;
         let v = match domain_instance.property_members.get(domained_index) {
-            None => if prop.get_domain_id() == unset::DOMAIN_ID {
-                panic!("The property {} was never initialized.", prop)
+            None => return Err(if prop.get_domain_id() == unset::DOMAIN_ID {
+                Error::PropertyNotInitialized(name())
             } else {
-                panic!("The property {} was added to the domain AFTER this Universe was created.", prop)
-            },
+                Error::PropertyAddedLate(name())
+            }),
             Some(v) => v,
         };
-        let l: Option<&RwLock<V>> = v.downcast_ref();
-        match l {
-            None => {
-                panic!("Downcast of property {} failed.", prop)
-            },
-            // FIXME: Say what the type is?
-            Some(ret) => ret
-        }
+        // FIXME: Say what the type is?
+        v.downcast_ref().ok_or_else(|| Error::PropertyType(name()))
+    }
+
+    /// Like `read`, but returns an error instead of panicking.
+    pub fn try_read<V: Any + Sync>(&self, prop: &ToPropRef<V>) -> Result<RwLockReadGuard<V>, Error> {
+        self.try_index(prop)?.read().map_err(|_| Error::Poisoned(prop_name(prop)))
+    }
+
+    /// Like `write`, but returns an error instead of panicking.
+    pub fn try_write<V: Any + Sync>(&self, prop: &ToPropRef<V>) -> Result<RwLockWriteGuard<V>, Error> {
+        self.try_index(prop)?.write().map_err(|_| Error::Poisoned(prop_name(prop)))
+    }
+}
+fn prop_name<V: Sync>(prop: &ToPropRef<V>) -> String {
+    unsafe { prop.get() }.to_string()
+}
+impl<'a, V: Any + Sync> ::std::ops::Index<&'a ToPropRef<V>> for Universe {
+    type Output = RwLock<V>;
+    fn index(&self, prop: &'a ToPropRef<V>) -> &RwLock<V> {
+        self.try_index(prop).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
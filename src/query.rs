@@ -0,0 +1,50 @@
+//! Loops over tables, joining them on equal keys. This supports the `query!` macro from
+//! `v11_macros`.
+//!
+//! ```ignored
+//! query!(universe, for (s in ships, c in sailors where c.ship == s && sailors.age[c] > 30) {
+//!     println!("{} sails on the {}", sailors.name[c], ships.name[s]);
+//! });
+//! ```
+//!
+//! Each `row in table` locks `table` with `table::read(universe)`, and binds the lock to a
+//! variable named after the table. `mut row in table` uses `table::write(universe)` instead.
+//! Each table is only locked once, even if it is listed twice.
+//!
+//! The `where` clause is a list of conditions separated by `&&`.
+//! An `||` has to be inside parentheses, as in `where c.ship == s && (a || b)`.
+//! A condition of the form `a.column == b` or `a.column == b.column`, where `a` and `b` are rows
+//! of different tables, joins the two tables. (A row by itself stands for its `RowId`.)
+//! Every other condition is an ordinary `bool` expression that filters the results.
+//!
+//! The first two tables are joined with `join::merge_join`, which looks rows up in an `#[index]`ed
+//! column (or by `RowId`) if it can, and otherwise merges the sorted keys of both tables
+//! with a `JoinCore`.
+//! Later tables are looked up for each match, and searched if there is no index.
+//! A table that isn't joined to an earlier one loops over all of its rows.
+//!
+//! The rows are `CheckedRowId`s, so indexing the tables' columns with them doesn't check bounds.
+//! If any table is `mut`, the matching rows are gathered before the body runs, and they are
+//! bound as plain `RowId`s instead.
+//!
+//! Deleted rows of `consistent` tables are skipped.
+//! A `break` in the body leaves the whole query.
+
+use crate::tables::LockedTable;
+use crate::index::CheckedRowId;
+use crate::join::{JoinKey, live_rows, checked};
+
+/// Returns the rows of `table` whose `key` is `value`.
+/// This uses the key's index if it has one, and searches the table if not.
+#[doc(hidden)]
+pub fn matching<'a, L, K>(table: &'a L, key: &'a K, value: K::Key) -> Box<Iterator<Item=CheckedRowId<'a, L>> + 'a>
+where
+    L: LockedTable,
+    K: JoinKey<L>,
+{
+    match key.lookup(table, value) {
+        // The index may have rows that are deleted, so they're checked.
+        Some(found) => Box::new(found.filter_map(move |i| checked(table, i))),
+        None => Box::new(live_rows(table).filter(move |i| key.key(table, i.to_usize()) == value)),
+    }
+}
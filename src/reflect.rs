@@ -0,0 +1,140 @@
+//! Looking at columns without knowing their types, for inspectors, consoles, and scripting bridges.
+//!
+//! ```no_compile
+//! let table = universe.table_by_name("MY_DOMAIN/my_table")?;
+//! let table = table.read().unwrap();
+//! for col in table.dyn_columns() {
+//!     for i in 0..col.len() {
+//!         if table.is_deleted(i) { continue; }
+//!         println!("{}[{}] = {:?}", col.meta().name, i, col.get_dyn(i));
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+
+use crate::Universe;
+use crate::error::Error;
+use crate::columns::AnyCol;
+use crate::domain::MaybeDomain;
+use crate::tables::{GenericTable, GenericColumn, ColumnMeta, TTable};
+
+/// Functions made by `table!` that know the concrete type of a column.
+/// Each is `None` if the table doesn't support it.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Reflection {
+    pub len: fn(&AnyCol) -> usize,
+    /// Requires `#[row_derive(Debug)]`.
+    pub get_dyn: Option<for<'a> fn(&'a AnyCol, usize) -> &'a fmt::Debug>,
+    /// Requires `#[save]`.
+    pub get_serialize: Option<for<'a> fn(&'a AnyCol, usize) -> &'a ::erased_serde::Serialize>,
+    /// Requires `#[save]`, and isn't available for sorted tables or composite-indexed columns.
+    pub set_from: Option<fn(&mut AnyCol, usize, &mut ::erased_serde::Deserializer) -> Result<(), ::erased_serde::Error>>,
+}
+
+/// A column of a `GenericTable`, with its elements' type erased.
+/// Rows that were deleted from consistent tables are still present; see `GenericTable::is_deleted`.
+pub trait DynColumn {
+    fn meta(&self) -> &ColumnMeta;
+    fn len(&self) -> usize;
+    /// Returns an element for printing. Panics if `i` is out of range.
+    /// Tables without `#[row_derive(Debug)]` give a placeholder.
+    fn get_dyn(&self, i: usize) -> &fmt::Debug;
+    /// Returns an element for serializing, if the table is `#[save]`. Panics if `i` is out of range.
+    fn get_serialize(&self, i: usize) -> Option<&::erased_serde::Serialize>;
+}
+
+/// A column that can be changed, from `GenericTable::dyn_column_mut`.
+pub struct DynColumnMut<'a> {
+    column: &'a mut GenericColumn,
+    table: &'a TTable,
+}
+impl<'a> DynColumnMut<'a> {
+    /// Replaces an element with a deserialized one. Deleted rows can't be set.
+    /// Indexes are kept up to date, and the row is marked for `#[track_edits]` and `#[journal]`,
+    /// but trackers are not told.
+    pub fn set_from(&mut self, i: usize, deserializer: &mut ::erased_serde::Deserializer) -> Result<(), Error> {
+        let set = self.column.reflection.set_from.ok_or_else(|| Error::ReadOnlyColumn(self.column.meta.name.to_string()))?;
+        let len = self.column.len();
+        if i >= len {
+            return Err(Error::RowOutOfRange { row: i, len });
+        }
+        if self.table.is_free(i) {
+            return Err(Error::RowDeleted(i));
+        }
+        set(&mut *self.column.data, i, deserializer).map_err(|e| Error::Deserialize(e.to_string()))
+    }
+}
+impl<'a> Deref for DynColumnMut<'a> {
+    type Target = DynColumn + 'a;
+    fn deref(&self) -> &Self::Target { &*self.column }
+}
+
+struct Opaque;
+impl fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<opaque>")
+    }
+}
+static OPAQUE: Opaque = Opaque;
+
+impl DynColumn for GenericColumn {
+    fn meta(&self) -> &ColumnMeta { &self.meta }
+
+    fn len(&self) -> usize { (self.reflection.len)(&*self.data) }
+
+    fn get_dyn(&self, i: usize) -> &fmt::Debug {
+        match self.reflection.get_dyn {
+            Some(get) => get(&*self.data, i),
+            None => &OPAQUE,
+        }
+    }
+
+    fn get_serialize(&self, i: usize) -> Option<&::erased_serde::Serialize> {
+        self.reflection.get_serialize.map(|get| get(&*self.data, i))
+    }
+}
+
+impl GenericTable {
+    pub fn dyn_columns(&self) -> Vec<&DynColumn> {
+        self.columns.iter().map(|c| c as &DynColumn).collect()
+    }
+
+    pub fn dyn_column(&self, name: &str) -> Result<&DynColumn, Error> {
+        match self.columns.iter().find(|c| c.meta.name == name) {
+            Some(c) => Ok(c),
+            None => Err(Error::UnknownColumn { table: self.name.0.to_owned(), column: name.to_owned() }),
+        }
+    }
+
+    pub fn dyn_column_mut(&mut self, name: &str) -> Result<DynColumnMut, Error> {
+        let table = &*self.table;
+        match self.columns.iter_mut().find(|c| c.meta.name == name) {
+            Some(column) => Ok(DynColumnMut { column, table }),
+            None => Err(Error::UnknownColumn { table: self.name.0.to_owned(), column: name.to_owned() }),
+        }
+    }
+
+    /// Returns `true` if the row is in the free-list of a consistent table.
+    pub fn is_deleted(&self, i: usize) -> bool {
+        self.table.is_free(i)
+    }
+}
+
+impl Universe {
+    /// Finds a table by its `"DOMAIN/table"` name.
+    pub fn table_by_name(&self, name: &str) -> Result<&::std::sync::RwLock<GenericTable>, Error> {
+        let mut parts = name.splitn(2, '/');
+        let (domain, table) = match (parts.next(), parts.next()) {
+            (Some(domain), Some(table)) => (domain, table),
+            _ => return Err(Error::InvalidName(name.to_owned())),
+        };
+        let instance = self.domains.iter().filter_map(|d| match *d {
+            MaybeDomain::Domain(ref i) if i.name.0 == domain => Some(i),
+            _ => None,
+        }).next().ok_or_else(|| Error::UnknownDomain(domain.to_owned()))?;
+        instance.try_get_generic_table(table)
+    }
+}
@@ -4,6 +4,7 @@ use serde::de::{Deserialize, Deserializer, Visitor, SeqAccess};
 use crate::columns::{Col, TCol};
 use crate::tables::GetTableName;
 use std::fmt;
+use std::marker::PhantomData;
 
 impl<C, T> Serialize for Col<C, T>
 where
@@ -15,13 +16,7 @@ where
     where
         S: Serializer,
     {
-        let inner = self.inner();
-        let mut seq = serializer.serialize_seq(Some(inner.len()))?;
-        for i in 0..inner.len() {
-            let e = unsafe { inner.unchecked_index(i) };
-            seq.serialize_element(e)?;
-        }
-        seq.end()
+        self.inner().serialize_col(serializer)
     }
 }
 impl<'de, C, T> Deserialize<'de> for Col<C, T>
@@ -34,35 +29,60 @@ where
     where
         D: Deserializer<'de>,
     {
-        let col = Col::new();
-        deserializer.deserialize_seq(col)
+        let mut col = Col::new();
+        *col.inner_mut() = C::deserialize_col(deserializer)?;
+        Ok(col)
     }
 }
-impl<'de, C, T> Visitor<'de> for Col<C, T>
+
+/// The default `TCol::serialize_col`: a sequence of the elements.
+pub(crate) fn serialize_elements<C, S>(col: &C, serializer: S) -> Result<S::Ok, S::Error>
+where
+    C: TCol,
+    C::Element: Serialize,
+    S: Serializer,
+{
+    let mut seq = serializer.serialize_seq(Some(col.len()))?;
+    for i in 0..col.len() {
+        let e = unsafe { col.unchecked_index(i) };
+        seq.serialize_element(e)?;
+    }
+    seq.end()
+}
+
+/// The default `TCol::deserialize_col`.
+pub(crate) fn deserialize_elements<'de, C, D>(deserializer: D) -> Result<C, D::Error>
+where
+    C: TCol,
+    C::Element: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_seq(Elements(PhantomData))
+}
+
+struct Elements<C>(PhantomData<C>);
+impl<'de, C> Visitor<'de> for Elements<C>
 where
     C: TCol,
-    T: GetTableName,
     C::Element: Deserialize<'de>,
 {
-    type Value = Col<C, T>;
+    type Value = C;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         write!(formatter, "a column")
     }
 
-    fn visit_seq<A>(mut self, mut seq: A) -> Result<Self::Value, A::Error>
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: SeqAccess<'de>,
     {
-        {
-            let inner = self.inner_mut();
-            if let Some(hint) = seq.size_hint() {
-                inner.reserve(hint);
-            }
-            while let Some(e) = seq.next_element()? {
-                inner.push(e);
-            }
+        let mut col = C::new();
+        if let Some(hint) = seq.size_hint() {
+            col.reserve(hint);
+        }
+        while let Some(e) = seq.next_element()? {
+            col.push(e);
         }
-        Ok(self)
+        Ok(col)
     }
 }
@@ -0,0 +1,352 @@
+//! Saving and loading every `#[save]` table and property of some domains at once.
+//!
+//! A snapshot is a map from domain names to
+//! `{ tables: { table_name: Extraction, … }, properties: { "DOMAIN/NAME": value, … } }`.
+//! Tables are written in the order they were registered, so that when they are read back,
+//! the tables that have foreign keys pointing at them are restored first,
+//! and the keys are remapped through their `Flush`.
+//!
+//! A delta has the same layout, but its tables are the `$table::Delta`s of the `#[journal]` tables,
+//! which hold only the rows that changed since the previous delta.
+
+use std::fmt;
+use std::sync::RwLockReadGuard;
+
+use serde::ser::{Serialize, Serializer, SerializeMap, SerializeStruct};
+use serde::de::{self, Deserializer, DeserializeSeed, Visitor, MapAccess, SeqAccess};
+
+use crate::Universe;
+use crate::event;
+use crate::domain::{DomainName, DomainInstance, MaybeDomain, GlobalProperties, clone_globals};
+use crate::tables::{Restorer, TableName, PendingDelta};
+use crate::tracking::Select;
+use crate::intern::PBox;
+use crate::property::PropertySerial;
+
+const DOMAIN_FIELDS: &'static [&'static str] = &["tables", "properties"];
+
+/// Snapshot methods.
+impl Universe {
+    /// Serializes the contents of every `#[save]` table and property in the given domains.
+    pub fn save_snapshot<S: Serializer>(&self, domains: &[DomainName], serializer: S) -> Result<S::Ok, S::Error> {
+        self.save_domains(domains, serializer, None)
+    }
+
+    /// Serializes the rows of every `#[journal]` table in the given domains
+    /// that changed since the last call, along with every `#[save]` property.
+    /// If this succeeds, the journals are emptied, so each delta picks up where the previous one ended.
+    ///
+    /// Load the full snapshot and then every delta, in order, to get back to the latest state.
+    pub fn save_delta<S: Serializer>(&self, domains: &[DomainName], serializer: S) -> Result<S::Ok, S::Error> {
+        // Every table's delta is taken before anything is written. The journals keep recording
+        // in the meantime, so if saving fails, the deltas can be merged back in under them.
+        let deltas: Vec<Vec<(TableName, Box<PendingDelta>)>> = domains
+            .iter()
+            .map(|name| {
+                let domain = self.get_domain_instance(*name);
+                domain.tables_registration_order
+                    .iter()
+                    .filter_map(|table| {
+                        let saver = {
+                            let gt = domain.get_generic_table(*table).read().unwrap();
+                            gt.table.get_delta_functions()
+                        };
+                        // The saver locks the table for writing.
+                        saver.map(|(save, _)| (*table, save(self)))
+                    })
+                    .collect()
+            })
+            .collect();
+        let ret = self.save_domains(domains, serializer, Some(&deltas));
+        if ret.is_err() {
+            for (_, delta) in deltas.iter().flat_map(|d| d.iter()) {
+                delta.put_back(self);
+            }
+        }
+        ret
+    }
+
+    fn save_domains<S: Serializer>(
+        &self,
+        domains: &[DomainName],
+        serializer: S,
+        deltas: Option<&[Vec<(TableName, Box<PendingDelta>)>]>,
+    ) -> Result<S::Ok, S::Error> {
+        let globals = clone_globals();
+        let globals = globals.read().unwrap();
+        let mut map = serializer.serialize_map(Some(domains.len()))?;
+        for (n, name) in domains.iter().enumerate() {
+            let domain = self.get_domain_instance(*name);
+            map.serialize_entry(domain.name.0, &DomainSnapshot {
+                universe: self,
+                domain,
+                globals: &globals,
+                deltas: deltas.map(|d| &d[n][..]),
+            })?;
+        }
+        map.end()
+    }
+
+    /// Loads a snapshot made by `save_snapshot`.
+    /// Rows are added to the tables as if they were pushed, with the `DESERIALIZE` event,
+    /// except that the rows of an empty `consistent` table keep their `RowId`s,
+    /// and the rows that had been deleted are deleted again. (So deltas can be applied on top.)
+    /// Loading isn't recorded in the journals of `#[journal]` tables.
+    /// Every domain in the snapshot must be in this `Universe`, and every foreign key must refer
+    /// to a row that is in the snapshot; otherwise an error is returned. A table that fails to
+    /// load is left as it was, but the tables loaded before it keep their rows.
+    pub fn load_snapshot<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(SnapshotVisitor { universe: self, delta: false })
+    }
+
+    /// Applies a delta made by `save_delta`.
+    /// Rows keep their `RowId`s, and no events are sent.
+    pub fn apply_delta<'de, D: Deserializer<'de>>(&self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(SnapshotVisitor { universe: self, delta: true })
+    }
+
+    fn get_domain_instance(&self, name: DomainName) -> &DomainInstance {
+        match self.domains.get(name.get_id().0) {
+            Some(&MaybeDomain::Domain(ref instance)) => instance,
+            _ => panic!("Domain {} is not in this Universe", name),
+        }
+    }
+}
+
+struct DomainSnapshot<'a> {
+    universe: &'a Universe,
+    domain: &'a DomainInstance,
+    globals: &'a GlobalProperties,
+    /// The domain's deltas, if this is a delta rather than a snapshot.
+    deltas: Option<&'a [(TableName, Box<PendingDelta>)]>,
+}
+impl<'a> Serialize for DomainSnapshot<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut out = serializer.serialize_struct("DomainSnapshot", DOMAIN_FIELDS.len())?;
+        out.serialize_field("tables", &TablesSnapshot(self))?;
+        out.serialize_field("properties", &PropertiesSnapshot(self))?;
+        out.end()
+    }
+}
+
+struct TablesSnapshot<'a: 'b, 'b>(&'b DomainSnapshot<'a>);
+impl<'a, 'b> Serialize for TablesSnapshot<'a, 'b> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let domain = self.0.domain;
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(deltas) = self.0.deltas {
+            for &(name, ref delta) in deltas {
+                map.serialize_entry(name.0, delta.as_serialize())?;
+            }
+            return map.end();
+        }
+        for name in &domain.tables_registration_order {
+            let extraction = {
+                let gt = domain.get_generic_table(*name).read().unwrap();
+                gt.table.extract_serialization(self.0.universe, Select::All)
+            };
+            if let Some(extraction) = extraction {
+                map.serialize_entry(name.0, &extraction)?;
+            }
+        }
+        map.end()
+    }
+}
+
+struct PropertiesSnapshot<'a: 'b, 'b>(&'b DomainSnapshot<'a>);
+impl<'a, 'b> Serialize for PropertiesSnapshot<'a, 'b> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (name, serial, val) in properties(self.0.globals, self.0.domain) {
+            let mut ret = Ok(());
+            (serial.save)(val, &mut |val| ret = map.serialize_entry(name, val));
+            ret?;
+        }
+        map.end()
+    }
+}
+
+/// Lists the `#[save]` properties of a domain.
+fn properties<'a>(
+    globals: &'a GlobalProperties,
+    domain: &'a DomainInstance,
+) -> impl Iterator<Item=(&'static str, PropertySerial, &'a PBox)> + 'a {
+    let info = &globals.domains[&domain.name];
+    info.property_members
+        .iter()
+        .zip(domain.property_members.iter())
+        .filter_map(move |(gid, val)| {
+            let serial = globals.gid2producer[gid.0].0.serial()?;
+            Some((globals.gid2name[gid].0, serial, val))
+        })
+}
+
+
+struct SnapshotVisitor<'u> {
+    universe: &'u Universe,
+    delta: bool,
+}
+impl<'de, 'u> Visitor<'de> for SnapshotVisitor<'u> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of domains")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            let domain = self.universe.domains.iter().filter_map(|d| match *d {
+                MaybeDomain::Domain(ref i) if i.name.0 == name => Some(i),
+                _ => None,
+            }).next().ok_or_else(|| de::Error::custom(format!("domain {} is not in this Universe", name)))?;
+            map.next_value_seed(DomainSeed {
+                universe: self.universe,
+                domain,
+                delta: self.delta,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DomainSeed<'u> {
+    universe: &'u Universe,
+    domain: &'u DomainInstance,
+    delta: bool,
+}
+impl<'de, 'u> DeserializeSeed<'de> for DomainSeed<'u> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_struct("DomainSnapshot", DOMAIN_FIELDS, self)
+    }
+}
+impl<'de, 'u> Visitor<'de> for DomainSeed<'u> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a domain's tables and properties")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(field) = map.next_key::<String>()? {
+            match field.as_str() {
+                "tables" => map.next_value_seed(TablesSeed(self))?,
+                "properties" => map.next_value_seed(PropertiesSeed(self))?,
+                _ => return Err(de::Error::unknown_field(&field, DOMAIN_FIELDS)),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        seq.next_element_seed(TablesSeed(self))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        seq.next_element_seed(PropertiesSeed(self))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(())
+    }
+}
+
+struct TablesSeed<'u>(DomainSeed<'u>);
+impl<'de, 'u> DeserializeSeed<'de> for TablesSeed<'u> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl<'de, 'u> Visitor<'de> for TablesSeed<'u> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of table extractions")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let DomainSeed { universe, domain, delta } = self.0;
+        let order = &domain.tables_registration_order;
+        let mut next = 0;
+        while let Some(name) = map.next_key::<String>()? {
+            let i = order
+                .iter()
+                .position(|t| t.0 == name)
+                .ok_or_else(|| de::Error::custom(format!("table {}/{} is not registered", domain.name, name)))?;
+            if i < next {
+                return Err(de::Error::custom(format!("table {}/{} is out of registration order", domain.name, name)));
+            }
+            next = i + 1;
+            let restorer = {
+                let gt = domain.get_generic_table(order[i]).read().unwrap();
+                if delta {
+                    gt.table
+                        .get_delta_functions()
+                        .map(|(_, apply)| apply)
+                        .ok_or_else(|| de::Error::custom(format!("table {}/{} is not #[journal]", domain.name, name)))?
+                } else {
+                    gt.table
+                        .get_restorer()
+                        .ok_or_else(|| de::Error::custom(format!("table {}/{} is not #[save]", domain.name, name)))?
+                }
+            };
+            map.next_value_seed(TableSeed { universe, restorer })?;
+        }
+        Ok(())
+    }
+}
+
+struct TableSeed<'u> {
+    universe: &'u Universe,
+    restorer: Restorer,
+}
+impl<'de, 'u> DeserializeSeed<'de> for TableSeed<'u> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let mut deserializer = ::erased_serde::Deserializer::erase(deserializer);
+        (self.restorer)(self.universe, &mut deserializer, event::DESERIALIZE).map_err(de::Error::custom)
+    }
+}
+
+struct PropertiesSeed<'u>(DomainSeed<'u>);
+impl<'de, 'u> DeserializeSeed<'de> for PropertiesSeed<'u> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl<'de, 'u> Visitor<'de> for PropertiesSeed<'u> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of properties")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let domain = (self.0).domain;
+        let globals = clone_globals();
+        let globals: RwLockReadGuard<GlobalProperties> = globals.read().unwrap();
+        while let Some(name) = map.next_key::<String>()? {
+            let (_, serial, val) = properties(&globals, domain)
+                .find(|p| p.0 == name)
+                .ok_or_else(|| de::Error::custom(format!("property {} is not #[save]", name)))?;
+            map.next_value_seed(PropertySeed { serial, val })?;
+        }
+        Ok(())
+    }
+}
+
+struct PropertySeed<'a> {
+    serial: PropertySerial,
+    val: &'a PBox,
+}
+impl<'de, 'a> DeserializeSeed<'de> for PropertySeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let mut deserializer = ::erased_serde::Deserializer::erase(deserializer);
+        (self.serial.load)(self.val, &mut deserializer).map_err(de::Error::custom)
+    }
+}
@@ -1,13 +1,16 @@
 //! Data structures for storing columnar elements.
 
 use crate::Storable;
-use crate::columns::TCol;
+use crate::columns::{TCol, ContiguousCol};
+use crate::avec::{ArrayVec, Pages, PagesMut};
+pub use crate::mapped::MappedCol;
+pub use crate::dictionary::{InternCol, IStr};
 
 /// Stores data contiguously using the standard rust `Vec`.
 /// This is ideal for tables that do not have rows added to them often.
 #[derive(Debug)]
 pub struct VecCol<E: Storable> {
-    data: Vec<E>,
+    pub(crate) data: Vec<E>,
 }
 impl<E: Storable> TCol for VecCol<E> {
     type Element = E;
@@ -36,14 +39,46 @@ impl<E: Storable> TCol for VecCol<E> {
         self.data.into_iter()
     }
 }
+impl<E: Storable> ContiguousCol for VecCol<E> {
+    fn as_slice(&self) -> &[E] { &self.data }
+    fn as_mut_slice(&mut self) -> &mut [E] { &mut self.data }
+}
 
-/// Temporary (hopefully) stub for avec.
-/// Use this for tables that may be heavily extended at run-time.
-// FIXME: Implement. Mostly just need some kind of page_size allocator.
-pub type SegCol<E> = VecCol<E>;
+/// Stores data in pages of about 16KiB.
+/// Use this for tables that may be heavily extended at run-time: adding rows never moves the
+/// existing ones, so there's no copying when the table grows.
+#[derive(Debug)]
+pub struct SegCol<E: Storable> {
+    data: ArrayVec<E>,
+}
+impl<E: Storable> SegCol<E> {
+    /// Returns an iterator over each page's elements.
+    pub fn pages(&self) -> Pages<E> { self.data.pages() }
+    /// Returns an iterator over each page's elements.
+    pub fn pages_mut(&mut self) -> PagesMut<E> { self.data.pages_mut() }
+}
+impl<E: Storable> TCol for SegCol<E> {
+    type Element = E;
+
+    fn new() -> Self { SegCol { data: ArrayVec::new_auto() } }
+
+    fn len(&self) -> usize { self.data.len() }
+    fn truncate(&mut self, len: usize) { self.data.truncate(len) }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element { self.data.get_unchecked(i) }
+    unsafe fn unchecked_index_mut(&mut self, i: usize) -> &mut Self::Element { self.data.get_unchecked_mut(i) }
+    fn reserve(&mut self, n: usize) { self.data.reserve(n) }
+    fn clear(&mut self) { self.data.clear() }
+    fn push(&mut self, v: Self::Element) { self.data.push(v) }
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) { self.data.swap(a, b) }
+
+    type IntoIter = crate::avec::IntoIter<Self::Element>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter()
+    }
+}
 
 extern crate bit_vec;
-type BitVec = self::bit_vec::BitVec<u32>;
+pub(crate) type BitVec = self::bit_vec::BitVec<u32>;
 /*
 fn bitvec_from_parts<E, R>(len: usize, mut data: Vec<u32>, err: E) -> Result<BitVec, R>
 where E: FnOnce(usize, &'static str) -> R
@@ -80,9 +115,9 @@ where E: FnOnce(usize, &'static str) -> R
 /// Densely packed booleans.
 #[derive(Debug)]
 pub struct BoolCol {
-    ref_val: bool,
-    ref_idx: usize,
-    data: BitVec,
+    pub(crate) ref_val: bool,
+    pub(crate) ref_idx: usize,
+    pub(crate) data: BitVec,
 }
 impl Default for BoolCol {
     fn default() -> Self {
@@ -149,6 +184,133 @@ impl TCol for BoolCol {
     }
 }
 
+/// The number of elements in each chunk of a `SoaChunkCol`.
+pub const SOA_LANES: usize = 16;
+
+/// Which lanes of a chunk hold rows. Bit `i` is set if lane `i` does.
+pub type LaneMask = u16;
+
+fn lane_mask(n: usize) -> LaneMask {
+    if n >= SOA_LANES { !0 } else { (1 << n) - 1 }
+}
+
+/// A block of `SOA_LANES` elements, aligned to 64 bytes (a cache line, or an AVX-512 register).
+#[repr(C, align(64))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SoaChunk<E: Copy> {
+    pub lanes: [E; SOA_LANES],
+}
+
+/// Stores `Copy` elements, usually numbers, in aligned chunks of `SOA_LANES`, for SIMD kernels.
+///
+/// The lanes of the last chunk that are past the end of the column hold `E::default()`, unless
+/// they were changed through `chunks_mut`. Kernels may work on every lane, and use the
+/// `LaneMask` to ignore the extra ones.
+#[derive(Debug)]
+pub struct SoaChunkCol<E: Storable + Copy + Default> {
+    chunks: Vec<SoaChunk<E>>,
+    len: usize,
+}
+impl<E: Storable + Copy + Default> SoaChunkCol<E> {
+    /// Returns an iterator over each chunk, and a mask of which of its lanes hold rows.
+    pub fn chunks(&self) -> SoaChunks<E> {
+        SoaChunks { chunks: self.chunks.iter(), left: self.len }
+    }
+
+    /// Returns an iterator over each chunk, and a mask of which of its lanes hold rows.
+    pub fn chunks_mut(&mut self) -> SoaChunksMut<E> {
+        SoaChunksMut { chunks: self.chunks.iter_mut(), left: self.len }
+    }
+}
+impl<E: Storable + Copy + Default> TCol for SoaChunkCol<E> {
+    type Element = E;
+
+    fn new() -> Self { SoaChunkCol { chunks: Vec::new(), len: 0 } }
+
+    fn len(&self) -> usize { self.len }
+    fn truncate(&mut self, len: usize) {
+        if len >= self.len { return; }
+        self.chunks.truncate((len + SOA_LANES - 1) / SOA_LANES);
+        let used = len % SOA_LANES;
+        if used != 0 {
+            let last = self.chunks.last_mut().unwrap();
+            for lane in &mut last.lanes[used..] {
+                *lane = E::default();
+            }
+        }
+        self.len = len;
+    }
+    unsafe fn unchecked_index(&self, i: usize) -> &Self::Element {
+        self.chunks.get_unchecked(i / SOA_LANES).lanes.get_unchecked(i % SOA_LANES)
+    }
+    unsafe fn unchecked_index_mut(&mut self, i: usize) -> &mut Self::Element {
+        self.chunks.get_unchecked_mut(i / SOA_LANES).lanes.get_unchecked_mut(i % SOA_LANES)
+    }
+    fn reserve(&mut self, n: usize) {
+        let chunks = (self.len + n + SOA_LANES - 1) / SOA_LANES;
+        let more = chunks.saturating_sub(self.chunks.len());
+        self.chunks.reserve(more)
+    }
+    fn push(&mut self, v: Self::Element) {
+        if self.len % SOA_LANES == 0 {
+            self.chunks.push(SoaChunk::default());
+        }
+        let i = self.len;
+        self.len += 1;
+        unsafe { *self.unchecked_index_mut(i) = v; }
+    }
+    unsafe fn unchecked_swap(&mut self, a: usize, b: usize) {
+        // The elements are `Copy`, so there's no need for two references at once.
+        let va = *self.unchecked_index(a);
+        let vb = ::std::mem::replace(self.unchecked_index_mut(b), va);
+        *self.unchecked_index_mut(a) = vb;
+    }
+
+    type IntoIter = ::std::vec::IntoIter<Self::Element>;
+    fn into_iter(self) -> Self::IntoIter {
+        let mut ret = Vec::with_capacity(self.chunks.len() * SOA_LANES);
+        for chunk in &self.chunks {
+            ret.extend_from_slice(&chunk.lanes);
+        }
+        ret.truncate(self.len);
+        ret.into_iter()
+    }
+}
+
+/// Returned by `SoaChunkCol::chunks`.
+pub struct SoaChunks<'a, E: Copy + 'a> {
+    chunks: ::std::slice::Iter<'a, SoaChunk<E>>,
+    left: usize,
+}
+impl<'a, E: Copy + 'a> Iterator for SoaChunks<'a, E> {
+    type Item = (&'a SoaChunk<E>, LaneMask);
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        let n = ::std::cmp::min(self.left, SOA_LANES);
+        self.left -= n;
+        Some((chunk, lane_mask(n)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.chunks.size_hint() }
+}
+
+/// Returned by `SoaChunkCol::chunks_mut`.
+pub struct SoaChunksMut<'a, E: Copy + 'a> {
+    chunks: ::std::slice::IterMut<'a, SoaChunk<E>>,
+    left: usize,
+}
+impl<'a, E: Copy + 'a> Iterator for SoaChunksMut<'a, E> {
+    type Item = (&'a mut SoaChunk<E>, LaneMask);
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunks.next()?;
+        let n = ::std::cmp::min(self.left, SOA_LANES);
+        self.left -= n;
+        Some((chunk, lane_mask(n)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.chunks.size_hint() }
+}
+
 #[cfg(test)]
 mod test {
     use super::{TCol, BoolCol};
@@ -202,4 +364,36 @@ mod test {
             assert_eq!(bc.unchecked_index(0), &false);
         }
     }
+
+    #[test]
+    fn soa_chunks() {
+        use super::{SoaChunkCol, SOA_LANES};
+        let mut col = SoaChunkCol::<f32>::new();
+        for i in 0..20 {
+            col.push(i as f32);
+        }
+        let masks: Vec<_> = col.chunks().map(|(chunk, mask)| {
+            assert_eq!(chunk as *const _ as usize % 64, 0);
+            mask
+        }).collect();
+        assert_eq!(masks, vec![0xFFFF, 0b1111]);
+        unsafe {
+            col.unchecked_swap(0, 19);
+            assert_eq!(*col.unchecked_index(0), 19.0);
+            assert_eq!(*col.unchecked_index(19), 0.0);
+        }
+        col.truncate(17);
+        assert_eq!(col.len(), 17);
+        let (last, mask) = col.chunks().last().unwrap();
+        assert_eq!(mask, 1);
+        assert_eq!(&last.lanes[1..4], &[0.0, 0.0, 0.0]);
+        for (chunk, _) in col.chunks_mut() {
+            for lane in chunk.lanes.iter_mut() {
+                *lane *= 2.0;
+            }
+        }
+        let all: Vec<f32> = col.into_iter().collect();
+        assert_eq!(all.len(), 17);
+        assert_eq!(all[SOA_LANES], 32.0);
+    }
 }
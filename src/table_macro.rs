@@ -34,8 +34,11 @@ Here are some example columns:
 * `[i32; VecCol<i32>]` (a column implemented with `Vec<i32>`)
 * `[u8; SegCol<u8>]` (a column of u8 stored in non-contiguous chunks)
 * `[bool; BoolCol]` (a column specialized for single bit storage)
+* `[f32; MappedCol<f32>]` (like `VecCol`, but can be backed by a memory-mapped file; see `#[binary]`)
+* `[f32; SoaChunkCol<f32>]` (stored in 64-byte aligned chunks, for SIMD; see `chunks()`)
+* `[IStr; InternCol]` (shared strings, stored as `u32` codes into a per-column dictionary; `find()` uses the column's own index of the codes, so it can't have `#[index]`)
 
-(As a special convenience, `VecCol`, `SegCol`, and `BoolCol` are automatically `use`d by the macro.)
+(As a special convenience, `VecCol`, `SegCol`, `BoolCol`, `MappedCol`, `SoaChunkCol`, `InternCol`, and `IStr` are automatically `use`d by the macro.)
 
 Table and column names must be valid Rust identifiers that also match the regex
 `[A-Za-z][A-Za-z_0-9]*`.
@@ -93,6 +96,7 @@ If you put `#[sort_key]` on a column, it will do this for you.
 (The macro derives `Eq`, `PartialEq`, and `PartialOrd` on `$table::RowRef`, and those + `Ord` on `$table::Row`)
 
 Rows can be added with `merge`, and removed with `retain`.
+With a `#[sort_key]`, the table can be searched without an `#[index]`; see below.
 
 Sorted tables are good for [`joincore`].
 
@@ -103,15 +107,51 @@ the main row and references to it are deleted as a unit.
 
 Since maintaining consistency requires locking other tables,
 you must call `table.flush(universe, event)` instead of letting the table drop.
+If a panic drops the lock instead, its unflushed pushes are undone. Call `universe.repair()` to use the
+poisoned lock again, and to finish any flush whose trackers were interrupted by the panic.
+
+With `#[row_derive(Clone)]`, `table.transaction()` turns the lock into a `$table::Transaction`,
+whose `push`, `delete`, and `replace` can be reverted with `undo` and repeated with `redo`.
+`edit(row)` remembers a row and returns the lock, so that its columns can be changed in the same way.
+`commit(universe, event)` flushes as usual, and `rollback()` undoes everything,
+putting back emptied slots in the free-list and the `#[journal]`.
+Use [`transaction::Transactions`] to commit or roll back several tables together.
 
 ## `#[kind = "bag"]`
-NYI. (Row order would be arbitrary and there would be no consistency guarantee.)
+Row order is arbitrary. `delete` immediately moves the last row into the hole,
+so there is no free-list, and iteration never has to skip over deleted rows.
+`push` always appends.
+
+Bag rows can be used as foreign keys. Trackers hear about deleted rows after they're gone,
+by their `RowId`s as of the last flush, and then the rows that were moved to fill the holes
+are passed to `Tracker::moved`;
+`#[foreign_auto]` on an `#[index]` column will follow them.
+(A `#[sort_key]` column can not follow them.)
+If a bag has trackers, you must call `table.flush(universe, event)` after deleting rows.
 
 ## `#[kind = "list"]`
-NYI. (Row order would remain intact, but there would be no consistency guarantee.)
+Row order is the order rows were inserted in. Rows can be added with `push` and `insert_at`,
+and removed with `remove` and `drain`. Insertions and removals are applied when the table is
+flushed (or `compact`ed), which shifts the remaining rows to close the gaps.
+Until then, RowIds keep referring to the positions rows had at the last flush.
+`insert_at(table.len(), row)` is the same as `push`, so rows added at the end stay in call order.
+
+Rows move around, and there is no consistency guarantee, so list rows can not be tracked as foreign keys.
+Lists require `#[row_derive(Clone)]`.
 
 ## `#[kind = "indirect"]`
-NYI. (There would be a table of handles introducing a layer of indirection, but making it easy to implement certain guarantees.)
+There is a table of handles introducing a layer of indirection.
+`push` returns a `$table::Handle`, which packs a slot and a generation into the `#[row_id]` type,
+so that has to be `u64` or `usize`. A slot whose generation runs out isn't reused.
+The columns themselves are kept dense: `delete` moves the last row into the hole, like a bag.
+Handles follow their rows as they move around, and a handle to a deleted row is stale:
+it won't `check`, even if its slot has been reused.
+(`RowId`s are plain positions, and are invalidated by deletions as usual.)
+
+Use `handle.check(&table)` to index the columns, `handle.is_valid(&table)` to see if the row is still there,
+`table.delete_handle(handle)` to delete the row,
+`table.resolve(handle)` to get a `RowId` without panicking, and `table.handle(row)` to go the other way.
+Indirect tables don't support `#[save]`, `restore_extract`, or functions like `retain` that shuffle rows around.
 
 # Using the generated table
 
@@ -119,6 +159,17 @@ A lock on the table must be obtained using `$tablename::read(universe)`.
 
 (FIXME: Link to `v11::example`)
 
+Code that only knows the table's name can use `universe.table_by_name("DOMAIN/table")`,
+and look at its columns through [`reflect::DynColumn`].
+Elements can be printed if the table has `#[row_derive(Debug)]`, and serialized if it is `#[save]`.
+Live rows of `#[save]` tables can also be set, through `GenericTable::dyn_column_mut`.
+
+Columns whose storage is a [`columns::ContiguousCol`], like `VecCol`, can be borrowed whole
+with `as_slice()` and `as_mut_slice()`.
+
+With v11's `rayon` feature, `par_iter()` and `par_iter_mut()` visit rows on several threads.
+See [`par`].
+
 # Table Attributes
 
 This works like so:
@@ -144,6 +195,37 @@ Puts `#[derive(Foo, Bar)]` on the generated `Row` and `RowRef` structs.
 ## `#[version = "0"]`
 A version number for the table. The default is `0`. It is a `u32`.
 
+## `#[save]`
+Makes the table serializable, through `$table::Extraction`s. Requires `#[row_derive(Clone)]`.
+The table is included in `Universe::save_snapshot`, which saves every `#[save]` table and property of some domains.
+
+## `#[journal]`
+Keeps a log of the rows that were created, edited, or deleted,
+so that `Universe::save_delta` can save just those rows, and a list of the deleted ones.
+`Universe::apply_delta` puts them back at the same `RowId`s.
+If saving a delta fails, its rows are kept for the next one.
+Edits are noticed the same way as with `#[track_edits]`, so every column is wrapped in an `EditTracked`;
+`table.mark_edited(row)` records anything else.
+Requires `#[save]` and `#[kind = "consistent"]`.
+
+## `#[binary]`
+Adds `Extraction::write_binary` and `Extraction::read_binary`, which use v11's own columnar format
+instead of serde. Every column must be a `VecCol` or `MappedCol` of `Pod` elements, or a `BoolCol`
+(any of which may be `#[index]`ed).
+Requires `#[row_derive(Clone)]`, and isn't supported by indirect tables.
+
+`unsafe { $table::open_mapped(universe, path) }` memory-maps such a file into the empty table and returns a `Read` lock.
+The file must not be changed while the table uses it, and it must hold every row of a table without holes.
+`MappedCol`s read their elements straight from the file, until they are first modified.
+
+## `#[migrate]`
+Lets a `#[save]` table load extractions from older versions of its schema.
+Deserialize the file into a `RawExtraction<V>`, where `V` is the format's value type (like `serde_json::Value`),
+and turn it into an `Extraction` with `$table::Extraction::from_raw(raw)`.
+If its `schema` or `columns` are out of date, it is first passed to your `impl Migrate<V> for $table::Row`,
+which can rename, drop, add, and convert columns until they match `Extraction::current_columns()`,
+and then bump the `schema`.
+
 ## `#[add_tracker = "expression"]`
 Register a user tracker automatically when the table is initialized using the given expression.
 For example, `#[add_tracker = "BirdWatch"]`.
@@ -153,6 +235,13 @@ Can be repeated.
 The trackers from `#[foreign]` and `#[foreign_auto]` take care of themselves;
 using this on the trackers they define would duplicate it.
 
+## `#[index(name = "by_owner_kind", cols = "owner, kind")]`
+Creates a `BTreeMap` index keyed on a tuple of 2 to 4 columns, found at `table.by_owner_kind()`.
+Search it with `find((owner, kind))`, `range(lo, hi)`, or by a leading part of the key with `prefix((owner,))`.
+The elements must be `Ord + Copy`, and the columns can't be changed, not even with `set`.
+
+Can be repeated.
+
 # Column Attributes
 
 ```no_compile
@@ -176,10 +265,40 @@ This requires `#[index]` or `#[sort_key]` on the local column.
 
 ## `#[index]`
 Creates an index of the column, using a `BTreeMap`.
-Indexed elements are immutable, and are duplicated.
+Indexed elements are duplicated, and can't be borrowed mutably.
+Search it with `table.column.find(element)` or `table.column.range(lo, hi)`.
+Change an element with `table.column.set(row, element)`,
+or `table.column.modify(row, |element| …)`, which updates the index once the closure returns.
+A `#[sort_key]` can't be changed this way, since that would unsort the table.
+
+`#[index = "hash"]` uses a `HashMap` instead. The element only needs `Hash + Eq`,
+and `find` doesn't have to search a tree, but there is no `range`.
+(`#[index = "btree"]` is the same as `#[index]`.)
+
+## `#[unique]`
+An indexed column whose values can't repeat. (Combine it with `#[index = "hash"]` to use a `HashMap`.)
+`push`, `push_all`, `push_array`, and `merge` panic if a row would repeat a value;
+`try_push`, `try_push_all`, `try_push_array`, and `try_merge` return `Result<_, UniqueViolation>` instead.
+`table.get_by_$COL(element)` returns the row with that value.
+The column can't be changed with `set` or `modify`, and there is no `visit`.
+Only append, consistent, and sorted tables support this.
+
+Functions that add several rows check all of them before adding any, so a duplicate leaves the table
+as it was. `restore_extract` does the same, and returns `RestoreError::Unique`.
+Rows that have been deleted from a consistent table don't count, even before the table is flushed.
+
+## `#[track_edits]`
+Remembers which rows had the column changed in place: mutably indexed, `set`, or `modify`d
+(or given new contents by the table, like when a consistent table reuses a deleted row's slot).
+`table.edited_rows()` lists the rows that had any such column changed, and `table.take_edits()` also forgets them.
+`flush(universe, event::MODIFY)`, or any other event that neither creates nor removes rows,
+takes the edits and delivers them to the table's `Tracker`s.
+Requires `#[kind = "consistent"]`.
 
 ## `#[sort_key]`
 Use the element's comparision order to derive `Ord` for `RowRef`.
+The column can then be binary searched with `table.find_sorted(&key)`, `table.range_sorted(lo..hi)`,
+`table.lower_bound(&key)`, and `table.upper_bound(&key)`, which return `RowRange`s or `RowId`s.
 
 # Debugging Macro Output
 If there is an error in the macro's output, it will give opaque errors and thus be impossible to debug... *UNTIL NOW*!
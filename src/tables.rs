@@ -1,7 +1,8 @@
 use std::any::Any;
 use std::sync::*;
 use std::fmt;
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
+use std::collections::BTreeMap;
 
 use serde::ser::{Serialize};
 use serde::de::{DeserializeOwned};
@@ -9,9 +10,11 @@ use serde::de::{DeserializeOwned};
 pub use v11_macros::*;
 
 use crate::Universe;
+use crate::error::Error;
 use crate::intern;
 use crate::domain::{DomainName, DomainId, MaybeDomain};
 use crate::columns::AnyCol;
+use crate::handle::GenericHandle;
 
 impl Universe {
     #[doc(hidden)]
@@ -21,12 +24,33 @@ impl Universe {
         }
         panic!("Request for table {} in unknown domain #{}", name, domain_id.0);
     }
+
+    /// Like `get_generic_table`, but returns an error if the domain or table isn't in this `Universe`.
+    pub fn try_get_generic_table(&self, domain_id: DomainId, name: &str) -> Result<&RwLock<GenericTable>, Error> {
+        match self.domains.get(domain_id.0) {
+            Some(&MaybeDomain::Domain(ref domain)) => domain.try_get_generic_table(name),
+            _ => Err(Error::UnknownDomain(format!("#{}", domain_id.0))),
+        }
+    }
 }
 
 
 /// A function that creates a new `GenericColumn`.
 pub type Prototyper = fn() -> GenericColumn;
 
+/// A function that deserializes a table's `Extraction` and restores it into the `Universe`.
+pub type Restorer = fn(&Universe, &mut ::erased_serde::Deserializer, Event) -> Result<(), String>;
+
+/// A function that takes the journal of a `#[journal]` table, and returns its `$table::Delta`.
+pub type DeltaSaver = fn(&Universe) -> Box<PendingDelta>;
+
+/// A `$table::Delta` that has been taken out of its table's journal, but not saved yet.
+pub trait PendingDelta {
+    fn as_serialize(&self) -> &::erased_serde::Serialize;
+    /// Puts the changes back into the journal, because the delta couldn't be saved.
+    fn put_back(&self, universe: &Universe);
+}
+
 
 use crate::tracking;
 pub trait TTable: ::mopa::Any + Send + Sync {
@@ -55,6 +79,20 @@ pub trait TTable: ::mopa::Any + Send + Sync {
         selection: tracking::SelectAny,
     ) -> Option<Box<::erased_serde::Serialize>>;
 
+    /// Returns a function that deserializes a `$table::Extraction` and restores it,
+    /// if the table supports serialization.
+    fn get_restorer(&self) -> Option<Restorer>;
+
+    /// Returns functions that save and apply a `$table::Delta`, if the table has `#[journal]`.
+    fn get_delta_functions(&self) -> Option<(DeltaSaver, Restorer)>;
+
+    /// Returns a function that finishes a flush that was interrupted by a panic,
+    /// if the table is consistent.
+    fn get_repairer(&self) -> Option<fn(&Universe)>;
+
+    /// Returns `true` if the row is in the free-list.
+    fn is_free(&self, i: usize) -> bool;
+
     fn generic_select(
         &self,
         universe: &Universe,
@@ -74,6 +112,9 @@ pub struct GenericTable {
     init_fns: Vec<fn(&Universe)>,
     pub guarantee: Guarantee,
     pub table: Box<TTable>,
+    /// Set by `Universe::repair`, and cleared by the next panic. The lock stays poisoned,
+    /// so this says whether to believe it.
+    pub(crate) repaired: bool,
 }
 #[doc(hidden)]
 #[derive(Default, Clone)]
@@ -97,9 +138,16 @@ impl GenericTable {
             guarantee,
 
             table: Box::new(table),
+            repaired: false,
         }
     }
 
+    /// Marks a table whose lock was poisoned as usable again, or as poisoned by a new panic.
+    #[doc(hidden)]
+    pub fn set_repaired(&mut self, repaired: bool) {
+        self.repaired = repaired;
+    }
+
     pub fn add_init(&mut self, init: fn(&Universe)) {
         self.init_fns.push(init);
     }
@@ -124,6 +172,7 @@ impl GenericTable {
             guarantee: self.guarantee.clone(),
 
             table: self.table.prototype(),
+            repaired: false,
         }
     }
 
@@ -146,17 +195,7 @@ impl GenericTable {
     ) -> &C
     where C: Any + Send + Sync
     {
-        let c = self.columns.iter().find(|c| c.meta.name == name).unwrap_or_else(|| {
-            panic!("Table {} doesn't have a {} column.", self.name, name);
-        });
-        if c.meta.stored_type_name != type_name { panic!("Column {}/{} has datatype {:?}, not {:?}", self.name, name, c.meta.stored_type_name, type_name); }
-        let cdata: &AnyCol = &*c.data;
-        match cdata.downcast_ref() {
-            Some(ret) => ret,
-            None => {
-                panic!("Column {}/{}: type conversion from {:?} to {:?} failed", self.name, name, c.meta.stored_type_name, type_name);
-            },
-        }
+        self.try_get_column(name, type_name).unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn get_column_mut<C>(
@@ -166,18 +205,61 @@ impl GenericTable {
     ) -> &mut C
     where C: Any + Send + Sync
     {
-        let my_name = &self.name;
-        let c = self.columns.iter_mut().find(|c| c.meta.name == name).unwrap_or_else(|| {
-            panic!("Table {} doesn't have a {} column.", my_name, name);
-        });
-        if c.meta.stored_type_name != type_name { panic!("Column {}/{} has datatype {:?}, not {:?}", self.name, name, c.meta.stored_type_name, type_name); }
-        let cdata: &mut AnyCol = &mut *c.data;
-        match cdata.downcast_mut() {
-            Some(ret) => ret,
-            None => {
-                panic!("Column {}/{}: type conversion from {:?} to {:?} failed", self.name, name, c.meta.stored_type_name, type_name);
-            },
+        self.try_get_column_mut(name, type_name).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn find_column(&self, name: &str, type_name: &'static str) -> Result<usize, Error> {
+        let i = self.columns.iter().position(|c| c.meta.name == name).ok_or_else(|| Error::UnknownColumn {
+            table: self.name.0.to_owned(),
+            column: name.to_owned(),
+        })?;
+        let stored = &self.columns[i].meta.stored_type_name;
+        if stored != type_name {
+            return Err(Error::ColumnType {
+                table: self.name.0.to_owned(),
+                column: name.to_owned(),
+                stored: stored.to_string(),
+                requested: type_name.to_owned(),
+            });
         }
+        Ok(i)
+    }
+
+    fn type_error(&self, i: usize, type_name: &'static str) -> Error {
+        let meta = &self.columns[i].meta;
+        Error::ColumnType {
+            table: self.name.0.to_owned(),
+            column: meta.name.to_string(),
+            stored: meta.stored_type_name.to_string(),
+            requested: type_name.to_owned(),
+        }
+    }
+
+    /// Like `get_column`, but returns an error if the column is missing or has another type.
+    pub fn try_get_column<C>(
+        &self,
+        name: &str,
+        type_name: &'static str,
+    ) -> Result<&C, Error>
+    where C: Any + Send + Sync
+    {
+        let i = self.find_column(name, type_name)?;
+        let cdata: &AnyCol = &*self.columns[i].data;
+        cdata.downcast_ref().ok_or_else(|| self.type_error(i, type_name))
+    }
+
+    /// Like `get_column_mut`, but returns an error if the column is missing or has another type.
+    pub fn try_get_column_mut<C>(
+        &mut self,
+        name: &str,
+        type_name: &'static str,
+    ) -> Result<&mut C, Error>
+    where C: Any + Send + Sync
+    {
+        let i = self.find_column(name, type_name)?;
+        let err = self.type_error(i, type_name);
+        let cdata: &mut AnyCol = &mut *self.columns[i].data;
+        cdata.downcast_mut().ok_or(err)
     }
 
     pub fn info(&self) -> String {
@@ -189,30 +271,40 @@ impl GenericTable {
     }
 
     pub fn register(self) {
-        use crate::domain::{GlobalProperties, clone_globals};
+        self.try_register().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Like `register`, but returns an error if the domain is missing or locked,
+    /// or if a different table was registered with the same name.
+    pub fn try_register(self) -> Result<(), Error> {
+        use crate::domain::{GlobalProperties, clone_globals, try_write_globals};
         use std::collections::hash_map::Entry;
         let globals = clone_globals();
-        let pmap: &mut GlobalProperties = &mut *globals.write().unwrap();
-        match pmap.domains.get_mut(&self.domain) {
-            None => panic!("Table {:?} registered before its domain {:?}", self.name, self.domain),
-            Some(info) => {
-                if super::domain::check_lock() && info.locked() {
-                    panic!("Adding {}/{} to a locked domain\n", self.domain, self.name);
-                }
-                match info.tables.entry(self.name) {
-                    Entry::Vacant(entry) => {
-                        info.tables_registration_order.push(self.name);
-                        entry.insert(self);
-                    },
-                    Entry::Occupied(entry) => {
-                        let entry = entry.get();
-                        if !self.equivalent(entry) {
-                            panic!("Tried to register {:?} on top of an existing table with different structure, {:?}", self, entry);
-                        }
-                    },
+        let pmap: &mut GlobalProperties = &mut *try_write_globals(&globals)?;
+        let info = pmap.domains.get_mut(&self.domain).ok_or_else(|| Error::UnknownDomain(self.domain.0.to_owned()))?;
+        if super::domain::check_lock() && info.locked() {
+            return Err(Error::DomainLocked {
+                domain: self.domain.0.to_owned(),
+                name: self.name.0.to_owned(),
+            });
+        }
+        match info.tables.entry(self.name) {
+            Entry::Vacant(entry) => {
+                info.tables_registration_order.push(self.name);
+                entry.insert(self);
+            },
+            Entry::Occupied(entry) => {
+                if !self.equivalent(entry.get()) {
+                    return Err(Error::TableMismatch {
+                        domain: self.domain.0.to_owned(),
+                        table: self.name.0.to_owned(),
+                        new: format!("{:?}", self),
+                        existing: format!("{:?}", entry.get()),
+                    });
                 }
-            }
+            },
         }
+        Ok(())
     }
 
     fn equivalent(&self, other: &GenericTable) -> bool {
@@ -239,6 +331,7 @@ pub struct GenericColumn {
     // "FIXME: PBox here is lame." -- What? No it isn't.
     pub data: Box<AnyCol>,
     pub prototyper: Prototyper,
+    pub reflection: crate::reflect::Reflection,
 }
 impl fmt::Debug for GenericColumn {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -264,6 +357,10 @@ impl fmt::Display for TableName {
         write!(f, "{}", self.0)
     }
 }
+/// Tables can be looked up by names that aren't `'static`.
+impl Borrow<str> for TableName {
+    fn borrow(&self) -> &str { self.0 }
+}
 
 // FIXME: Rename. `TableRowId`? Difficult to say. `TableIdent`?
 #[doc(hidden)]
@@ -279,6 +376,8 @@ pub trait GetTableName: 'static + Send + Sync {
     fn get_domain() -> DomainName;
     fn get_name() -> TableName;
     fn get_guarantee() -> Guarantee;
+    /// Rows may be moved by deletions, as in `#[kind = "bag"]`; see `Tracker::moved`.
+    fn get_remapping() -> bool { false }
     fn get_generic_table(_: &Universe) -> &RwLock<GenericTable>;
     fn new_generic_table() -> GenericTable;
 }
@@ -292,12 +391,125 @@ pub trait SerialExtraction: GetTableName {
     fn restore(universe: &Universe, extraction: Self::Extraction, event: Event) -> Result<(), &'static str>;
 }
 
+use crate::map_index::UniqueViolation;
+/// Why `restore_extract` failed on a table with `#[unique]` columns. Nothing is restored if it
+/// fails.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestoreError {
+    /// The extraction is for a different table, or a different version of it.
+    Mismatch(&'static str),
+    /// A row has the same value as another in a `#[unique]` column.
+    Unique(UniqueViolation),
+}
+impl From<&'static str> for RestoreError {
+    fn from(msg: &'static str) -> Self { RestoreError::Mismatch(msg) }
+}
+impl From<UniqueViolation> for RestoreError {
+    fn from(e: UniqueViolation) -> Self { RestoreError::Unique(e) }
+}
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RestoreError::Mismatch(msg) => write!(f, "{}", msg),
+            RestoreError::Unique(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+impl ::std::error::Error for RestoreError {}
+
+/// A `$table::Extraction` whose columns haven't been deserialized yet, so that a table with
+/// `#[migrate]` can bring it up to date. `V` is a self-describing value of the serialization
+/// format, such as `serde_json::Value`.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+pub struct RawExtraction<V> {
+    pub extraction_fmt: u32,
+    pub domain: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+    pub schema: u32,
+    pub columns: Vec<ColumnMeta>,
+    pub selection: Vec<u64>,
+    pub data: BTreeMap<String, V>,
+}
+impl<V> RawExtraction<V> {
+    /// The number of rows.
+    pub fn len(&self) -> usize { self.selection.len() }
+    pub fn is_empty(&self) -> bool { self.selection.is_empty() }
+
+    fn position(&self, name: &str) -> Result<usize, String> {
+        self.columns
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| format!("no column named {:?}", name))
+    }
+
+    pub fn column(&self, name: &str) -> Option<(&ColumnMeta, &V)> {
+        let meta = self.columns.iter().find(|c| c.name == name)?;
+        Some((meta, self.data.get(name)?))
+    }
+
+    pub fn rename_column(&mut self, from: &str, to: &'static str) -> Result<(), String> {
+        let i = self.position(from)?;
+        if self.position(to).is_ok() {
+            return Err(format!("there is already a column named {:?}", to));
+        }
+        self.columns[i].name = Cow::Borrowed(to);
+        if let Some(data) = self.data.remove(from) {
+            self.data.insert(to.to_string(), data);
+        }
+        Ok(())
+    }
+
+    /// Removes a column, returning its data.
+    pub fn drop_column(&mut self, name: &str) -> Result<(ColumnMeta, V), String> {
+        let i = self.position(name)?;
+        let meta = self.columns.remove(i);
+        let data = self.data
+            .remove(name)
+            .ok_or_else(|| format!("column {:?} has no data", name))?;
+        Ok((meta, data))
+    }
+
+    /// Adds a column. `data` should have `self.len()` elements.
+    pub fn add_column(&mut self, meta: ColumnMeta, data: V) -> Result<(), String> {
+        if self.position(&meta.name).is_ok() {
+            return Err(format!("there is already a column named {:?}", meta.name));
+        }
+        self.data.insert(meta.name.to_string(), data);
+        self.columns.push(meta);
+        Ok(())
+    }
+
+    /// Replaces a column's data. The function should also update the `ColumnMeta`'s `version`,
+    /// and `stored_type_name` if it has changed.
+    pub fn convert_column<F>(&mut self, name: &str, f: F) -> Result<(), String>
+    where F: FnOnce(&mut ColumnMeta, V) -> Result<V, String>
+    {
+        let i = self.position(name)?;
+        let data = self.data
+            .remove(name)
+            .ok_or_else(|| format!("column {:?} has no data", name))?;
+        let data = f(&mut self.columns[i], data)?;
+        self.data.insert(self.columns[i].name.to_string(), data);
+        Ok(())
+    }
+}
+
+/// Brings an outdated `RawExtraction` up to date, for tables with `#[migrate]`.
+/// It is only called if `schema` or `columns` differ from the table's own,
+/// and should leave them equal to the table's own (the order of the columns doesn't matter).
+pub trait Migrate<V>: SerialExtraction {
+    fn migrate(raw: &mut RawExtraction<V>) -> Result<(), String>;
+}
+
 #[doc(hidden)]
 pub trait LockedTable: Sized {
     type Row: GetTableName;
     fn len(&self) -> usize;
     fn is_deleted(&self, _idx: GenericRowId<Self::Row>) -> bool { false }
     fn delete_row(&mut self, _idx: GenericRowId<Self::Row>) { unimplemented!("LockedTable::delete") }
+    /// Only `#[kind = "indirect"]` tables have handles.
+    fn resolve_handle(&self, _handle: GenericHandle<Self::Row>) -> Option<GenericRowId<Self::Row>> { None }
 }
 
 // FIXME: Why?
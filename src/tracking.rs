@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use crate::Universe;
 use crate::tables::GetTableName;
 use crate::index::GenericRowId;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 /// Everything you need to define a [`Tracker`].
 pub mod prelude {
@@ -165,7 +165,8 @@ where
 use crate::event::{self, Event};
 
 /// `Tracker`s are notified of structural changes to tables. This requires the 'consistent'
-/// guarantee on the foreign table, which is provided by `#[kind = "consistent"]`.
+/// guarantee on the foreign table, which is provided by `#[kind = "consistent"]`, or the
+/// 'remapping' guarantee, which is provided by `#[kind = "bag"]`.
 /// You can use `#[foreign_auto]` to derive an implementation.
 // FIXME: https://github.com/rust-lang/rust/issues/29628
 // https://doc.rust-lang.org/beta/unstable-book/language-features/on-unimplemented.html
@@ -208,6 +209,11 @@ pub trait Tracker: 'static + Send + Sync {
     /// able to access their contents without a panic), but will become actually-deleted after the
     /// flush completes.
     ///
+    /// Tables with the 'remapping' guarantee are the exception: a bag removes its rows as soon as
+    /// they're deleted, moving other rows into their place. So the deleted rows are given as they
+    /// were numbered at the previous flush, and must not be looked up in the foreign table.
+    /// Where the other rows went is given to `moved` afterwards.
+    ///
     /// You may lock the foreign table for editing, but making structural changes to it
     /// will likely cause trouble.
     fn handle(
@@ -217,6 +223,15 @@ pub trait Tracker: 'static + Send + Sync {
         rows: SelectRows<Self::Foreign>,
         handler: &dyn event::Function,
     );
+
+    /// Foreign rows were moved to fill the holes left by deleted rows, as `(old, new)` pairs.
+    /// This is called after `handle`, during the flush of a table with the 'remapping' guarantee,
+    /// even if the event wasn't `consider`ed.
+    fn moved(
+        &self,
+        _universe: &Universe,
+        _moves: &[(GenericRowId<Self::Foreign>, GenericRowId<Self::Foreign>)],
+    ) {}
 }
 
 #[doc(hidden)]
@@ -227,6 +242,36 @@ pub struct Flush<T: GetTableName> {
     trackers: Vec<Box<Tracker<Foreign=T>>>,
     identity_remapping: bool,
     pub remapped: HashMap<GenericRowId<T>, GenericRowId<T>>,
+    /// Set when a tracker panics, so that the flush can be finished by `Universe::repair`.
+    interrupted: Mutex<Option<Interrupted<T>>>,
+}
+
+/// A flush whose trackers didn't all finish.
+#[doc(hidden)]
+pub struct Interrupted<T: GetTableName> {
+    pub event: Event,
+    rows: SelectOwned<T>,
+    /// The tracker that panicked. The ones before it have already seen the rows.
+    next_tracker: usize,
+    include_self: bool,
+}
+
+/// Records the flush in `Flush::interrupted` if it is dropped by a panic.
+struct Unwinding<'a, T: GetTableName + 'a> {
+    flush: &'a Flush<T>,
+    pending: Option<Interrupted<T>>,
+}
+impl<'a, T: GetTableName> Drop for Unwinding<'a, T> {
+    fn drop(&mut self) {
+        // The flush takes `pending` back when it finishes, so this only happens while unwinding.
+        if let Some(pending) = self.pending.take() {
+            let mut interrupted = match self.flush.interrupted.lock() {
+                Ok(i) => i,
+                Err(poison) => poison.into_inner(),
+            };
+            *interrupted = Some(pending);
+        }
+    }
 }
 use std::fmt;
 impl<T: GetTableName> fmt::Debug for Flush<T> {
@@ -240,6 +285,7 @@ impl<T: GetTableName> Default for Flush<T> {
             trackers: Default::default(),
             identity_remapping: false,
             remapped: HashMap::new(),
+            interrupted: Mutex::new(None),
         }
     }
 }
@@ -251,40 +297,77 @@ impl<T: GetTableName> Flush<T> {
         event: Event,
         pushed: bool,
         delete: bool,
-        mut select: SelectOwned<T>,
+        select: SelectOwned<T>,
         include_self: bool,
     ) -> SelectOwned<T> {
         if (pushed && delete) || (event.is_removal && event.is_creation) {
             panic!("Can't interleave pushes & deletes");
         }
+        self.run(universe, Interrupted {
+            event,
+            rows: select,
+            next_tracker: 0,
+            include_self,
+        })
+    }
+
+    /// Finishes a flush that was interrupted by a panic,
+    /// starting with the tracker that panicked.
+    pub fn resume(&self, universe: &Universe, interrupted: Interrupted<T>) -> SelectOwned<T> {
+        self.run(universe, interrupted)
+    }
+
+    fn run(&self, universe: &Universe, pending: Interrupted<T>) -> SelectOwned<T> {
+        let event = pending.event;
+        let include_self = pending.include_self;
+        let first = pending.next_tracker;
+        let mut unwinding = Unwinding {
+            flush: self,
+            pending: Some(pending),
+        };
         // either way, send to trackers first
         let function = universe.event_handlers.get(event);
-        let mut sorted = select.is_all();
-        {
-            for tracker in &self.trackers {
-                if !tracker.consider(event) { continue; }
+        let mut sorted = unwinding.pending.as_ref().unwrap().rows.is_all();
+        let moves: Vec<_> = self.remapped.iter().map(|(&o, &n)| (o, n)).collect();
+        for (i, tracker) in self.trackers.iter().enumerate().skip(first) {
+            let pending = unwinding.pending.as_mut().unwrap();
+            pending.next_tracker = i;
+            if tracker.consider(event) {
                 if !sorted && tracker.sort() {
                     sorted = true;
-                    select.as_mut().map(|s| s.sort());
+                    pending.rows.sort();
                 }
                 tracker.handle(
                     universe,
                     event,
-                    select.as_slice(),
+                    pending.rows.as_slice(),
                     function,
                 );
             }
+            if !moves.is_empty() {
+                tracker.moved(universe, &moves);
+            }
         }
         if include_self {
+            let pending = unwinding.pending.as_mut().unwrap();
+            pending.next_tracker = self.trackers.len();
             let gt = T::get_generic_table(universe);
             if !sorted && function.needs_sort(gt) {
-                select.sort();
+                pending.rows.sort();
             }
-            let select = select.as_slice();
+            let select = pending.rows.as_slice();
             let select = select.as_any();
             function.handle(universe, gt, event, select);
         }
-        select
+        unwinding.pending.take().unwrap().rows
+    }
+
+    /// Takes the flush that was interrupted by a panic, if there was one.
+    pub fn take_interrupted(&self) -> Option<Interrupted<T>> {
+        match self.interrupted.lock() {
+            Ok(mut i) => i.take(),
+            Err(poison) => poison.into_inner().take(),
+        }
     }
     pub fn set_remapping(&mut self, remap: &[(GenericRowId<T>, GenericRowId<T>)]) {
         if self.identity_remapping {
@@ -313,7 +396,7 @@ impl<T: GetTableName> Flush<T> {
 
 
     pub fn register_tracker<R: Tracker<Foreign=T>>(&mut self, tracker: R) {
-        if !R::Foreign::get_guarantee().consistent {
+        if !(R::Foreign::get_guarantee().consistent || R::Foreign::get_remapping()) {
             panic!("Tried to add tracker to inconsistent table, {}/{}",
                    R::Foreign::get_domain(), R::Foreign::get_name());
         }
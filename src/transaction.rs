@@ -0,0 +1,54 @@
+//! Undoable changes to tables, using the `Transaction` type generated for consistent tables
+//! with `#[row_derive(Clone)]`.
+//!
+//! A table's `Transaction` wraps its `Write` lock, and records how to reverse each
+//! `push`, `delete`, `replace`, and `edit`. `Transactions` groups several of them, so that they
+//! can be committed or rolled back together.
+
+use crate::Universe;
+use crate::event::Event;
+
+/// A table's `Transaction`, with the type of the table erased.
+pub trait TableTransaction<'u> {
+    /// Reverses the latest change. Returns `false` if there was nothing to undo.
+    fn undo(&mut self) -> bool;
+    /// Repeats the latest undone change. Returns `false` if there was nothing to redo.
+    fn redo(&mut self) -> bool;
+    /// Flushes the changes, and releases the lock.
+    fn commit_boxed(self: Box<Self>, universe: &'u Universe, event: Event);
+    /// Undoes every change, and releases the lock.
+    /// The lock must not have had unflushed changes before the transaction started.
+    fn rollback_boxed(self: Box<Self>);
+}
+
+/// Several `Transaction`s that are committed or rolled back together.
+#[derive(Default)]
+pub struct Transactions<'u> {
+    tables: Vec<Box<TableTransaction<'u> + 'u>>,
+}
+impl<'u> Transactions<'u> {
+    pub fn new() -> Self {
+        Transactions { tables: Vec::new() }
+    }
+
+    pub fn add<T: TableTransaction<'u> + 'u>(&mut self, transaction: T) {
+        self.tables.push(Box::new(transaction));
+    }
+
+    pub fn len(&self) -> usize { self.tables.len() }
+    pub fn is_empty(&self) -> bool { self.tables.is_empty() }
+
+    /// Flushes each table, in the order they were added.
+    pub fn commit(self, universe: &'u Universe, event: Event) {
+        for table in self.tables {
+            table.commit_boxed(universe, event);
+        }
+    }
+
+    /// Undoes every change, in the reverse of the order the tables were added.
+    pub fn rollback(self) {
+        for table in self.tables.into_iter().rev() {
+            table.rollback_boxed();
+        }
+    }
+}
@@ -17,13 +17,45 @@ table! {
     }
 }
 
+table! {
+    #[kind = "bag"]
+    #[row_derive(Debug, Clone)]
+    [TEST/particles] {
+        x: [i32; VecCol<i32>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_derive(Debug)]
+    [TEST/sparks] {
+        #[foreign_auto]
+        #[index]
+        particle: [particles::RowId; VecCol<particles::RowId>],
+        x: [i32; VecCol<i32>],
+    }
+}
+
 use v11::Universe;
+use v11::event;
+use v11::index::Checkable;
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        hello_there::register();
+        particles::register();
+        sparks::register();
+    });
+    Universe::new(&[TEST])
+}
 
 #[test]
 fn compiles() {
-    TEST.register();
-    hello_there::register();
-    let universe = &Universe::new(&[TEST]);
+    let universe = &make_universe();
     {
         let mut ht = hello_there::write(universe);
         ht.push(hello_there::Row {
@@ -78,3 +110,106 @@ fn compiles() {
         println!("...is empty, yes?");
     }
 }
+
+#[test]
+fn swap_remove() {
+    let universe = &make_universe();
+    let mut ht = hello_there::write(universe);
+    for foo in 0..5 {
+        ht.push(hello_there::Row { foo });
+    }
+    ht.delete(hello_there::RowId::from_usize(1));
+    assert_eq!(ht.len(), 4);
+    assert_eq!(ht.foo[hello_there::RowId::from_usize(1)], 4);
+    assert_eq!(ht.next_pushed(), hello_there::RowId::from_usize(4));
+    let id = ht.push(hello_there::Row { foo: 5 });
+    assert_eq!(id, hello_there::RowId::from_usize(4));
+}
+
+#[test]
+fn tracked() {
+    let universe = &make_universe();
+    {
+        let mut particles = particles::write(universe);
+        for x in 0..5 {
+            particles.push(particles::Row { x });
+        }
+        particles.flush(universe, event::CREATE);
+    }
+    {
+        let particles = particles::read(universe);
+        let mut sparks = sparks::write(universe);
+        for particle in particles.iter() {
+            sparks.push(sparks::Row {
+                particle: particle.uncheck(),
+                x: particles.x[particle],
+            });
+        }
+        sparks.flush(universe, event::CREATE);
+    }
+    {
+        let mut particles = particles::write(universe);
+        // Row 4 moves into 1, and then row 3 moves into 0.
+        particles.delete(particles::RowId::from_usize(1));
+        particles.delete(particles::RowId::from_usize(0));
+        particles.flush(universe, event::DELETE);
+    }
+    let particles = particles::read(universe);
+    let sparks = sparks::read(universe);
+    assert_eq!(particles.len(), 3);
+    let mut n = 0;
+    for spark in sparks.iter() {
+        let particle = sparks.particle[spark];
+        assert_eq!(particles.x[particle], sparks.x[spark]);
+        n += 1;
+    }
+    assert_eq!(n, 3);
+}
+
+#[test]
+fn live_flush_tracked() {
+    let universe = &make_universe();
+    {
+        let mut particles = particles::write(universe);
+        for x in 0..3 {
+            particles.push(particles::Row { x });
+        }
+        particles.flush(universe, event::CREATE);
+        let mut sparks = sparks::write(universe);
+        for x in 0..3 {
+            sparks.push(sparks::Row { particle: particles::RowId::from_usize(x as usize), x });
+        }
+        sparks.flush(universe, event::CREATE);
+    }
+    let mut particles = particles::write(universe);
+    particles.delete(particles::RowId::from_usize(0));
+    particles.live_flush(universe, event::DELETE);
+    assert_eq!(particles.len(), 2);
+    let sparks = sparks::read(universe);
+    let mut n = 0;
+    for spark in sparks.iter() {
+        assert_eq!(particles.x[sparks.particle[spark]], sparks.x[spark]);
+        n += 1;
+    }
+    assert_eq!(n, 2);
+}
+
+#[test]
+fn panic_with_moved_rows() {
+    use std::panic::{self, AssertUnwindSafe};
+    let universe = &make_universe();
+    {
+        let mut particles = particles::write(universe);
+        for x in 0..3 {
+            particles.push(particles::Row { x });
+        }
+        particles.flush(universe, event::CREATE);
+    }
+    // Dropping the unflushed lock while unwinding mustn't panic again, which would abort.
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut particles = particles::write(universe);
+        particles.delete(particles::RowId::from_usize(0));
+        panic!("before flushing");
+    }));
+    assert!(got.is_err());
+}
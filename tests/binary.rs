@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        cells::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_id = "u16"]
+    #[row_derive(Clone)]
+    #[binary]
+    [TEST/cells] {
+        height: [f64; VecCol<f64>],
+        #[index]
+        biome: [u16; VecCol<u16>],
+        wet: [bool; BoolCol],
+    }
+}
+
+#[test]
+fn round_trip() {
+    let universe = &make_universe();
+    {
+        let mut cells = cells::write(universe);
+        for i in 0..100 {
+            cells.push(cells::Row { height: i as f64 * 0.5, biome: i % 7, wet: i % 3 == 0 });
+        }
+        cells.flush(universe, event::CREATE);
+        let mut cells = cells::write(universe);
+        cells.delete(cells::RowId::new(5));
+        cells.flush(universe, event::DELETE);
+    }
+    let mut file = vec![];
+    cells::read(universe)
+        .extract_selection(v11::tracking::Select::All)
+        .write_binary(&mut file)
+        .unwrap();
+
+    let extraction = cells::Extraction::read_binary(&mut &file[..]).unwrap();
+    assert_eq!(extraction.columns, cells::Extraction::current_columns());
+    let alternia = &make_universe();
+    cells::write(alternia).restore_extract(alternia, extraction, event::CREATE).unwrap();
+    let cells = cells::read(alternia);
+    assert_eq!(cells.len(), 99);
+    let row = cells::RowId::new(5);
+    assert_eq!((cells.height[row], cells.biome[row], cells.wet[row]), (3.0, 6, true));
+    assert_eq!(cells.biome.find(6).count(), 14);
+}
+
+#[test]
+fn truncated() {
+    let universe = &make_universe();
+    {
+        let mut cells = cells::write(universe);
+        cells.push(cells::Row { height: 1.0, biome: 2, wet: true });
+        cells.flush(universe, event::CREATE);
+    }
+    let mut file = vec![];
+    cells::read(universe)
+        .extract_selection(v11::tracking::Select::All)
+        .write_binary(&mut file)
+        .unwrap();
+    file.pop();
+    assert!(cells::Extraction::read_binary(&mut &file[..]).is_err());
+}
+
+#[test]
+fn bad_length() {
+    let universe = &make_universe();
+    let mut file = vec![];
+    cells::read(universe)
+        .extract_selection(v11::tracking::Select::All)
+        .write_binary(&mut file)
+        .unwrap();
+    // The length of the domain's name, after the magic number, byte order, and extraction_fmt.
+    for &len in &[::std::u64::MAX, 1 << 40] {
+        let mut file = file.clone();
+        file[12..20].copy_from_slice(&len.to_ne_bytes());
+        assert!(cells::Extraction::read_binary(&mut &file[..]).is_err());
+    }
+}
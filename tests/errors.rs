@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::{Error, Universe};
+
+domain! { TEST }
+domain! { NOWHERE }
+domain! { SHELF }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        HERE.register();
+        books::register();
+    });
+    Universe::new(&[TEST])
+}
+
+property! { static TEST/HERE: u32 = 7; }
+property! { static TEST/MISSING: u32 }
+
+table! {
+    #[kind = "append"]
+    [TEST/books] {
+        title: [&'static str; VecCol<&'static str>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/late] {
+        x: [u8; VecCol<u8>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [NOWHERE/lost] {
+        x: [u8; VecCol<u8>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [SHELF/shelves] {
+        x: [u8; VecCol<u8>],
+    }
+}
+
+mod rebuilt {
+    use super::SHELF;
+    table! {
+        #[kind = "append"]
+        pub [SHELF/shelves] {
+            x: [u16; VecCol<u16>],
+        }
+    }
+}
+
+#[test]
+fn tables() {
+    let universe = &make_universe();
+    assert!(universe.try_get_generic_table(TEST.get_id(), "books").is_ok());
+    let name = format!("no{}", "pe");
+    match universe.try_get_generic_table(TEST.get_id(), &name) {
+        Err(Error::UnknownTable { ref table, ref known, .. }) => {
+            assert_eq!(table, "nope");
+            assert_eq!(known, &["books"]);
+        },
+        _ => panic!("found a table that doesn't exist"),
+    }
+    let books = universe.try_get_generic_table(TEST.get_id(), "books").unwrap();
+    let books = books.read().unwrap();
+    match books.try_get_column::<u8>("author", "u8") {
+        Err(Error::UnknownColumn { ref column, .. }) => assert_eq!(column, "author"),
+        _ => panic!("found a column that doesn't exist"),
+    }
+    match books.try_get_column::<u8>("title", "u8") {
+        Err(Error::ColumnType { ref requested, .. }) => assert_eq!(requested, "u8"),
+        _ => panic!("column has the wrong type"),
+    }
+}
+
+#[test]
+fn registration() {
+    let _universe = make_universe();
+    match late::try_register() {
+        Err(Error::DomainLocked { ref name, .. }) => assert_eq!(name, "late"),
+        got => panic!("registered on a locked domain: {:?}", got),
+    }
+    assert_eq!(lost::try_register(), Err(Error::UnknownDomain("NOWHERE".to_owned())));
+    SHELF.register();
+    assert_eq!(shelves::try_register(), Ok(()));
+    match rebuilt::shelves::try_register() {
+        Err(Error::TableMismatch { ref new, ref existing, .. }) => {
+            assert!(new.contains("u16"), "{}", new);
+            assert!(existing.contains("u8"), "{}", existing);
+        },
+        got => panic!("registered a different table on top: {:?}", got),
+    }
+}
+
+#[test]
+fn properties() {
+    let universe = &make_universe();
+    assert_eq!(*universe.try_read(HERE).unwrap(), 7);
+    *universe.try_write(HERE).unwrap() = 8;
+    assert_eq!(universe.get(HERE), 8);
+    let missing = universe.try_read(MISSING).map(|_| ());
+    assert!(match missing {
+        Err(Error::PropertyNotRegistered(_)) => true,
+        _ => false,
+    });
+}
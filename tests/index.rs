@@ -12,6 +12,8 @@ fn make_universe() -> Universe {
     REGISTER.call_once(|| {
         TEST.register();
         orchard::register();
+        greenhouse::register();
+        harvest::register();
     });
     Universe::new(&[TEST])
 }
@@ -71,3 +73,133 @@ fn indexing() {
         orchard.variety.find(TreeType::Melon).next(),
     );
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Flower {
+    Rose, Tulip, Daisy
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/greenhouse] {
+        #[index = "hash"]
+        flower: [Flower; VecCol<Flower>],
+        petals: [usize; VecCol<usize>],
+    }
+}
+
+#[test]
+fn hash_indexing() {
+    let universe = &make_universe();
+    let mut greenhouse = greenhouse::write(universe);
+    let rose = greenhouse.push(greenhouse::Row {
+        flower: Flower::Rose,
+        petals: 5,
+    });
+    greenhouse.push(greenhouse::Row {
+        flower: Flower::Tulip,
+        petals: 6,
+    });
+    greenhouse.push(greenhouse::Row {
+        flower: Flower::Tulip,
+        petals: 6,
+    });
+    greenhouse.live_flush(universe, ::v11::event::CREATE);
+    let mut tulips = greenhouse.flower.find(Flower::Tulip).collect::<Vec<_>>();
+    tulips.sort();
+    assert_eq!(tulips, vec![greenhouse::RowId::new(1), greenhouse::RowId::new(2)]);
+    assert_eq!(greenhouse.flower.find(Flower::Daisy).next(), None);
+    greenhouse.delete(rose);
+    greenhouse.live_flush(universe, ::v11::event::DELETE);
+    assert_eq!(greenhouse.flower.find(Flower::Rose).next(), None);
+    greenhouse.push(greenhouse::Row {
+        flower: Flower::Daisy,
+        petals: 34,
+    });
+    greenhouse.live_flush(universe, ::v11::event::CREATE);
+    assert_eq!(greenhouse.flower.find(Flower::Daisy).next(), Some(rose));
+}
+
+#[test]
+fn hash_index_shared_value() {
+    let universe = &make_universe();
+    let mut greenhouse = greenhouse::write(universe);
+    let pots: Vec<_> = (0..100).map(|petals| greenhouse.push(greenhouse::Row {
+        flower: Flower::Tulip,
+        petals,
+    })).collect();
+    greenhouse.live_flush(universe, ::v11::event::CREATE);
+    for &pot in pots.iter().step_by(2) {
+        greenhouse.delete(pot);
+    }
+    greenhouse.live_flush(universe, ::v11::event::DELETE);
+    let tulips = greenhouse.flower.find(Flower::Tulip).collect::<Vec<_>>();
+    let odd: Vec<_> = pots.iter().cloned().skip(1).step_by(2).collect();
+    assert_eq!(tulips, odd);
+}
+
+table! {
+    #[kind = "bag"]
+    #[index(name = "by_tree_variety", cols = "tree, variety")]
+    [TEST/harvest] {
+        tree: [u32; VecCol<u32>],
+        variety: [TreeType; VecCol<TreeType>],
+        weight: [usize; VecCol<usize>],
+    }
+}
+
+#[test]
+fn composite_indexing() {
+    let universe = &make_universe();
+    let mut harvest = harvest::write(universe);
+    for &(tree, variety, weight) in &[
+        (1, TreeType::Apple, 10),
+        (2, TreeType::Orange, 20),
+        (1, TreeType::Orange, 30),
+        (2, TreeType::Apple, 40),
+        (1, TreeType::Apple, 50),
+    ] {
+        harvest.push(harvest::Row { tree, variety, weight });
+    }
+    let weights = |harvest: &harvest::Write, rows: Vec<harvest::RowId>| -> Vec<usize> {
+        let mut w: Vec<usize> = rows.into_iter().map(|i| harvest.weight[i]).collect();
+        w.sort();
+        w
+    };
+    let found = harvest.by_tree_variety().find((1, TreeType::Apple)).collect();
+    assert_eq!(weights(&harvest, found), vec![10, 50]);
+    let found = harvest.by_tree_variety().prefix((2,)).collect();
+    assert_eq!(weights(&harvest, found), vec![20, 40]);
+    let found = harvest.by_tree_variety().range((1, TreeType::Orange), (2, TreeType::Apple)).collect();
+    assert_eq!(weights(&harvest, found), vec![30, 40]);
+    // Moves the last row into the hole.
+    harvest.delete(harvest::FIRST);
+    let found = harvest.by_tree_variety().find((1, TreeType::Apple)).collect();
+    assert_eq!(weights(&harvest, found), vec![50]);
+    assert_eq!(harvest.by_tree_variety().len(), 4);
+    harvest.flush(universe, ::v11::event::DELETE);
+}
+
+#[test]
+fn set_indexed() {
+    let universe = &make_universe();
+    let mut greenhouse = greenhouse::write(universe);
+    let pot = greenhouse.push(greenhouse::Row {
+        flower: Flower::Rose,
+        petals: 5,
+    });
+    greenhouse.live_flush(universe, ::v11::event::CREATE);
+    greenhouse.flower.set(pot, Flower::Tulip);
+    assert_eq!(greenhouse.flower[pot], Flower::Tulip);
+    assert_eq!(greenhouse.flower.find(Flower::Rose).next(), None);
+    assert_eq!(greenhouse.flower.find(Flower::Tulip).collect::<Vec<_>>(), vec![pot]);
+    let was = greenhouse.flower.modify(pot, |flower| {
+        let was = *flower;
+        *flower = Flower::Daisy;
+        was
+    });
+    assert_eq!(was, Flower::Tulip);
+    assert_eq!(greenhouse.flower.find(Flower::Tulip).next(), None);
+    assert_eq!(greenhouse.flower.find(Flower::Daisy).collect::<Vec<_>>(), vec![pot]);
+    greenhouse.close();
+}
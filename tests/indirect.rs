@@ -0,0 +1,78 @@
+#![allow(dead_code)]
+
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+
+domain! { TEST }
+
+
+table! {
+    #[kind = "indirect"]
+    #[row_derive(Debug, Clone)]
+    #[row_id = "u64"]
+    [TEST/entities] {
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+use v11::Universe;
+use v11::index::Checkable;
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        entities::register();
+    });
+    Universe::new(&[TEST])
+}
+
+#[test]
+fn handles_follow_rows() {
+    let universe = &make_universe();
+    let mut entities = entities::write(universe);
+    let alice = entities.push(entities::Row { name: "alice" });
+    let bob = entities.push(entities::Row { name: "bob" });
+    let carol = entities.push(entities::Row { name: "carol" });
+    entities.delete_handle(alice);
+    assert_eq!(entities.len(), 2);
+    assert_eq!(entities.resolve(alice), None);
+    assert_eq!(entities.name[bob.check(&entities)], "bob");
+    assert_eq!(entities.name[carol.check(&entities)], "carol");
+    assert_eq!(entities.handle(entities::FIRST), carol);
+    for row in entities.iter() {
+        let handle = entities.handle(row);
+        assert_eq!(entities.resolve(handle), Some(row.uncheck()));
+    }
+}
+
+#[test]
+#[should_panic(expected = "stale handle")]
+fn stale_handle_does_not_alias() {
+    let universe = &make_universe();
+    let mut entities = entities::write(universe);
+    let alice = entities.push(entities::Row { name: "alice" });
+    entities.delete_handle(alice);
+    let dave = entities.push(entities::Row { name: "dave" });
+    assert_eq!(alice.slot(), dave.slot());
+    assert_eq!(entities.name[dave.check(&entities)], "dave");
+    entities.get_row(alice.check(&entities));
+}
+
+#[test]
+fn clear_keeps_handles_stale() {
+    let universe = &make_universe();
+    let mut entities = entities::write(universe);
+    let alice = entities.push(entities::Row { name: "alice" });
+    entities.clear_raw();
+    assert!(!alice.is_valid(&entities));
+    let bob = entities.push(entities::Row { name: "bob" });
+    assert!(bob.is_valid(&entities));
+    assert!(!alice.is_valid(&entities));
+    assert_eq!(entities.name[bob.check(&entities)], "bob");
+}
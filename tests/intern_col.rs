@@ -0,0 +1,121 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+use v11::Universe;
+use v11::event;
+use v11::storage::IStr;
+use v11::tables::RawExtraction;
+use serde_json::Value;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        cities::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_derive(Clone, Debug)]
+    #[save]
+    [TEST/cities] {
+        name: [IStr; InternCol],
+        country: [IStr; InternCol],
+    }
+}
+
+fn fill(universe: &Universe) {
+    let mut cities = cities::write(universe);
+    for &(name, country) in &[
+        ("Lyon", "France"),
+        ("Osaka", "Japan"),
+        ("Paris", "France"),
+        ("Kyoto", "Japan"),
+        ("Nice", "France"),
+    ] {
+        cities.push(cities::Row { name: IStr::new(name), country: IStr::new(country) });
+    }
+    cities.flush(universe, event::CREATE);
+}
+
+#[test]
+fn index_by_string() {
+    let universe = &make_universe();
+    fill(universe);
+    let cities = cities::read(universe);
+    let name: &str = &cities.name[cities::FIRST];
+    assert_eq!(name, "Lyon");
+    assert_eq!(cities.country[cities::FIRST], "France");
+    let french: Vec<&str> = cities.country
+        .find("France")
+        .map(|c| cities.name[c].as_str())
+        .collect();
+    assert_eq!(french, vec!["Lyon", "Paris", "Nice"]);
+    let kyoto: Vec<_> = cities.name.find("Kyoto").collect();
+    assert_eq!(kyoto, vec![cities::RowId::new(3)]);
+    assert_eq!(cities.name.find("Atlantis").count(), 0);
+}
+
+#[test]
+fn change() {
+    let universe = &make_universe();
+    fill(universe);
+    let mut cities = cities::write(universe);
+    let osaka = cities::RowId::new(1);
+    cities.name[osaka] = IStr::new("Tokyo");
+    assert_eq!(cities.name[osaka], "Tokyo");
+    assert_eq!(cities.name.find("Tokyo").collect::<Vec<_>>(), vec![osaka]);
+    assert_eq!(cities.name.find("Osaka").count(), 0);
+    cities.country[osaka] = IStr::new("Nippon");
+    assert_eq!(cities.country.find("Nippon").collect::<Vec<_>>(), vec![osaka]);
+    assert_eq!(cities.country.find("Japan").count(), 1);
+}
+
+#[test]
+fn deleted_rows_are_not_found() {
+    let universe = &make_universe();
+    fill(universe);
+    let mut cities = cities::write(universe);
+    cities.delete(cities::RowId::new(2));
+    cities.flush(universe, event::DELETE);
+    let cities = cities::read(universe);
+    let french: Vec<&str> = cities.country
+        .find("France")
+        .map(|c| cities.name[c].as_str())
+        .collect();
+    assert_eq!(french, vec!["Lyon", "Nice"]);
+    assert_eq!(cities.name.find("Paris").count(), 0);
+}
+
+#[test]
+fn save_dictionary_once() {
+    let universe = &make_universe();
+    fill(universe);
+    let json = serde_json::to_string(&cities::read(universe).extract_selection(v11::tracking::Select::All)).unwrap();
+    let raw: RawExtraction<Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(raw.data["country"], json!({
+        "dictionary": ["France", "Japan"],
+        "codes": [0, 1, 0, 1, 0],
+    }));
+    assert_eq!(json.matches("France").count(), 1);
+
+    let extraction = cities::Extraction::from_raw(raw).unwrap();
+    let elsewhere = &make_universe();
+    cities::write(elsewhere).restore_extract(elsewhere, extraction, event::CREATE).unwrap();
+    let cities = cities::read(elsewhere);
+    assert_eq!(cities.len(), 5);
+    assert_eq!(cities.name[cities::RowId::new(4)], "Nice");
+    assert_eq!(cities.country.find("Japan").count(), 2);
+}
@@ -0,0 +1,138 @@
+#[macro_use] extern crate v11;
+#[macro_use] extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+use v11::join::{self, RowIds};
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        ports::register();
+        ships::register();
+        countries::register();
+    });
+    let universe = Universe::new(&[TEST]);
+    {
+        let mut countries = countries::write(&universe);
+        countries.merge(vec![
+            countries::Row { code: 1, name: "Avalon" },
+            countries::Row { code: 2, name: "Brigadoon" },
+            countries::Row { code: 3, name: "Cockaigne" },
+        ]);
+        countries.close();
+        let mut ports = ports::write(&universe);
+        let north = ports.push(ports::Row { name: "North", country: 1 });
+        let south = ports.push(ports::Row { name: "South", country: 3 });
+        ports.push(ports::Row { name: "East", country: 1 });
+        ports.flush(&universe, event::CREATE);
+        let mut ships = ships::write(&universe);
+        ships.push(ships::Row { name: "Albatross", port: south, flag: 2 });
+        ships.push(ships::Row { name: "Barnacle", port: north, flag: 4 });
+        ships.push(ships::Row { name: "Coracle", port: south, flag: 2 });
+        ships.close();
+    }
+    universe
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/ports] {
+        name: [&'static str; VecCol<&'static str>],
+        #[index]
+        country: [u8; VecCol<u8>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/ships] {
+        name: [&'static str; VecCol<&'static str>],
+        port: [::ports::RowId; VecCol<::ports::RowId>],
+        flag: [u8; VecCol<u8>],
+    }
+}
+
+table! {
+    #[kind = "sorted"]
+    #[row_derive(Clone)]
+    [TEST/countries] {
+        #[sort_key]
+        code: [u8; VecCol<u8>],
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+#[test]
+fn foreign_to_parent() {
+    let universe = &make_universe();
+    let ships = ships::read(universe);
+    let ports = ports::read(universe);
+    let docked: Vec<_> = join::merge_join(&ships, &ships.port, &ports, &RowIds)
+        .map(|(ship, port)| (ships.name[ship], ports.name[port]))
+        .collect();
+    assert_eq!(docked, vec![("Albatross", "South"), ("Barnacle", "North"), ("Coracle", "South")]);
+}
+
+#[test]
+fn anti() {
+    let universe = &make_universe();
+    let ships = ships::read(universe);
+    let ports = ports::read(universe);
+    let empty: Vec<_> = join::anti_join(&ports, &RowIds, &ships, &ships.port)
+        .map(|port| ports.name[port])
+        .collect();
+    assert_eq!(empty, vec!["East"]);
+}
+
+#[test]
+fn left_outer_merge() {
+    let universe = &make_universe();
+    let ships = ships::read(universe);
+    let countries = countries::read(universe);
+    let flags: Vec<_> = join::left_join(&ships, &ships.flag, &countries, &countries.code)
+        .map(|(ship, country)| (ships.name[ship], country.map(|c| countries.name[c])))
+        .collect();
+    assert_eq!(flags, vec![
+        ("Albatross", Some("Brigadoon")),
+        ("Coracle", Some("Brigadoon")),
+        ("Barnacle", None),
+    ]);
+}
+
+#[test]
+fn indexed() {
+    let universe = &make_universe();
+    let countries = countries::read(universe);
+    let ports = ports::read(universe);
+    let found: Vec<_> = join::merge_join(&countries, &countries.code, &ports, &ports.country)
+        .map(|(country, port)| (countries.name[country], ports.name[port]))
+        .collect();
+    assert_eq!(found, vec![("Avalon", "North"), ("Avalon", "East"), ("Cockaigne", "South")]);
+}
+
+/// Looks up a row that isn't there.
+struct Wild;
+impl<'u> join::JoinKey<ports::Read<'u>> for Wild {
+    type Key = ports::RowId;
+    fn key(&self, _table: &ports::Read<'u>, i: usize) -> ports::RowId { ports::RowId::new(i) }
+    fn lookup<'s>(&'s self, _table: &'s ports::Read<'u>, _key: ports::RowId) -> Option<Box<Iterator<Item=usize> + 's>> {
+        Some(Box::new(vec![1, 99].into_iter()))
+    }
+}
+
+#[test]
+fn out_of_bounds_lookup() {
+    let universe = &make_universe();
+    let ships = ships::read(universe);
+    let ports = ports::read(universe);
+    let found: Vec<_> = join::merge_join(&ships, &ships.port, &ports, &Wild)
+        .map(|(ship, port)| (ships.name[ship], ports.name[port]))
+        .collect();
+    assert_eq!(found, vec![("Albatross", "South"), ("Barnacle", "South"), ("Coracle", "South")]);
+}
@@ -0,0 +1,229 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use v11::Universe;
+use v11::erased_serde;
+use v11::event;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        TICK.register();
+        towns::register();
+        roads::register();
+    });
+    Universe::new(&[TEST])
+}
+
+property! { #[save] static TEST/TICK: u32 }
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[journal]
+    #[row_derive(Clone)]
+    [TEST/towns] {
+        name: [String; VecCol<String>],
+        #[index]
+        size: [u32; VecCol<u32>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[journal]
+    #[row_derive(Clone)]
+    [TEST/roads] {
+        #[foreign_auto]
+        #[index]
+        from: [::towns::RowId; VecCol<::towns::RowId>],
+        length: [u32; VecCol<u32>],
+    }
+}
+
+fn delta(universe: &Universe) -> Vec<u8> {
+    let mut json = vec![];
+    universe.save_delta(&[TEST], &mut serde_json::Serializer::new(&mut json)).unwrap();
+    json
+}
+
+fn towns(universe: &Universe) -> Vec<(usize, String, u32)> {
+    let towns = towns::read(universe);
+    towns.iter().map(|t| (t.to_usize(), towns.name[t].clone(), towns.size[t])).collect()
+}
+
+#[test]
+fn deltas_replay() {
+    let universe = &make_universe();
+    let alternia = &make_universe();
+    let (a, b);
+    {
+        let mut towns = towns::write(universe);
+        a = towns.push(towns::Row { name: "Ashford".to_string(), size: 10 });
+        b = towns.push(towns::Row { name: "Brill".to_string(), size: 20 });
+        towns.push(towns::Row { name: "Coombe".to_string(), size: 30 });
+        towns.flush(universe, event::CREATE);
+        let mut roads = roads::write(universe);
+        roads.push(roads::Row { from: a, length: 5 });
+        roads.push(roads::Row { from: b, length: 7 });
+        roads.flush(universe, event::CREATE);
+    }
+    let first = delta(universe);
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&first)).unwrap();
+    assert_eq!(towns(alternia), towns(universe));
+
+    // Nothing changed.
+    assert!(towns::write(universe).journal_is_empty());
+
+    {
+        let mut towns = towns::write(universe);
+        towns.delete(a);
+        towns.flush(universe, event::DELETE);
+        let mut towns = towns::write(universe);
+        towns.size.set(b, 21);
+        towns.push(towns::Row { name: "Dunmore".to_string(), size: 40 });
+        towns.push(towns::Row { name: "Eastwick".to_string(), size: 50 });
+        towns.flush(universe, event::CREATE);
+        universe.set(TICK, 3);
+    }
+    let second = delta(universe);
+    let text = String::from_utf8(second.clone()).unwrap();
+    assert!(!text.contains("Coombe"), "unchanged rows aren't saved: {}", text);
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&second)).unwrap();
+    assert_eq!(towns(alternia), towns(universe));
+    assert_eq!(alternia.get(TICK), 3);
+    let roads = roads::read(alternia);
+    let lengths: Vec<u32> = roads.iter().map(|r| roads.length[r]).collect();
+    assert_eq!(lengths, vec![7]);
+    let towns = towns::read(alternia);
+    assert_eq!(towns.size.find(21).count(), 1);
+    assert_eq!(towns.size.find(10).count(), 0);
+}
+
+#[test]
+fn not_journaled() {
+    let universe = &make_universe();
+    let json = r#"{ "TEST": { "tables": { "nope": {} }, "properties": {} } }"#;
+    let err = universe.apply_delta(&mut serde_json::Deserializer::from_str(json)).unwrap_err();
+    assert!(err.to_string().contains("not registered"));
+}
+
+/// Fails when it gets to the `roads` table.
+struct NoRoads {
+    written: Vec<u8>,
+}
+impl std::io::Write for NoRoads {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf == b"roads" {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"));
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn failed_delta_is_kept() {
+    let universe = &make_universe();
+    let alternia = &make_universe();
+    let a;
+    {
+        let mut towns = towns::write(universe);
+        a = towns.push(towns::Row { name: "Ashford".to_string(), size: 10 });
+        towns.flush(universe, event::CREATE);
+        let mut roads = roads::write(universe);
+        roads.push(roads::Row { from: a, length: 5 });
+        roads.flush(universe, event::CREATE);
+    }
+    // The towns are written, and then the roads fail.
+    let mut out = NoRoads { written: vec![] };
+    let got = universe.save_delta(&[TEST], &mut serde_json::Serializer::new(&mut out));
+    assert!(got.is_err());
+    assert!(String::from_utf8(out.written).unwrap().contains("Ashford"));
+    {
+        let mut towns = towns::write(universe);
+        towns.size.set(a, 11);
+    }
+    let json = delta(universe);
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(towns(alternia), vec![(0, "Ashford".to_string(), 11)]);
+    assert_eq!(roads::read(alternia).len(), 1);
+    assert!(towns::write(universe).journal_is_empty());
+    assert!(towns::write(alternia).journal_is_empty());
+}
+
+#[test]
+fn set_from_is_journaled() {
+    let universe = &make_universe();
+    let alternia = &make_universe();
+    {
+        let mut towns = towns::write(universe);
+        towns.push(towns::Row { name: "Ashford".to_string(), size: 10 });
+        towns.flush(universe, event::CREATE);
+    }
+    let json = delta(universe);
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    {
+        let mut table = universe.table_by_name("TEST/towns").unwrap().write().unwrap();
+        let json = &mut serde_json::Deserializer::from_str("12");
+        table.dyn_column_mut("size").unwrap().set_from(0, &mut erased_serde::Deserializer::erase(json)).unwrap();
+    }
+    assert!(!towns::write(universe).journal_is_empty());
+    let json = delta(universe);
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(towns(alternia), vec![(0, "Ashford".to_string(), 12)]);
+}
+
+#[test]
+fn snapshot_with_holes_then_delta() {
+    let universe = &make_universe();
+    let alternia = &make_universe();
+    let c;
+    {
+        let mut towns = towns::write(universe);
+        let a = towns.push(towns::Row { name: "Ashford".to_string(), size: 10 });
+        let b = towns.push(towns::Row { name: "Brill".to_string(), size: 20 });
+        c = towns.push(towns::Row { name: "Coombe".to_string(), size: 30 });
+        towns.flush(universe, event::CREATE);
+        let mut roads = roads::write(universe);
+        roads.push(roads::Row { from: c, length: 5 });
+        roads.flush(universe, event::CREATE);
+        let mut towns = towns::write(universe);
+        towns.delete(a);
+        towns.delete(b);
+        towns.flush(universe, event::DELETE);
+    }
+    delta(universe);
+    let mut snapshot = vec![];
+    universe.save_snapshot(&[TEST], &mut serde_json::Serializer::new(&mut snapshot)).unwrap();
+    {
+        let mut towns = towns::write(universe);
+        towns.size.set(c, 31);
+        towns.push(towns::Row { name: "Dunmore".to_string(), size: 40 });
+        towns.flush(universe, event::CREATE);
+    }
+    let json = delta(universe);
+
+    alternia.load_snapshot(&mut serde_json::Deserializer::from_slice(&snapshot)).unwrap();
+    assert_eq!(towns(alternia), vec![(2, "Coombe".to_string(), 30)]);
+    assert!(towns::write(alternia).journal_is_empty());
+    assert!(roads::write(alternia).journal_is_empty());
+    alternia.apply_delta(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(towns(alternia), towns(universe));
+    let roads = roads::read(alternia);
+    let towns = towns::read(alternia);
+    let from: Vec<&str> = roads.iter().map(|r| towns.name[roads.from[r]].as_str()).collect();
+    assert_eq!(from, vec!["Coombe"]);
+}
@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+
+domain! { TEST }
+
+
+table! {
+    #[kind = "list"]
+    #[row_derive(Debug, Clone)]
+    [TEST/commands] {
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+use v11::Universe;
+use v11::event;
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        commands::register();
+    });
+    Universe::new(&[TEST])
+}
+
+fn names(universe: &Universe) -> Vec<&'static str> {
+    let commands = commands::read(universe);
+    commands.iter().map(|i| commands.name[i]).collect()
+}
+
+fn id(i: usize) -> commands::RowId { commands::RowId::from_usize(i) }
+
+#[test]
+fn push_keeps_order() {
+    let universe = &make_universe();
+    let mut commands = commands::write(universe);
+    for &name in &["a", "b", "c"] {
+        commands.push(commands::Row { name });
+    }
+    commands.flush(universe, event::CREATE);
+    assert_eq!(names(universe), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn insert_and_remove() {
+    let universe = &make_universe();
+    let mut commands = commands::write(universe);
+    for &name in &["a", "b", "c", "d"] {
+        commands.push(commands::Row { name });
+    }
+    commands.insert_at(id(0), commands::Row { name: "start" });
+    commands.insert_at(id(2), commands::Row { name: "x" });
+    commands.insert_at(id(2), commands::Row { name: "y" });
+    commands.remove(id(1));
+    commands.insert_at(id(4), commands::Row { name: "end" });
+    assert!(commands.is_removed(id(1)));
+    // Inserting at the end is a push.
+    assert_eq!(commands.len(), 5);
+    commands.flush(universe, event::CREATE);
+    assert_eq!(names(universe), vec!["start", "a", "x", "y", "c", "d", "end"]);
+}
+
+#[test]
+fn insert_at_end_then_push() {
+    let universe = &make_universe();
+    let mut commands = commands::write(universe);
+    commands.push(commands::Row { name: "a" });
+    let end = commands.next_pushed();
+    commands.insert_at(end, commands::Row { name: "b" });
+    commands.push(commands::Row { name: "c" });
+    commands.insert_at(id(1), commands::Row { name: "a2" });
+    commands.flush(universe, event::CREATE);
+    assert_eq!(names(universe), vec!["a", "a2", "b", "c"]);
+}
+
+#[test]
+fn drain() {
+    let universe = &make_universe();
+    let mut commands = commands::write(universe);
+    for &name in &["a", "b", "c", "d", "e"] {
+        commands.push(commands::Row { name });
+    }
+    let drained = commands.drain((id(1)..id(3)).into());
+    assert_eq!(drained.iter().map(|r| r.name).collect::<Vec<_>>(), vec!["b", "c"]);
+    commands.compact();
+    assert_eq!(commands.len(), 3);
+    commands.close();
+    assert_eq!(names(universe), vec!["a", "d", "e"]);
+}
+
+#[test]
+fn panic_with_pending_edits() {
+    use std::panic::{self, AssertUnwindSafe};
+    let universe = &make_universe();
+    {
+        let mut commands = commands::write(universe);
+        commands.push(commands::Row { name: "a" });
+        commands.flush(universe, event::CREATE);
+    }
+    // Dropping the unflushed lock while unwinding mustn't panic again, which would abort.
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut commands = commands::write(universe);
+        commands.insert_at(id(0), commands::Row { name: "start" });
+        commands.remove(id(0));
+        panic!("before flushing");
+    }));
+    assert!(got.is_err());
+}
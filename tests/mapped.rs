@@ -0,0 +1,110 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use std::fs::File;
+use v11::Universe;
+use v11::event;
+use v11::tracking::Select;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        samples::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "append"]
+    #[row_derive(Clone)]
+    #[binary]
+    [TEST/samples] {
+        time: [u64; MappedCol<u64>],
+        #[index]
+        channel: [u8; MappedCol<u8>],
+        value: [f32; MappedCol<f32>],
+        clipped: [bool; BoolCol],
+    }
+}
+
+fn write_file(name: &str) -> std::path::PathBuf {
+    let universe = &make_universe();
+    {
+        let mut samples = samples::write(universe);
+        for i in 0..1000 {
+            samples.push(samples::Row {
+                time: i * 10,
+                channel: (i % 3) as u8,
+                value: i as f32 / 2.0,
+                clipped: i % 7 == 0,
+            });
+        }
+        samples.flush(universe, event::CREATE);
+    }
+    let path = std::env::temp_dir().join(format!("v11-mapped-{}-{}.bin", name, std::process::id()));
+    let mut file = File::create(&path).unwrap();
+    samples::read(universe).extract_selection(Select::All).write_binary(&mut file).unwrap();
+    path
+}
+
+#[test]
+fn open_mapped() {
+    let path = write_file("open");
+    let universe = &make_universe();
+    {
+        let samples = unsafe { samples::open_mapped(universe, &path) }.unwrap();
+        assert_eq!(samples.len(), 1000);
+        assert!(samples.time.deref().inner().is_mapped());
+        assert_eq!(samples.time.deref().inner().as_slice()[999], 9990);
+        assert_eq!(samples.value[samples::RowId::new(3)], 1.5);
+        assert!(samples.clipped[samples::RowId::new(14)]);
+        assert_eq!(samples.channel.find(2).count(), 333);
+    }
+    {
+        let mut samples = samples::write(universe);
+        samples.time[samples::RowId::new(0)] = 5;
+        assert!(!samples.time.deref().inner().is_mapped());
+        assert!(samples.value.deref().inner().is_mapped());
+        assert_eq!(samples.time[samples::RowId::new(999)], 9990);
+        samples.push(samples::Row { time: 10000, channel: 0, value: 0.0, clipped: false });
+        assert!(!samples.value.deref().inner().is_mapped());
+        assert_eq!(samples.len(), 1001);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn needs_empty_table() {
+    let path = write_file("empty");
+    let universe = &make_universe();
+    samples::write(universe).push(samples::Row { time: 0, channel: 0, value: 0.0, clipped: false });
+    assert!(unsafe { samples::open_mapped(universe, &path) }.is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn rejects_holes() {
+    let universe = &make_universe();
+    {
+        let mut samples = samples::write(universe);
+        for i in 0..3 {
+            samples.push(samples::Row { time: i, channel: 0, value: 0.0, clipped: false });
+        }
+        samples.flush(universe, event::CREATE);
+    }
+    let path = std::env::temp_dir().join(format!("v11-mapped-holes-{}.bin", std::process::id()));
+    let rows = [samples::RowId::new(0), samples::RowId::new(2)];
+    let mut file = File::create(&path).unwrap();
+    samples::read(universe).extract_selection(Select::These(&rows)).write_binary(&mut file).unwrap();
+    let elsewhere = &make_universe();
+    assert!(unsafe { samples::open_mapped(elsewhere, &path) }.is_err());
+    assert_eq!(samples::read(elsewhere).len(), 0);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,122 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+use v11::Universe;
+use v11::event;
+use v11::tables::{Migrate, RawExtraction};
+use serde_json::Value;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        pets::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_derive(Clone)]
+    #[save]
+    #[migrate]
+    #[version = "1"]
+    [TEST/pets] {
+        name: [String; VecCol<String>],
+        // Was `feet`.
+        legs: [u8; VecCol<u8>],
+        // Was in pounds.
+        #[version = "1"]
+        grams: [u32; VecCol<u32>],
+        // New.
+        age: [u32; VecCol<u32>],
+        // `color` was removed.
+    }
+}
+
+impl Migrate<Value> for pets::Row {
+    fn migrate(raw: &mut RawExtraction<Value>) -> Result<(), String> {
+        if raw.schema == 0 {
+            raw.rename_column("feet", "legs")?;
+            raw.drop_column("color")?;
+            raw.convert_column("weight", |meta, data| {
+                meta.name = "grams".into();
+                meta.version = 1;
+                let pounds: Vec<u32> = serde_json::from_value(data).map_err(|e| e.to_string())?;
+                let grams: Vec<u32> = pounds.iter().map(|lb| lb * 453).collect();
+                Ok(json!(grams))
+            })?;
+            let age = json!(vec![0; raw.len()]);
+            raw.add_column(v11::tables::ColumnMeta {
+                name: "age".into(),
+                stored_type_name: "u32".into(),
+                version: 0,
+            }, age)?;
+            raw.schema = 1;
+        }
+        Ok(())
+    }
+}
+
+const OLD_SAVE: &'static str = r#"{
+    "extraction_fmt": 0,
+    "domain": "TEST",
+    "name": "pets",
+    "schema": 0,
+    "columns": [
+        { "name": "name", "stored_type_name": "String", "version": 0 },
+        { "name": "feet", "stored_type_name": "u8", "version": 0 },
+        { "name": "color", "stored_type_name": "String", "version": 0 },
+        { "name": "weight", "stored_type_name": "u32", "version": 0 }
+    ],
+    "selection": [0, 1],
+    "data": {
+        "name": ["Rex", "Polly"],
+        "feet": [4, 2],
+        "color": ["brown", "green"],
+        "weight": [30, 1]
+    }
+}"#;
+
+#[test]
+fn migrate_old_save() {
+    let universe = &make_universe();
+    let raw: RawExtraction<Value> = serde_json::from_str(OLD_SAVE).unwrap();
+    let extraction = pets::Extraction::from_raw(raw).unwrap();
+    pets::write(universe).restore_extract(universe, extraction, event::CREATE).unwrap();
+    let pets = pets::read(universe);
+    let rows: Vec<_> = pets.iter().map(|i| (pets.name[i].clone(), pets.legs[i], pets.grams[i], pets.age[i])).collect();
+    assert_eq!(rows, vec![
+        ("Rex".to_string(), 4, 13590, 0),
+        ("Polly".to_string(), 2, 453, 0),
+    ]);
+}
+
+#[test]
+fn current_save_is_untouched() {
+    let universe = &make_universe();
+    {
+        let mut pets = pets::write(universe);
+        pets.push(pets::Row { name: "Tom".to_string(), legs: 4, grams: 4000, age: 3 });
+        pets.flush(universe, event::CREATE);
+    }
+    let json = serde_json::to_string(&pets::read(universe).extract_selection(v11::tracking::Select::All)).unwrap();
+    let raw: RawExtraction<Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(raw.columns, pets::Extraction::current_columns());
+    let extraction = pets::Extraction::from_raw(raw).unwrap();
+    let alternia = &make_universe();
+    pets::write(alternia).restore_extract(alternia, extraction, event::CREATE).unwrap();
+    let pets = pets::read(alternia);
+    assert_eq!(pets.len(), 1);
+    assert_eq!(pets.name[pets::FIRST], "Tom");
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "rayon")]
+
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+use v11::rayon::prelude::*;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        particles::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/particles] {
+        position: [i64; VecCol<i64>],
+        velocity: [i64; VecCol<i64>],
+        alive: [bool; BoolCol],
+    }
+}
+
+fn populate(universe: &Universe) {
+    let mut particles = particles::write(universe);
+    for i in 0..1000 {
+        particles.push(particles::Row { position: i, velocity: i % 7, alive: true });
+    }
+    particles.flush(universe, event::CREATE);
+    let mut particles = particles::write(universe);
+    for i in 0..1000 {
+        if i % 3 == 0 {
+            particles.delete(particles::RowId::new(i));
+        }
+    }
+    particles.flush(universe, event::DELETE);
+}
+
+#[test]
+fn par_iter() {
+    let universe = &make_universe();
+    populate(universe);
+    let particles = particles::read(universe);
+    let total: i64 = particles.par_iter().map(|p| particles.position[p]).sum();
+    let expected: i64 = particles.iter().map(|p| particles.position[p]).sum();
+    assert_eq!(total, expected);
+    assert_eq!(particles.par_iter().count(), 666);
+}
+
+#[test]
+fn par_iter_mut() {
+    let universe = &make_universe();
+    populate(universe);
+    {
+        let mut particles = particles::write(universe);
+        let (edit, rows) = particles.par_iter_mut();
+        let moved = rows
+            .zip((&mut *edit.position, &*edit.velocity))
+            .map(|(_row, (pos, vel))| *pos += *vel)
+            .count();
+        assert_eq!(moved, 666);
+    }
+    let particles = particles::read(universe);
+    for i in 0..1000 {
+        let i = particles::RowId::new(i);
+        let moved = if i.to_usize() % 3 == 0 { 0 } else { i.to_usize() as i64 % 7 };
+        assert_eq!(particles.position[i], i.to_usize() as i64 + moved);
+    }
+}
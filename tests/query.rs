@@ -0,0 +1,182 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+use v11::index::Checkable;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        ships::register();
+        sailors::register();
+        parrots::register();
+    });
+    let universe = Universe::new(&[TEST]);
+    {
+        let mut ships = ships::write(&universe);
+        let ark = ships.push(ships::Row { name: "Ark" });
+        let bounty = ships.push(ships::Row { name: "Bounty" });
+        ships.push(ships::Row { name: "Coracle" });
+        ships.flush(&universe, event::CREATE);
+        let mut sailors = sailors::write(&universe);
+        let noah = sailors.push(sailors::Row { name: "Noah", ship: ark, age: 600 });
+        sailors.push(sailors::Row { name: "Bligh", ship: bounty, age: 35 });
+        let christian = sailors.push(sailors::Row { name: "Christian", ship: bounty, age: 24 });
+        sailors.close();
+        let mut parrots = parrots::write(&universe);
+        parrots.push(parrots::Row { name: "Polly", owner: christian });
+        parrots.push(parrots::Row { name: "Pip", owner: noah });
+        parrots.push(parrots::Row { name: "Percy", owner: noah });
+        parrots.close();
+    }
+    universe
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/ships] {
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/sailors] {
+        name: [&'static str; VecCol<&'static str>],
+        #[index]
+        ship: [::ships::RowId; VecCol<::ships::RowId>],
+        age: [u16; VecCol<u16>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/parrots] {
+        name: [&'static str; VecCol<&'static str>],
+        owner: [::sailors::RowId; VecCol<::sailors::RowId>],
+    }
+}
+
+#[test]
+fn join() {
+    let universe = &make_universe();
+    let mut crew = vec![];
+    query!(universe, for (s in ships, c in sailors where c.ship == s) {
+        crew.push((ships.name[s], sailors.name[c]));
+    });
+    assert_eq!(crew, vec![("Ark", "Noah"), ("Bounty", "Bligh"), ("Bounty", "Christian")]);
+}
+
+#[test]
+fn reversed() {
+    let universe = &make_universe();
+    let mut crew = vec![];
+    query!(universe, for (c in sailors, s in ships where s == c.ship) {
+        crew.push((sailors.name[c], ships.name[s]));
+    });
+    assert_eq!(crew, vec![("Noah", "Ark"), ("Bligh", "Bounty"), ("Christian", "Bounty")]);
+}
+
+#[test]
+fn filter() {
+    let universe = &make_universe();
+    let oldest = 100;
+    let mut crew = vec![];
+    query!(universe, for (s in ships, c in sailors where c.ship == s && sailors.age[c] < oldest) {
+        crew.push(sailors.name[c]);
+    });
+    assert_eq!(crew, vec!["Bligh", "Christian"]);
+}
+
+#[test]
+fn either() {
+    let universe = &make_universe();
+    let mut crew = vec![];
+    query!(universe, for (s in ships, c in sailors where c.ship == s && (sailors.age[c] > 500 || sailors.age[c] < 30)) {
+        crew.push(sailors.name[c]);
+    });
+    assert_eq!(crew, vec!["Noah", "Christian"]);
+}
+
+#[test]
+fn three_tables() {
+    let universe = &make_universe();
+    let mut pets = vec![];
+    query!(universe, for (s in ships, c in sailors, p in parrots where c.ship == s && p.owner == c) {
+        pets.push((ships.name[s], parrots.name[p]));
+    });
+    assert_eq!(pets, vec![("Ark", "Pip"), ("Ark", "Percy"), ("Bounty", "Polly")]);
+}
+
+#[test]
+fn unjoined() {
+    let universe = &make_universe();
+    let mut n = 0;
+    query!(universe, for (_s in ships, _c in sailors) {
+        n += 1;
+    });
+    assert_eq!(n, 9);
+}
+
+#[test]
+fn write() {
+    let universe = &make_universe();
+    query!(universe, for (mut c in sailors, s in ships where c.ship == s && ships.name[s] == "Bounty") {
+        sailors.age[c] += 1;
+    });
+    let sailors = sailors::read(universe);
+    let ages: Vec<u16> = sailors.iter().map(|c| sailors.age[c]).collect();
+    assert_eq!(ages, vec![600, 36, 25]);
+}
+
+#[test]
+fn nested() {
+    let universe = &make_universe();
+    let mut crews = vec![];
+    query!(universe, for (s in ships) {
+        let mut crew = 0;
+        query!(universe, for (c in sailors where sailors.ship[c] == s.uncheck()) {
+            let _ = c;
+            crew += 1;
+        });
+        crews.push(crew);
+    });
+    assert_eq!(crews, vec![1, 2, 0]);
+}
+
+fn same<A, B>(universe: &Universe) -> &Universe { universe }
+
+#[test]
+fn turbofish() {
+    let universe = &make_universe();
+    let mut n = 0;
+    query!(same::<u8, u16>(universe), for (_s in ships) {
+        n += 1;
+    });
+    assert_eq!(n, 3);
+}
+
+#[test]
+fn break_out() {
+    let universe = &make_universe();
+    let mut crew = vec![];
+    query!(universe, for (s in ships, c in sailors) {
+        for _ in 0..2 {
+            // This only leaves the inner loop.
+            break;
+        }
+        if ships.name[s] == "Bounty" {
+            break;
+        }
+        crew.push(sailors.name[c]);
+    });
+    assert_eq!(crew, vec!["Noah", "Bligh", "Christian"]);
+}
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use v11::{Error, Universe};
+use v11::erased_serde;
+use v11::event;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        planets::register();
+        moons::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[row_derive(Clone, Debug)]
+    [TEST/planets] {
+        name: [String; VecCol<String>],
+        #[index]
+        moons: [u32; VecCol<u32>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/moons] {
+        x: [u8; VecCol<u8>],
+    }
+}
+
+fn populate(universe: &Universe) {
+    let mut planets = planets::write(universe);
+    planets.push(planets::Row { name: "Mercury".to_string(), moons: 0 });
+    let pluto = planets.push(planets::Row { name: "Pluto".to_string(), moons: 5 });
+    planets.push(planets::Row { name: "Earth".to_string(), moons: 1 });
+    planets.flush(universe, event::CREATE);
+    let mut planets = planets::write(universe);
+    planets.delete(pluto);
+    planets.flush(universe, event::DELETE);
+    moons::write(universe).push(moons::Row { x: 3 });
+}
+
+#[test]
+fn inspect() {
+    let universe = &make_universe();
+    populate(universe);
+    let table = universe.table_by_name("TEST/planets").unwrap().read().unwrap();
+    let mut seen = vec![];
+    for col in table.dyn_columns() {
+        for i in 0..col.len() {
+            if table.is_deleted(i) { continue; }
+            let json = serde_json::to_string(col.get_serialize(i).unwrap()).unwrap();
+            seen.push(format!("{}={:?}={}", col.meta().name, col.get_dyn(i), json));
+        }
+    }
+    assert_eq!(seen, vec![
+        r#"name="Mercury"="Mercury""#,
+        r#"name="Earth"="Earth""#,
+        "moons=0=0",
+        "moons=1=1",
+    ]);
+
+    let moons = universe.table_by_name("TEST/moons").unwrap().read().unwrap();
+    let x = moons.dyn_column("x").unwrap();
+    assert_eq!(format!("{:?}", x.get_dyn(0)), "<opaque>");
+    assert!(x.get_serialize(0).is_none());
+}
+
+#[test]
+fn set_from() {
+    let universe = &make_universe();
+    populate(universe);
+    {
+        let mut table = universe.table_by_name("TEST/planets").unwrap().write().unwrap();
+        let mut moons = table.dyn_column_mut("moons").unwrap();
+        let json = &mut serde_json::Deserializer::from_str("2");
+        moons.set_from(2, &mut erased_serde::Deserializer::erase(json)).unwrap();
+        let json = &mut serde_json::Deserializer::from_str("\"lots\"");
+        assert!(moons.set_from(0, &mut erased_serde::Deserializer::erase(json)).is_err());
+        let json = &mut serde_json::Deserializer::from_str("2");
+        match moons.set_from(9, &mut erased_serde::Deserializer::erase(json)) {
+            Err(Error::RowOutOfRange { row: 9, len: 3 }) => (),
+            got => panic!("{:?}", got.err()),
+        }
+        // Pluto.
+        let json = &mut serde_json::Deserializer::from_str("2");
+        assert_eq!(moons.set_from(1, &mut erased_serde::Deserializer::erase(json)).err(), Some(Error::RowDeleted(1)));
+    }
+    let planets = planets::read(universe);
+    assert_eq!(planets.moons[planets::RowId::new(2)], 2);
+    assert_eq!(planets.moons.find(2).count(), 1);
+    assert_eq!(planets.moons.find(1).count(), 0);
+}
+
+#[test]
+fn unknown_names() {
+    let universe = &make_universe();
+    assert_eq!(universe.table_by_name("TEST/stars").err(), Some(Error::UnknownTable {
+        domain: "TEST".to_owned(),
+        table: "stars".to_owned(),
+        known: vec!["planets".to_owned(), "moons".to_owned()],
+    }));
+    assert_eq!(universe.table_by_name("GALAXY/stars").err(), Some(Error::UnknownDomain("GALAXY".to_owned())));
+    assert_eq!(universe.table_by_name("planets").err(), Some(Error::InvalidName("planets".to_owned())));
+}
@@ -0,0 +1,229 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use v11::Universe;
+use v11::event;
+use v11::tracking::prelude::*;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        hives::register();
+        bees::register();
+        swarms::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[add_tracker = "Plugin"]
+    [TEST/hives] {
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/bees] {
+        #[foreign_auto]
+        #[index]
+        hive: [::hives::RowId; VecCol<::hives::RowId>],
+    }
+}
+
+table! {
+    #[kind = "bag"]
+    [TEST/swarms] {
+        size: [u32; VecCol<u32>],
+    }
+}
+
+static PLUGIN_PANICS: AtomicBool = AtomicBool::new(false);
+
+struct Plugin;
+impl Tracker for Plugin {
+    type Foreign = hives::Row;
+    fn sort(&self) -> bool { false }
+    fn handle(&self, _: &Universe, event: Event, _: SelectRows<hives::Row>, _: &dyn event::Function) {
+        if event.is_removal && PLUGIN_PANICS.swap(false, Ordering::SeqCst) {
+            panic!("plugin bug");
+        }
+    }
+}
+
+fn names(universe: &Universe) -> Vec<&'static str> {
+    let hives = hives::read(universe);
+    hives.iter().map(|h| hives.name[h]).collect()
+}
+
+#[test]
+fn unwinding_write_rolls_back() {
+    let universe = &make_universe();
+    {
+        let mut hives = hives::write(universe);
+        hives.push(hives::Row { name: "apiary" });
+        let doomed = hives.push(hives::Row { name: "doomed" });
+        hives.push(hives::Row { name: "skep" });
+        hives.flush(universe, event::CREATE);
+        let mut hives = hives::write(universe);
+        hives.delete(doomed);
+        hives.flush(universe, event::DELETE);
+    }
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut hives = hives::write(universe);
+        hives.push(hives::Row { name: "reused" });
+        hives.push(hives::Row { name: "appended" });
+        panic!("before flushing");
+    }));
+    assert!(got.is_err());
+    assert!(hives::write_result(universe).is_err());
+    universe.repair();
+    assert_eq!(names(universe), vec!["apiary", "skep"]);
+    let mut hives = hives::write(universe);
+    assert_eq!(hives.len(), 3);
+    assert_eq!(hives.next_pushed().to_usize(), 1);
+    hives.push(hives::Row { name: "afterwards" });
+    hives.flush(universe, event::CREATE);
+}
+
+#[test]
+fn interrupted_flush_is_finished() {
+    let universe = &make_universe();
+    let target;
+    {
+        let mut hives = hives::write(universe);
+        hives.push(hives::Row { name: "keep" });
+        target = hives.push(hives::Row { name: "target" });
+        hives.flush(universe, event::CREATE);
+        let mut bees = bees::write(universe);
+        bees.push(bees::Row { hive: target });
+        bees.push(bees::Row { hive: target });
+        bees.flush(universe, event::CREATE);
+    }
+    PLUGIN_PANICS.store(true, Ordering::SeqCst);
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut hives = hives::write(universe);
+        hives.delete(target);
+        hives.flush(universe, event::DELETE);
+    }));
+    assert!(got.is_err());
+    assert_eq!(names(universe), vec!["keep", "target"]);
+    universe.repair();
+    assert_eq!(names(universe), vec!["keep"]);
+    assert_eq!(bees::read(universe).iter().count(), 0);
+    // Nothing is left to repair.
+    universe.repair();
+    assert_eq!(names(universe), vec!["keep"]);
+}
+
+/// Panics the first time it sees a deletion.
+struct Flaky {
+    panicked: AtomicBool,
+    calls: Arc<AtomicUsize>,
+}
+impl Tracker for Flaky {
+    type Foreign = hives::Row;
+    fn sort(&self) -> bool { false }
+    fn handle(&self, _: &Universe, event: Event, _: SelectRows<hives::Row>, _: &dyn event::Function) {
+        if !event.is_removal { return; }
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        if !self.panicked.swap(true, Ordering::SeqCst) {
+            panic!("flaky plugin");
+        }
+    }
+}
+
+#[test]
+fn finished_trackers_are_not_rerun() {
+    let universe = &make_universe();
+    // Runs after the `bees` tracker.
+    let calls = Arc::new(AtomicUsize::new(0));
+    universe.register_tracker(Flaky { panicked: AtomicBool::new(false), calls: calls.clone() });
+    let (target, other);
+    {
+        let mut hives = hives::write(universe);
+        target = hives.push(hives::Row { name: "target" });
+        other = hives.push(hives::Row { name: "other" });
+        hives.flush(universe, event::CREATE);
+        let mut bees = bees::write(universe);
+        bees.push(bees::Row { hive: target });
+        bees.push(bees::Row { hive: other });
+        bees.flush(universe, event::CREATE);
+    }
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut hives = hives::write(universe);
+        hives.delete(target);
+        hives.flush(universe, event::DELETE);
+    }));
+    assert!(got.is_err());
+    assert_eq!(bees::read(universe).iter().count(), 1);
+    universe.repair();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(names(universe), vec!["other"]);
+    let bees = bees::read(universe);
+    let hives: Vec<_> = bees.iter().map(|b| bees.hive[b]).collect();
+    assert_eq!(hives, vec![other]);
+}
+
+#[test]
+fn repaired_table_can_be_poisoned_again() {
+    let universe = &make_universe();
+    for _ in 0..2 {
+        let got = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _hives = hives::write(universe);
+            panic!("while locked");
+        }));
+        assert!(got.is_err());
+        assert!(hives::write_result(universe).is_err());
+        universe.repair();
+        assert!(hives::write_result(universe).is_ok());
+    }
+}
+
+#[test]
+fn mixed_write_rolls_back_pushes() {
+    let universe = &make_universe();
+    let doomed;
+    {
+        let mut hives = hives::write(universe);
+        hives.push(hives::Row { name: "apiary" });
+        doomed = hives.push(hives::Row { name: "doomed" });
+        hives.flush(universe, event::CREATE);
+    }
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut hives = hives::write(universe);
+        hives.push(hives::Row { name: "pushed" });
+        hives.delete(doomed);
+        hives.push(hives::Row { name: "also pushed" });
+        panic!("before flushing");
+    }));
+    assert!(got.is_err());
+    universe.repair();
+    assert_eq!(names(universe), vec!["apiary", "doomed"]);
+}
+
+#[test]
+fn other_kinds_stay_poisoned() {
+    let universe = &make_universe();
+    let got = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut swarms = swarms::write(universe);
+        swarms.push(swarms::Row { size: 3 });
+        panic!("before flushing");
+    }));
+    assert!(got.is_err());
+    universe.repair();
+    assert!(swarms::write_result(universe).is_err());
+    assert!(hives::write_result(universe).is_ok());
+}
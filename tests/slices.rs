@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        points::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/points] {
+        x: [f32; VecCol<f32>],
+        y: [f32; VecCol<f32>],
+    }
+}
+
+#[test]
+fn bulk() {
+    let universe = &make_universe();
+    {
+        let mut points = points::write(universe);
+        for i in 0..4 {
+            points.push(points::Row { x: i as f32, y: 1.0 });
+        }
+        for (x, y) in points.x.as_mut_slice().iter_mut().zip(points.y.as_slice()) {
+            *x *= 2.0;
+            *x += *y;
+        }
+    }
+    let points = points::read(universe);
+    assert_eq!(points.x.as_slice(), &[1.0, 3.0, 5.0, 7.0]);
+    assert_eq!(points.x.as_slice()[2], points.x[points::RowId::new(2)]);
+}
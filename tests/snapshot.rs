@@ -0,0 +1,130 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+use v11::Universe;
+use v11::event;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        TURN.register();
+        SCRATCH.register();
+        players::register();
+        items::register();
+        notes::register();
+    });
+    Universe::new(&[TEST])
+}
+
+property! { #[save] static TEST/TURN: u32 }
+property! { static TEST/SCRATCH: u32 }
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[row_derive(Clone)]
+    [TEST/players] {
+        name: [String; VecCol<String>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[row_derive(Clone)]
+    [TEST/items] {
+        #[foreign_auto]
+        #[index]
+        owner: [::players::RowId; VecCol<::players::RowId>],
+        name: [String; VecCol<String>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/notes] {
+        text: [String; VecCol<String>],
+    }
+}
+
+#[test]
+fn round_trip() {
+    let universe = &make_universe();
+    {
+        let mut players = players::write(universe);
+        let gone = players.push(players::Row { name: "Gone".to_string() });
+        let alice = players.push(players::Row { name: "Alice".to_string() });
+        let bob = players.push(players::Row { name: "Bob".to_string() });
+        players.flush(universe, event::CREATE);
+        let mut items = items::write(universe);
+        items.push(items::Row { owner: bob, name: "sword".to_string() });
+        items.push(items::Row { owner: alice, name: "shield".to_string() });
+        items.flush(universe, event::CREATE);
+        let mut players = players::write(universe);
+        players.delete(gone);
+        players.flush(universe, event::DELETE);
+        let mut notes = notes::write(universe);
+        notes.push(notes::Row { text: "not saved".to_string() });
+        universe.set(TURN, 12);
+        universe.set(SCRATCH, 99);
+    }
+
+    let mut json = vec![];
+    universe.save_snapshot(&[TEST], &mut serde_json::Serializer::new(&mut json)).unwrap();
+
+    let alternia = &make_universe();
+    alternia.load_snapshot(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+    assert_eq!(alternia.get(TURN), 12);
+    assert_eq!(alternia.get(SCRATCH), 0);
+    assert_eq!(notes::read(alternia).len(), 0);
+    let players = players::read(alternia);
+    let items = items::read(alternia);
+    assert_eq!(players.iter().count(), 2);
+    let owners: Vec<_> = items.iter().map(|i| (items.name[i].as_str(), players.name[items.owner[i]].as_str())).collect();
+    assert_eq!(owners, vec![("sword", "Bob"), ("shield", "Alice")]);
+}
+
+#[test]
+fn unknown_table() {
+    let universe = &make_universe();
+    let json = r#"{ "TEST": { "tables": { "notes": {} }, "properties": {} } }"#;
+    let err = universe.load_snapshot(&mut serde_json::Deserializer::from_str(json)).unwrap_err();
+    assert!(err.to_string().contains("table TEST/notes is not #[save]"));
+}
+
+#[test]
+fn unknown_domain() {
+    let universe = &make_universe();
+    let json = r#"{ "ELSEWHERE": { "tables": {}, "properties": {} } }"#;
+    let err = universe.load_snapshot(&mut serde_json::Deserializer::from_str(json)).unwrap_err();
+    assert!(err.to_string().contains("domain ELSEWHERE is not in this Universe"));
+}
+
+#[test]
+fn dangling_foreign_key() {
+    let universe = &make_universe();
+    let json = r#"{ "TEST": { "tables": {
+        "players": { "extraction_fmt": 0, "domain": "TEST", "name": "players", "schema": 0,
+            "columns": [{ "name": "name", "stored_type_name": "String", "version": 0 }],
+            "selection": [0], "data": { "name": ["Bob"] } },
+        "items": { "extraction_fmt": 0, "domain": "TEST", "name": "items", "schema": 0,
+            "columns": [
+                { "name": "owner", "stored_type_name": "::players::RowId", "version": 0 },
+                { "name": "name", "stored_type_name": "String", "version": 0 }
+            ],
+            "selection": [0, 1], "data": { "owner": [0, 7], "name": ["sword", "shield"] } }
+    }, "properties": {} } }"#;
+    let err = universe.load_snapshot(&mut serde_json::Deserializer::from_str(json)).unwrap_err();
+    assert!(err.to_string().contains("no remapping"));
+    assert_eq!(items::read(universe).len(), 0);
+}
@@ -0,0 +1,53 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::storage::SOA_LANES;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        bodies::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/bodies] {
+        mass: [f32; SoaChunkCol<f32>],
+        speed: [f32; SoaChunkCol<f32>],
+    }
+}
+
+#[test]
+fn kernels() {
+    let universe = &make_universe();
+    {
+        let mut bodies = bodies::write(universe);
+        for i in 0..40 {
+            bodies.push(bodies::Row { mass: i as f32, speed: 2.0 });
+        }
+        let speeds: Vec<_> = bodies.speed.chunks().map(|(chunk, _)| chunk.lanes).collect();
+        for ((mass, mask), speed) in bodies.mass.chunks_mut().zip(speeds) {
+            for lane in 0..SOA_LANES {
+                if mask & (1 << lane) != 0 {
+                    mass.lanes[lane] *= speed[lane];
+                }
+            }
+        }
+    }
+    let bodies = bodies::read(universe);
+    let masks: Vec<_> = bodies.mass.chunks().map(|(_, mask)| mask).collect();
+    assert_eq!(masks, vec![0xFFFF, 0xFFFF, 0xFF]);
+    for row in bodies.iter() {
+        assert_eq!(bodies.mass[row], row.to_usize() as f32 * 2.0);
+    }
+}
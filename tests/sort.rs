@@ -155,3 +155,28 @@ impl<'u> sorted::Write<'u> {
         }
     }
 }
+
+#[test]
+fn search_sort_key() {
+    let universe = &make_universe();
+    let mut sorted = sorted::write(universe);
+    sorted.merge(vec![
+        sorted::Row { key: 1, val: "a" },
+        sorted::Row { key: 3, val: "b" },
+        sorted::Row { key: 3, val: "c" },
+        sorted::Row { key: 3, val: "d" },
+        sorted::Row { key: 7, val: "e" },
+    ]);
+    let sorted = sorted.as_read();
+    let vals = |range: v11::tables::RowRange<sorted::RowId>| -> Vec<&'static str> {
+        range.iter_slow().map(|i| sorted.val[i]).collect()
+    };
+    assert_eq!(vals(sorted.find_sorted(&3)), vec!["b", "c", "d"]);
+    assert_eq!(vals(sorted.find_sorted(&2)), Vec::<&str>::new());
+    assert_eq!(sorted.lower_bound(&2), sorted::RowId::new(1));
+    assert_eq!(sorted.upper_bound(&3), sorted::RowId::new(4));
+    assert_eq!(sorted.lower_bound(&8), sorted::RowId::new(5));
+    assert_eq!(vals(sorted.range_sorted(2..8)), vec!["b", "c", "d", "e"]);
+    assert_eq!(vals(sorted.range_sorted(0..3)), vec!["a"]);
+    assert_eq!(vals(sorted.range_sorted(5..2)), Vec::<&str>::new());
+}
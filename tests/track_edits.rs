@@ -0,0 +1,98 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+use v11::tracking::prelude::*;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        sprites::register();
+        uploads::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[add_tracker = "Uploads"]
+    [TEST/sprites] {
+        #[track_edits]
+        x: [f32; VecCol<f32>],
+        #[track_edits]
+        #[index]
+        layer: [u8; VecCol<u8>],
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+table! {
+    #[kind = "append"]
+    [TEST/uploads] {
+        event: [u16; VecCol<u16>],
+        sprite: [usize; VecCol<usize>],
+    }
+}
+
+struct Uploads;
+impl Tracker for Uploads {
+    type Foreign = sprites::Row;
+    fn sort(&self) -> bool { true }
+    fn handle(&self, universe: &Universe, event: Event, rows: SelectRows<sprites::Row>, _: &dyn event::Function) {
+        if event != event::MODIFY { return; }
+        let mut uploads = uploads::write(universe);
+        for row in rows.iter_or_all(None.into_iter()) {
+            uploads.push(uploads::Row { event: event.id, sprite: row.to_usize() });
+        }
+    }
+}
+
+fn populate(universe: &Universe) {
+    let mut sprites = sprites::write(universe);
+    for i in 0..5 {
+        sprites.push(sprites::Row { x: i as f32, layer: 0, name: "sprite" });
+    }
+    sprites.flush(universe, event::CREATE);
+}
+
+#[test]
+fn edits_are_recorded() {
+    let universe = &make_universe();
+    populate(universe);
+    let mut sprites = sprites::write(universe);
+    assert!(sprites.edited_rows().is_empty());
+    sprites.x[sprites::RowId::new(3)] += 1.0;
+    sprites.layer.set(sprites::RowId::new(1), 2);
+    sprites.name[sprites::RowId::new(4)] = "untracked";
+    assert_eq!(sprites.edited_rows(), vec![sprites::RowId::new(1), sprites::RowId::new(3)]);
+    assert_eq!(sprites.layer.find(2).count(), 1);
+    assert_eq!(sprites.take_edits().len(), 2);
+    assert!(sprites.edited_rows().is_empty());
+}
+
+#[test]
+fn modify_flush_reaches_trackers() {
+    let universe = &make_universe();
+    populate(universe);
+    {
+        let mut sprites = sprites::write(universe);
+        sprites.x[sprites::RowId::new(0)] = 10.0;
+        sprites.x[sprites::RowId::new(2)] = 12.0;
+        sprites.flush(universe, event::MODIFY);
+    }
+    {
+        // Nothing new was edited.
+        sprites::write(universe).flush(universe, event::MODIFY);
+    }
+    let uploads = uploads::read(universe);
+    let got: Vec<_> = uploads.iter().map(|u| (uploads.event[u], uploads.sprite[u])).collect();
+    assert_eq!(got, vec![(event::MODIFY.id, 0), (event::MODIFY.id, 2)]);
+}
@@ -0,0 +1,161 @@
+#[macro_use]
+extern crate v11;
+#[macro_use]
+extern crate v11_macros;
+
+use v11::Universe;
+use v11::event;
+use v11::transaction::Transactions;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        ships::register();
+        crew::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_derive(Clone, Debug, PartialEq)]
+    [TEST/ships] {
+        name: [&'static str; VecCol<&'static str>],
+        #[index]
+        tonnage: [u32; VecCol<u32>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    #[row_derive(Clone, Debug, PartialEq)]
+    [TEST/crew] {
+        #[unique]
+        name: [&'static str; VecCol<&'static str>],
+    }
+}
+
+fn fleet(universe: &Universe) -> Vec<(usize, &'static str, u32)> {
+    let ships = ships::read(universe);
+    ships.iter().map(|s| (s.to_usize(), ships.name[s], ships.tonnage[s])).collect()
+}
+
+fn populate(universe: &Universe) {
+    let mut ships = ships::write(universe);
+    ships.push(ships::Row { name: "Argo", tonnage: 50 });
+    let b = ships.push(ships::Row { name: "Beagle", tonnage: 240 });
+    ships.push(ships::Row { name: "Cutty Sark", tonnage: 963 });
+    ships.flush(universe, event::CREATE);
+    let mut ships = ships::write(universe);
+    ships.delete(b);
+    ships.flush(universe, event::DELETE);
+}
+
+#[test]
+fn rollback_restores_everything() {
+    let universe = &make_universe();
+    populate(universe);
+    let before = fleet(universe);
+    let mut tx = ships::write(universe).transaction();
+    let reused = tx.push(ships::Row { name: "Dawn Treader", tonnage: 80 });
+    assert_eq!(reused.to_usize(), 1);
+    tx.push(ships::Row { name: "Endeavour", tonnage: 366 });
+    tx.delete(ships::RowId::new(0));
+    let old = tx.replace(ships::RowId::new(2), ships::Row { name: "Fram", tonnage: 402 });
+    assert_eq!(old, ships::Row { name: "Cutty Sark", tonnage: 963 });
+    assert_eq!(tx.tonnage.find(963).count(), 0);
+    let ships_lock = tx.rollback();
+    assert_eq!(ships_lock.len(), 3);
+    assert_eq!(ships_lock.tonnage.find(963).count(), 1);
+    assert_eq!(ships_lock.tonnage.find(80).count(), 0);
+    // The reused slot is free again.
+    assert_eq!(ships_lock.next_pushed().to_usize(), 1);
+    // Nothing is left to flush.
+    drop(ships_lock);
+    assert_eq!(fleet(universe), before);
+}
+
+#[test]
+fn undo_redo_commit() {
+    let universe = &make_universe();
+    populate(universe);
+    let mut tx = ships::write(universe).transaction();
+    tx.push(ships::Row { name: "Golden Hind", tonnage: 150 });
+    tx.push(ships::Row { name: "Hispaniola", tonnage: 200 });
+    assert!(tx.undo());
+    assert_eq!(tx.len(), 3);
+    assert!(tx.redo());
+    assert!(!tx.redo());
+    assert_eq!(tx.tonnage.find(200).count(), 1);
+    tx.delete(ships::RowId::new(0));
+    assert!(tx.undo());
+    tx.commit(universe, event::CREATE);
+    let got: Vec<_> = fleet(universe).into_iter().map(|s| s.1).collect();
+    assert_eq!(got, vec!["Argo", "Golden Hind", "Cutty Sark", "Hispaniola"]);
+}
+
+#[test]
+fn grouped() {
+    let universe = &make_universe();
+    populate(universe);
+    let mut ships = ships::write(universe).transaction();
+    let mut crew = crew::write(universe).transaction();
+    ships.push(ships::Row { name: "Icarus", tonnage: 1 });
+    crew.push(crew::Row { name: "Ishmael" });
+    let mut group = Transactions::new();
+    group.add(ships);
+    group.add(crew);
+    group.rollback();
+    assert_eq!(crew::read(universe).len(), 0);
+    assert_eq!(fleet(universe).len(), 2);
+
+    let mut ships = ships::write(universe).transaction();
+    let mut crew = crew::write(universe).transaction();
+    ships.push(ships::Row { name: "Jenny", tonnage: 2 });
+    crew.push(crew::Row { name: "Jim" });
+    let mut group = Transactions::new();
+    group.add(ships);
+    group.add(crew);
+    group.commit(universe, event::CREATE);
+    assert_eq!(crew::read(universe).len(), 1);
+    assert_eq!(fleet(universe).len(), 3);
+}
+
+#[test]
+fn edit_columns() {
+    let universe = &make_universe();
+    populate(universe);
+    let argo = ships::RowId::new(0);
+    let mut tx = ships::write(universe).transaction();
+    tx.edit(argo).name[argo] = "Argo II";
+    tx.edit(argo).tonnage.set(argo, 55);
+    assert_eq!(tx.get_row(argo), ships::Row { name: "Argo II", tonnage: 55 });
+    assert!(tx.undo());
+    assert_eq!(tx.get_row(argo), ships::Row { name: "Argo II", tonnage: 50 });
+    assert!(tx.undo());
+    assert_eq!(tx.tonnage.find(55).count(), 0);
+    assert!(tx.redo());
+    assert!(tx.redo());
+    assert_eq!(tx.tonnage.find(55).collect::<Vec<_>>(), vec![argo]);
+    tx.commit(universe, event::CREATE);
+    assert_eq!(fleet(universe)[0], (0, "Argo II", 55));
+}
+
+#[test]
+fn replace_checks_unique() {
+    let universe = &make_universe();
+    let mut tx = crew::write(universe).transaction();
+    let jim = tx.push(crew::Row { name: "Jim" });
+    tx.push(crew::Row { name: "Silver" });
+    // A row may keep its own value.
+    tx.replace(jim, crew::Row { name: "Jim" });
+    let err = tx.try_replace(jim, crew::Row { name: "Silver" }).unwrap_err();
+    assert_eq!(err.column, "name");
+    assert_eq!(tx.name[jim], "Jim");
+    tx.commit(universe, event::CREATE);
+}
@@ -0,0 +1,195 @@
+#[macro_use] extern crate v11;
+#[macro_use] extern crate v11_macros;
+#[macro_use] extern crate serde_derive;
+
+use v11::Universe;
+use v11::event;
+use v11::map_index::UniqueViolation;
+use v11::tables::RestoreError;
+
+domain! { TEST }
+
+fn make_universe() -> Universe {
+    // Prevent lock clobbering breaking tests w/ threading.
+    use std::sync::{Once, ONCE_INIT};
+    static REGISTER: Once = ONCE_INIT;
+    REGISTER.call_once(|| {
+        TEST.register();
+        accounts::register();
+        ranks::register();
+        badges::register();
+    });
+    Universe::new(&[TEST])
+}
+
+table! {
+    #[kind = "consistent"]
+    [TEST/accounts] {
+        #[unique]
+        name: [&'static str; VecCol<&'static str>],
+        #[unique]
+        #[index = "hash"]
+        number: [u32; VecCol<u32>],
+    }
+}
+
+table! {
+    #[kind = "sorted"]
+    #[row_derive(Clone)]
+    [TEST/ranks] {
+        #[sort_key]
+        #[unique]
+        level: [u8; VecCol<u8>],
+        title: [&'static str; VecCol<&'static str>],
+    }
+}
+
+table! {
+    #[kind = "consistent"]
+    #[save]
+    #[row_derive(Clone)]
+    [TEST/badges] {
+        #[unique]
+        number: [u32; VecCol<u32>],
+    }
+}
+
+#[test]
+fn push_duplicate() {
+    let universe = &make_universe();
+    let mut accounts = accounts::write(universe);
+    let alice = accounts.push(accounts::Row { name: "alice", number: 1 });
+    let bob = accounts.try_push(accounts::Row { name: "bob", number: 2 }).unwrap();
+    let err = accounts.try_push(accounts::Row { name: "alice", number: 3 }).unwrap_err();
+    assert_eq!(err.column, "name");
+    assert_eq!(err.existing, Some(alice.to_usize()));
+    let err = accounts.try_push(accounts::Row { name: "carol", number: 2 }).unwrap_err();
+    assert_eq!(err.column, "number");
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts.get_by_name("bob"), Some(bob));
+    assert_eq!(accounts.get_by_number(1), Some(alice));
+    assert_eq!(accounts.get_by_name("carol"), None);
+    accounts.flush(universe, event::CREATE);
+
+    let mut accounts = accounts::write(universe);
+    accounts.delete(alice);
+    accounts.flush(universe, event::DELETE);
+
+    let mut accounts = accounts::write(universe);
+    assert_eq!(accounts.get_by_name("alice"), None);
+    accounts.push(accounts::Row { name: "alice", number: 1 });
+    accounts.flush(universe, event::CREATE);
+    assert!(accounts::read(universe).get_by_name("alice").is_some());
+}
+
+#[test]
+#[should_panic(expected = "unique column accounts.name already has that value")]
+fn push_duplicate_panics() {
+    let universe = &make_universe();
+    let mut accounts = accounts::write(universe);
+    accounts.push(accounts::Row { name: "alice", number: 1 });
+    accounts.push(accounts::Row { name: "alice", number: 2 });
+}
+
+#[test]
+fn deleted_rows_dont_count() {
+    let universe = &make_universe();
+    let mut badges = badges::write(universe);
+    let first = badges.push(badges::Row { number: 1 });
+    badges.flush(universe, event::CREATE);
+
+    let mut badges = badges::write(universe).transaction();
+    badges.delete(first);
+    assert_eq!(badges.get_by_number(1), None);
+    let again = badges.try_push(badges::Row { number: 1 }).unwrap();
+    assert_ne!(again, first);
+    assert_eq!(badges.get_by_number(1), Some(again));
+    let badges = badges.rollback();
+    assert_eq!(badges.get_by_number(1), Some(first));
+}
+
+#[test]
+fn merge() {
+    let universe = &make_universe();
+    let mut ranks = ranks::write(universe);
+    ranks.merge(vec![
+        ranks::Row { level: 3, title: "captain" },
+        ranks::Row { level: 1, title: "private" },
+    ]);
+    let sergeant = ranks.merge_in_a_single_row(ranks::Row { level: 2, title: "sergeant" });
+    assert_eq!(ranks.get_by_level(2), Some(sergeant));
+    assert_eq!(ranks.title[ranks.get_by_level(3).unwrap()], "captain");
+    let rows = vec![
+        ranks::Row { level: 4, title: "major" },
+        ranks::Row { level: 4, title: "colonel" },
+    ];
+    assert_eq!(ranks.check_unique_all(&rows).unwrap_err().existing, None);
+    ranks.flush(universe, event::CREATE);
+}
+
+#[test]
+fn try_merge_duplicate() {
+    let universe = &make_universe();
+    let mut ranks = ranks::write(universe);
+    ranks.merge(vec![ranks::Row { level: 1, title: "private" }]);
+    let rows = vec![
+        ranks::Row { level: 2, title: "sergeant" },
+        ranks::Row { level: 1, title: "recruit" },
+    ];
+    assert_eq!(ranks.try_merge(rows).unwrap_err().column, "level");
+    assert_eq!(ranks.len(), 1);
+    ranks.flush(universe, event::CREATE);
+}
+
+#[test]
+#[should_panic(expected = "unique column ranks.level already has that value")]
+fn merge_duplicate() {
+    let universe = &make_universe();
+    let mut ranks = ranks::write(universe);
+    ranks.merge(vec![ranks::Row { level: 1, title: "private" }]);
+    ranks.merge(vec![ranks::Row { level: 1, title: "recruit" }]);
+}
+
+#[test]
+fn push_many_duplicates() {
+    let universe = &make_universe();
+    let mut accounts = accounts::write(universe);
+    accounts.push(accounts::Row { name: "alice", number: 1 });
+    let rows = || vec![
+        accounts::Row { name: "bob", number: 2 },
+        accounts::Row { name: "carol", number: 1 },
+    ].into_iter();
+    assert_eq!(accounts.try_push_all(rows()).unwrap_err().column, "number");
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts.try_push_array(rows()).unwrap_err().column, "number");
+    assert_eq!(accounts.len(), 1);
+    let rows = vec![
+        accounts::Row { name: "bob", number: 2 },
+        accounts::Row { name: "carol", number: 3 },
+    ];
+    let range = accounts.push_array(rows.into_iter());
+    assert_eq!(range.len(), 2);
+    assert_eq!(accounts.get_by_number(3), Some(range.end.prev()));
+    accounts.flush(universe, event::CREATE);
+}
+
+#[test]
+fn restore_duplicate() {
+    let universe = &make_universe();
+    let elsewhere = &make_universe();
+    let mut badges = badges::write(universe);
+    badges.push_all((1..4).map(|number| badges::Row { number }));
+    badges.flush(universe, event::CREATE);
+    let mut badges = badges::write(elsewhere);
+    badges.push(badges::Row { number: 3 });
+    badges.flush(elsewhere, event::CREATE);
+
+    let extraction = badges::read(universe).extract_selection(v11::tracking::Select::All);
+    let err = badges::write(elsewhere).restore_extract(elsewhere, extraction, event::CREATE).unwrap_err();
+    assert_eq!(err, RestoreError::Unique(UniqueViolation {
+        table: badges::TABLE_NAME,
+        column: "number",
+        existing: Some(0),
+    }));
+    assert_eq!(badges::read(elsewhere).len(), 1);
+}